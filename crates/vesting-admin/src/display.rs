@@ -0,0 +1,92 @@
+// Plain-text printing of program state for the read commands
+
+use anchor_lang::prelude::Pubkey;
+use vesting_client::state::{Investor, TokenIco};
+use vesting_program::{SaleStats, WalletPositionsSummary};
+
+pub fn print_config(address: &Pubkey, c: &TokenIco) {
+    println!("TokenIco {address}");
+    println!("  authority:                 {}", c.authority);
+    println!("  reward_token_mint:         {}", c.reward_token_mint);
+    println!("  reward_token_decimals:     {}", c.reward_token_decimals);
+    println!("  sol_treasury:              {}", c.sol_treasury);
+    println!("  prize_treasury:            {}", c.prize_treasury);
+    println!("  ico_start_time:            {}", c.ico_start_time);
+    println!("  ico_end_time:              {}", c.ico_end_time);
+    println!("  tge_time:                  {}", c.tge_time);
+    println!("  min_amount:                {}", c.min_amount);
+    println!("  max_amount:                {}", c.max_amount);
+    println!("  tokens_per_sol:            {}", c.tokens_per_sol);
+    println!("  seconds_per_day:           {}", c.seconds_per_day);
+    println!("  paused:                    {}", c.paused);
+    println!("  sale_mode:                 {}", c.sale_mode);
+    println!("  reward_mode:               {}", c.reward_mode);
+    println!("  reward_supply_cap:         {}", c.reward_supply_cap);
+    println!("  total_allocated:           {}", c.total_allocated);
+    println!("  total_user_allocated:      {}", c.total_user_allocated);
+    println!("  total_prize_deposited:     {}", c.total_prize_deposited);
+    println!("  total_claimed:             {}", c.total_claimed);
+    println!("  claim_count:               {}", c.claim_count);
+    println!("  immediate_positions:       {}", c.immediate_positions);
+    println!("  linear_positions:          {}", c.linear_positions);
+    println!("  wallet_count:              {}", c.wallet_count);
+    println!("  sol_raised:                {}", c.sol_raised);
+    println!("  clawback_destination:      {}", c.clawback_destination);
+    println!(
+        "  position_transfers:        {}",
+        c.position_transfers_enabled
+    );
+    println!("  transfer_requires_admin:   {}", c.transfer_requires_admin);
+    println!("  keeper_fee_bps:            {}", c.keeper_fee_bps);
+    println!("  keeper_fee_cap:            {}", c.keeper_fee_cap);
+    println!("  referral_bonus_bps:        {}", c.referral_bonus_bps);
+    println!("  referral_proceeds_bps:     {}", c.referral_proceeds_bps);
+    println!("  referral_bonus_cap:        {}", c.referral_bonus_cap);
+}
+
+pub fn print_investor(address: &Pubkey, i: &Investor) {
+    println!("Investor {address}");
+    println!("  address:                   {}", i.address);
+    println!("  position_index:            {}", i.position_index);
+    println!("  vesting_type:              {}", i.vesting_type);
+    println!("  allocation:                {}", i.allocation);
+    println!("  claimed_tokens:            {}", i.claimed_tokens);
+    println!("  released_tokens:           {}", i.released_tokens);
+    println!("  cliff_end:                 {}", i.cliff_end);
+    println!("  last_claimed:              {}", i.last_claimed);
+    println!("  whitelisted_at:            {}", i.whitelisted_at);
+    println!("  blocked:                   {}", i.blocked);
+    println!("  block_reason:              {}", i.block_reason);
+    println!("  blocked_until:             {}", i.blocked_until);
+    println!("  revocable:                 {}", i.revocable);
+    println!("  revoked:                   {}", i.revoked);
+    println!("  revoked_at:                {}", i.revoked_at);
+    println!("  position_mint:             {}", i.position_mint);
+    println!("  claim_destination:         {}", i.claim_destination);
+}
+
+pub fn print_sale_stats(s: &SaleStats) {
+    println!("Sale statistics");
+    println!("  total_allocated:           {}", s.total_allocated);
+    println!("  total_user_allocated:      {}", s.total_user_allocated);
+    println!("  total_prize_deposited:     {}", s.total_prize_deposited);
+    println!("  total_claimed:             {}", s.total_claimed);
+    println!("  claim_count:               {}", s.claim_count);
+    println!("  position_count:            {}", s.position_count);
+    println!("  immediate_positions:       {}", s.immediate_positions);
+    println!("  linear_positions:          {}", s.linear_positions);
+    println!("  wallet_count:              {}", s.wallet_count);
+    println!("  sol_raised:                {}", s.sol_raised);
+    for raised in &s.token_raised {
+        println!("  raised {}: {}", raised.mint, raised.total_raised);
+    }
+}
+
+pub fn print_wallet_positions(s: &WalletPositionsSummary) {
+    println!("Positions of {}", s.wallet);
+    println!("  position_count:            {}", s.position_count);
+    println!("  positions_included:        {}", s.positions_included);
+    println!("  total_allocation:          {}", s.total_allocation);
+    println!("  total_claimed:             {}", s.total_claimed);
+    println!("  total_claimable:           {}", s.total_claimable);
+}
@@ -0,0 +1,746 @@
+// CSV bulk whitelist import, see `vesting-admin import-whitelist --help`.
+//
+// Rows are sent through `whitelist_investors_batch`, which skips positions that already hold
+// an allocation, so resending a batch whose outcome was lost never allocates twice. The
+// results file records every row; rows it marks as done are not looked at again on resume.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::path::Path;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::AccountMeta;
+use anchor_lang::system_program;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use vesting_client::rpc::{Keypair, RpcClient, Signer};
+use vesting_client::{accounts, events, instructions, pda, state};
+use vesting_math::{VESTING_TYPE_IMMEDIATE, VESTING_TYPE_LINEAR};
+use vesting_program::{
+    BatchResult, WhitelistEntry, BATCH_STATUS_INVALID, BATCH_STATUS_NOT_PROCESSED,
+    BATCH_STATUS_PROCESSED, BATCH_STATUS_SKIPPED,
+};
+
+// Whitelisted by this import, or found on chain with the same allocation
+const STATUS_WHITELISTED: &str = "whitelisted";
+const STATUS_ALREADY_WHITELISTED: &str = "already_whitelisted";
+// Rejected before sending, or by the program
+const STATUS_INVALID: &str = "invalid";
+// The position holds a different allocation, which this tool does not change
+const STATUS_CONFLICT: &str = "conflict";
+// The transaction failed or the batch ran out of compute; retried on the next run
+const STATUS_FAILED: &str = "failed";
+const STATUS_NOT_PROCESSED: &str = "not_processed";
+
+// CSV input: wallet,amount,vesting_type[,position_index][,revocable]
+#[derive(Deserialize)]
+struct Row {
+    wallet: String,
+    amount: String,
+    vesting_type: String,
+    #[serde(default)]
+    position_index: Option<String>,
+    #[serde(default)]
+    revocable: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Outcome {
+    line: u64,
+    wallet: String,
+    position_index: String,
+    amount: String,
+    vesting_type: String,
+    status: String,
+    signature: String,
+    error: String,
+}
+
+struct Entry {
+    line: u64,
+    wallet: Pubkey,
+    position_index: u32,
+    amount: u64,
+    vesting_type: u8,
+    revocable: bool,
+}
+
+impl Entry {
+    fn outcome(&self, status: &str, signature: &str, error: &str) -> Outcome {
+        Outcome {
+            line: self.line,
+            wallet: self.wallet.to_string(),
+            position_index: self.position_index.to_string(),
+            amount: self.amount.to_string(),
+            vesting_type: self.vesting_type.to_string(),
+            status: status.to_owned(),
+            signature: signature.to_owned(),
+            error: error.to_owned(),
+        }
+    }
+}
+
+pub struct Import<'a> {
+    pub rpc: &'a RpcClient,
+    pub signer: &'a Keypair,
+    pub batch_size: usize,
+    pub dry_run: bool,
+}
+
+impl Import<'_> {
+    pub fn run(&self, csv_path: &Path, results_path: &Path) -> Result<()> {
+        if self.batch_size == 0 {
+            bail!("--batch-size must be at least 1");
+        }
+        let (ico_config, _) = pda::config();
+        let data = self
+            .rpc
+            .get_account_data(&ico_config)?
+            .ok_or_else(|| anyhow!("ICO config {ico_config} does not exist"))?;
+        let config = state::token_ico(&data)?;
+
+        let done = read_done(results_path)?;
+        let mut results = Results::open(results_path, self.dry_run)?;
+
+        let file =
+            File::open(csv_path).with_context(|| format!("reading {}", csv_path.display()))?;
+        let (entries, rejected) = read_entries(file, config.min_amount, config.max_amount)?;
+        for outcome in rejected {
+            results.write(outcome)?;
+        }
+        let (resumed, pending): (Vec<Entry>, Vec<Entry>) = entries
+            .into_iter()
+            .partition(|entry| done.contains(&(entry.wallet, entry.position_index)));
+        let resumed = resumed.len();
+
+        // Compare with what is already on chain, so resumed runs skip finished positions
+        let addresses: Vec<Pubkey> = pending
+            .iter()
+            .map(|entry| pda::investor(&entry.wallet, entry.position_index).0)
+            .collect();
+        let existing = self.rpc.get_multiple_account_data(&addresses)?;
+        let mut to_send = Vec::new();
+        for (entry, data) in pending.into_iter().zip(existing) {
+            let investor = match data {
+                Some(data) => Some(state::investor(&data)?),
+                None => None,
+            };
+            match investor {
+                Some(investor) if investor.allocation == entry.amount => {
+                    results.write(entry.outcome(STATUS_ALREADY_WHITELISTED, "", ""))?;
+                }
+                Some(investor) if investor.allocation > 0 || investor.revoked => {
+                    let error = format!("position holds an allocation of {}", investor.allocation);
+                    results.write(entry.outcome(STATUS_CONFLICT, "", &error))?;
+                }
+                _ => to_send.push(entry),
+            }
+        }
+
+        // The program requires a wallet's positions to be created in index order
+        to_send.sort_by_key(|entry| (entry.wallet, entry.position_index));
+        let batches = batches(&to_send, self.batch_size);
+        let split_wallets = batches
+            .windows(2)
+            .any(|pair| pair[0][pair[0].len() - 1].wallet == pair[1][0].wallet);
+        if self.dry_run && split_wallets {
+            println!(
+                "Some wallets have more positions than --batch-size; their later batches are \
+                 simulated without the earlier ones and may report positions as invalid"
+            );
+        }
+        for batch in batches {
+            for outcome in self.send_batch(batch)? {
+                results.write(outcome)?;
+            }
+        }
+
+        println!(
+            "Import finished: {} whitelisted, {} already whitelisted, {} done in an earlier run",
+            results.count(STATUS_WHITELISTED),
+            results.count(STATUS_ALREADY_WHITELISTED),
+            resumed
+        );
+        let failed: usize = [
+            STATUS_INVALID,
+            STATUS_CONFLICT,
+            STATUS_FAILED,
+            STATUS_NOT_PROCESSED,
+        ]
+        .iter()
+        .map(|status| results.count(status))
+        .sum();
+        if failed > 0 {
+            println!(
+                "{failed} rows need attention, see {}",
+                results_path.display()
+            );
+        }
+        Ok(())
+    }
+
+    fn send_batch(&self, batch: &[Entry]) -> Result<Vec<Outcome>> {
+        let entries = batch
+            .iter()
+            .map(|entry| WhitelistEntry {
+                address: entry.wallet,
+                position_index: entry.position_index,
+                amount: entry.amount,
+                vesting_type: entry.vesting_type,
+                revocable: entry.revocable,
+            })
+            .collect();
+        let remaining_accounts = batch
+            .iter()
+            .flat_map(|entry| {
+                [
+                    AccountMeta::new(pda::investor(&entry.wallet, entry.position_index).0, false),
+                    AccountMeta::new(pda::wallet_positions(&entry.wallet).0, false),
+                ]
+            })
+            .collect();
+        let ix = instructions::whitelist_investors_batch(
+            accounts::WhitelistInvestorsBatch {
+                authority: self.signer.pubkey(),
+                ico_config: pda::config().0,
+                system_program: system_program::ID,
+            },
+            entries,
+            remaining_accounts,
+        );
+        let transaction = self.rpc.signed_transaction(&[ix], &[self.signer])?;
+
+        let (signature, err, return_data) = if self.dry_run {
+            let simulation = self.rpc.simulate_transaction(&transaction)?;
+            (String::new(), simulation.err, simulation.return_data)
+        } else {
+            match self.rpc.send_and_confirm_transaction(&transaction) {
+                Ok(signature) => {
+                    let landed = self
+                        .rpc
+                        .get_transaction(&signature)?
+                        .ok_or_else(|| anyhow!("transaction {signature} not found"))?;
+                    (signature, landed.err, landed.return_data)
+                }
+                Err(e) => {
+                    let error = e.to_string();
+                    return Ok(failed_batch(batch, "", &error));
+                }
+            }
+        };
+        if let Some(err) = err {
+            return Ok(failed_batch(batch, &signature, &err.to_string()));
+        }
+
+        let result: BatchResult = match return_data {
+            Some((program, data)) if program == vesting_client::PROGRAM_ID => {
+                events::decode_return(&data)?
+            }
+            _ => return Ok(failed_batch(batch, &signature, "no batch result returned")),
+        };
+        Ok(batch_outcomes(batch, &result, &signature))
+    }
+}
+
+// Valid, distinct entries of a CSV file, and the outcomes of the rows rejected
+fn read_entries<R: Read>(
+    input: R,
+    min_amount: u64,
+    max_amount: u64,
+) -> Result<(Vec<Entry>, Vec<Outcome>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(input);
+    let headers = reader.headers()?.clone();
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    let mut rejected = Vec::new();
+    for record in reader.records() {
+        let row = record.and_then(|record| {
+            let line = record.position().map_or(0, |position| position.line());
+            Ok((line, record.deserialize::<Row>(Some(&headers))?))
+        });
+        let (line, row) = match row {
+            Ok(row) => row,
+            Err(e) => {
+                let line = e.position().map_or(0, |position| position.line());
+                rejected.push(invalid_row(line, None, &e.to_string()));
+                continue;
+            }
+        };
+        let entry = match parse_row(line, &row, min_amount, max_amount) {
+            Ok(entry) => entry,
+            Err(e) => {
+                rejected.push(invalid_row(line, Some(&row), &e));
+                continue;
+            }
+        };
+        if !seen.insert((entry.wallet, entry.position_index)) {
+            rejected.push(entry.outcome(STATUS_INVALID, "", "duplicate wallet and position"));
+            continue;
+        }
+        entries.push(entry);
+    }
+    Ok((entries, rejected))
+}
+
+// Outcome of each entry of a batch that landed, from the statuses the program returned
+fn batch_outcomes(batch: &[Entry], result: &BatchResult, signature: &str) -> Vec<Outcome> {
+    batch
+        .iter()
+        .zip(&result.statuses)
+        .map(|(entry, status)| match *status {
+            BATCH_STATUS_PROCESSED => entry.outcome(STATUS_WHITELISTED, signature, ""),
+            BATCH_STATUS_SKIPPED => entry.outcome(
+                STATUS_CONFLICT,
+                signature,
+                "position was allocated before this batch landed",
+            ),
+            BATCH_STATUS_INVALID => entry.outcome(
+                STATUS_INVALID,
+                signature,
+                "rejected by the program (amount, schedule or position index)",
+            ),
+            BATCH_STATUS_NOT_PROCESSED => {
+                entry.outcome(STATUS_NOT_PROCESSED, signature, "batch ran out of compute")
+            }
+            other => entry.outcome(STATUS_FAILED, signature, &format!("status {other}")),
+        })
+        .collect()
+}
+
+fn parse_row(line: u64, row: &Row, min_amount: u64, max_amount: u64) -> Result<Entry, String> {
+    let wallet: Pubkey = row
+        .wallet
+        .parse()
+        .map_err(|_| format!("invalid wallet {}", row.wallet))?;
+    let amount: u64 = row
+        .amount
+        .parse()
+        .map_err(|_| format!("invalid amount {}", row.amount))?;
+    if amount < min_amount || amount > max_amount {
+        return Err(format!(
+            "amount {amount} outside {min_amount}..={max_amount}"
+        ));
+    }
+    let vesting_type = match row.vesting_type.to_ascii_lowercase().as_str() {
+        "immediate" => VESTING_TYPE_IMMEDIATE,
+        "linear" => VESTING_TYPE_LINEAR,
+        id => match id.parse() {
+            Ok(id) if id == VESTING_TYPE_IMMEDIATE || id == VESTING_TYPE_LINEAR => id,
+            _ => return Err(format!("unknown vesting schedule {}", row.vesting_type)),
+        },
+    };
+    let position_index = match row.position_index.as_deref() {
+        None | Some("") => 0,
+        Some(index) => index
+            .parse()
+            .map_err(|_| format!("invalid position_index {index}"))?,
+    };
+    let revocable = match row.revocable.as_deref() {
+        None | Some("") => false,
+        Some(flag) => flag
+            .to_ascii_lowercase()
+            .parse()
+            .map_err(|_| format!("invalid revocable {flag}"))?,
+    };
+    Ok(Entry {
+        line,
+        wallet,
+        position_index,
+        amount,
+        vesting_type,
+        revocable,
+    })
+}
+
+fn invalid_row(line: u64, row: Option<&Row>, error: &str) -> Outcome {
+    let field = |value: Option<&str>| value.unwrap_or_default().to_owned();
+    Outcome {
+        line,
+        wallet: field(row.map(|row| row.wallet.as_str())),
+        position_index: field(row.and_then(|row| row.position_index.as_deref())),
+        amount: field(row.map(|row| row.amount.as_str())),
+        vesting_type: field(row.map(|row| row.vesting_type.as_str())),
+        status: STATUS_INVALID.to_owned(),
+        signature: String::new(),
+        error: error.to_owned(),
+    }
+}
+
+// Batches of at most `batch_size` entries that keep a wallet's positions together, so a
+// simulated batch never depends on positions created by an earlier one. Only a wallet with
+// more positions than `batch_size` is split. `entries` must be sorted by wallet.
+fn batches(entries: &[Entry], batch_size: usize) -> Vec<&[Entry]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    while end < entries.len() {
+        let wallet = entries[end].wallet;
+        let group_end = end
+            + entries[end..]
+                .iter()
+                .take_while(|entry| entry.wallet == wallet)
+                .count();
+        if group_end - start > batch_size && end > start {
+            batches.push(&entries[start..end]);
+            start = end;
+        }
+        while group_end - start > batch_size {
+            batches.push(&entries[start..start + batch_size]);
+            start += batch_size;
+        }
+        end = group_end;
+    }
+    if end > start {
+        batches.push(&entries[start..end]);
+    }
+    batches
+}
+
+fn failed_batch(batch: &[Entry], signature: &str, error: &str) -> Vec<Outcome> {
+    batch
+        .iter()
+        .map(|entry| entry.outcome(STATUS_FAILED, signature, error))
+        .collect()
+}
+
+// Positions a previous run whitelisted or found whitelisted
+fn read_done(results_path: &Path) -> Result<HashSet<(Pubkey, u32)>> {
+    let mut done = HashSet::new();
+    if !results_path.exists() {
+        return Ok(done);
+    }
+    let mut reader = csv::Reader::from_path(results_path)
+        .with_context(|| format!("reading {}", results_path.display()))?;
+    for outcome in reader.deserialize::<Outcome>() {
+        let outcome = outcome?;
+        if outcome.status != STATUS_WHITELISTED && outcome.status != STATUS_ALREADY_WHITELISTED {
+            continue;
+        }
+        if let (Ok(wallet), Ok(index)) = (outcome.wallet.parse(), outcome.position_index.parse()) {
+            done.insert((wallet, index));
+        }
+    }
+    Ok(done)
+}
+
+// Appends to the results file and flushes after every row, so an interrupted run loses at
+// most the batch in flight. Dry runs print the rows instead.
+struct Results {
+    writer: Option<csv::Writer<File>>,
+    counts: HashMap<String, usize>,
+}
+
+impl Results {
+    fn open(path: &Path, dry_run: bool) -> Result<Self> {
+        let writer = if dry_run {
+            None
+        } else {
+            let exists = path.exists() && path.metadata()?.len() > 0;
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("opening {}", path.display()))?;
+            Some(
+                csv::WriterBuilder::new()
+                    .has_headers(!exists)
+                    .from_writer(file),
+            )
+        };
+        Ok(Results {
+            writer,
+            counts: HashMap::new(),
+        })
+    }
+
+    fn write(&mut self, outcome: Outcome) -> Result<()> {
+        *self.counts.entry(outcome.status.clone()).or_default() += 1;
+        match &mut self.writer {
+            Some(writer) => {
+                writer.serialize(&outcome)?;
+                writer.flush()?;
+            }
+            None => println!(
+                "line {}: {} #{} {} -> {} {}",
+                outcome.line,
+                outcome.wallet,
+                outcome.position_index,
+                outcome.amount,
+                outcome.status,
+                outcome.error
+            ),
+        }
+        Ok(())
+    }
+
+    fn count(&self, status: &str) -> usize {
+        self.counts.get(status).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLET: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+
+    fn row(amount: &str, vesting_type: &str, index: Option<&str>, revocable: Option<&str>) -> Row {
+        Row {
+            wallet: WALLET.to_owned(),
+            amount: amount.to_owned(),
+            vesting_type: vesting_type.to_owned(),
+            position_index: index.map(str::to_owned),
+            revocable: revocable.map(str::to_owned),
+        }
+    }
+
+    fn parse(row: &Row) -> Result<Entry, String> {
+        parse_row(7, row, 100, 1_000)
+    }
+
+    // A results file of its own under the temp directory, removed when dropped
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "vesting-admin-{}-{name}.results.csv",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn entry(position_index: u32) -> Entry {
+        wallet_entry(WALLET.parse().unwrap(), position_index)
+    }
+
+    fn wallet_entry(wallet: Pubkey, position_index: u32) -> Entry {
+        Entry {
+            line: position_index as u64 + 2,
+            wallet,
+            position_index,
+            amount: 500,
+            vesting_type: VESTING_TYPE_LINEAR,
+            revocable: false,
+        }
+    }
+
+    #[test]
+    fn amounts_are_bounded_by_the_buy_limits() {
+        assert_eq!(parse(&row("100", "0", None, None)).unwrap().amount, 100);
+        assert_eq!(parse(&row("1000", "0", None, None)).unwrap().amount, 1_000);
+        assert_eq!(
+            parse(&row("99", "0", None, None)).err().unwrap(),
+            "amount 99 outside 100..=1000"
+        );
+        assert!(parse(&row("1001", "0", None, None)).is_err());
+        assert!(parse(&row("-5", "0", None, None)).is_err());
+        assert!(parse(&row("1.5", "0", None, None)).is_err());
+    }
+
+    #[test]
+    fn schedules_are_named_or_numbered() {
+        for (name, expected) in [
+            ("immediate", VESTING_TYPE_IMMEDIATE),
+            ("Immediate", VESTING_TYPE_IMMEDIATE),
+            ("0", VESTING_TYPE_IMMEDIATE),
+            ("LINEAR", VESTING_TYPE_LINEAR),
+            ("1", VESTING_TYPE_LINEAR),
+        ] {
+            assert_eq!(
+                parse(&row("500", name, None, None)).unwrap().vesting_type,
+                expected
+            );
+        }
+        for name in ["2", "255", "monthly", ""] {
+            assert_eq!(
+                parse(&row("500", name, None, None)).err().unwrap(),
+                format!("unknown vesting schedule {name}")
+            );
+        }
+    }
+
+    #[test]
+    fn optional_columns_default_to_position_zero_and_not_revocable() {
+        let entry = parse(&row("500", "linear", None, None)).unwrap();
+        assert_eq!(
+            (entry.position_index, entry.revocable, entry.line),
+            (0, false, 7)
+        );
+        let entry = parse(&row("500", "linear", Some(""), Some(""))).unwrap();
+        assert_eq!((entry.position_index, entry.revocable), (0, false));
+
+        let entry = parse(&row("500", "linear", Some("3"), Some("TRUE"))).unwrap();
+        assert_eq!((entry.position_index, entry.revocable), (3, true));
+
+        assert!(parse(&row("500", "linear", Some("-1"), None)).is_err());
+        assert!(parse(&row("500", "linear", None, Some("yes"))).is_err());
+
+        let mut bad_wallet = row("500", "linear", None, None);
+        bad_wallet.wallet = "not-a-key".to_owned();
+        assert_eq!(
+            parse(&bad_wallet).err().unwrap(),
+            "invalid wallet not-a-key"
+        );
+    }
+
+    #[test]
+    fn duplicate_and_malformed_rows_are_rejected() {
+        let csv = format!(
+            "wallet,amount,vesting_type,position_index,revocable\n\
+             {WALLET},500,linear,0,false\n\
+             {WALLET},600,0\n\
+             {WALLET},700,immediate,1\n\
+             {WALLET},5,linear,2\n\
+             {WALLET}\n"
+        );
+        let (entries, rejected) = read_entries(csv.as_bytes(), 100, 1_000).unwrap();
+
+        let accepted: Vec<(u64, u32)> = entries
+            .iter()
+            .map(|entry| (entry.line, entry.position_index))
+            .collect();
+        assert_eq!(accepted, vec![(2, 0), (4, 1)]);
+
+        let rejected: Vec<(u64, &str)> = rejected
+            .iter()
+            .map(|outcome| (outcome.line, outcome.error.as_str()))
+            .collect();
+        assert_eq!(rejected[0], (3, "duplicate wallet and position"));
+        assert_eq!(rejected[1], (5, "amount 5 outside 100..=1000"));
+        assert_eq!(rejected[2].0, 6);
+        assert_eq!(rejected.len(), 3);
+    }
+
+    #[test]
+    fn batch_statuses_map_to_outcomes() {
+        let batch: Vec<Entry> = (0..5).map(entry).collect();
+        let result = BatchResult {
+            processed: 1,
+            skipped: 1,
+            invalid: 1,
+            not_processed: 1,
+            statuses: vec![
+                BATCH_STATUS_PROCESSED,
+                BATCH_STATUS_SKIPPED,
+                BATCH_STATUS_INVALID,
+                BATCH_STATUS_NOT_PROCESSED,
+                42,
+            ],
+        };
+        let outcomes = batch_outcomes(&batch, &result, "sig");
+        let statuses: Vec<&str> = outcomes.iter().map(|o| o.status.as_str()).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                STATUS_WHITELISTED,
+                STATUS_CONFLICT,
+                STATUS_INVALID,
+                STATUS_NOT_PROCESSED,
+                STATUS_FAILED
+            ]
+        );
+        assert!(outcomes.iter().all(|o| o.signature == "sig"));
+        assert_eq!(outcomes[4].error, "status 42");
+    }
+
+    #[test]
+    fn results_round_trip_and_resume() {
+        let file = TempFile::new("round-trip");
+        assert!(read_done(&file.0).unwrap().is_empty());
+
+        let mut results = Results::open(&file.0, false).unwrap();
+        results
+            .write(entry(0).outcome(STATUS_WHITELISTED, "sig", ""))
+            .unwrap();
+        results
+            .write(entry(1).outcome(STATUS_FAILED, "", "timeout"))
+            .unwrap();
+        results
+            .write(entry(2).outcome(STATUS_CONFLICT, "", ""))
+            .unwrap();
+        assert_eq!(results.count(STATUS_WHITELISTED), 1);
+        assert_eq!(results.count(STATUS_NOT_PROCESSED), 0);
+        drop(results);
+
+        // A resumed run appends without repeating the header
+        let mut results = Results::open(&file.0, false).unwrap();
+        results
+            .write(entry(1).outcome(STATUS_WHITELISTED, "sig2", ""))
+            .unwrap();
+        results
+            .write(entry(3).outcome(STATUS_ALREADY_WHITELISTED, "", ""))
+            .unwrap();
+        results.write(invalid_row(9, None, "bad row")).unwrap();
+        drop(results);
+
+        let text = std::fs::read_to_string(&file.0).unwrap();
+        assert_eq!(text.matches("line,wallet").count(), 1);
+        assert_eq!(text.lines().count(), 7);
+
+        let wallet: Pubkey = WALLET.parse().unwrap();
+        let done = read_done(&file.0).unwrap();
+        assert_eq!(done, HashSet::from([(wallet, 0), (wallet, 1), (wallet, 3)]));
+    }
+
+    #[test]
+    fn dry_runs_write_no_results_file() {
+        let file = TempFile::new("dry-run");
+        let mut results = Results::open(&file.0, true).unwrap();
+        results
+            .write(entry(0).outcome(STATUS_WHITELISTED, "", ""))
+            .unwrap();
+        assert_eq!(results.count(STATUS_WHITELISTED), 1);
+        assert!(!file.0.exists());
+    }
+
+    #[test]
+    fn batches_keep_a_wallet_together() {
+        let mut wallets = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        wallets.sort();
+        let [a, b, c] = wallets;
+        let entries: Vec<Entry> = [(a, 0), (a, 1), (a, 2), (b, 0), (b, 1), (c, 0)]
+            .into_iter()
+            .map(|(wallet, index)| wallet_entry(wallet, index))
+            .collect();
+        let shape = |batch_size| -> Vec<Vec<(Pubkey, u32)>> {
+            batches(&entries, batch_size)
+                .iter()
+                .map(|batch| batch.iter().map(|e| (e.wallet, e.position_index)).collect())
+                .collect()
+        };
+
+        assert_eq!(
+            shape(4),
+            vec![vec![(a, 0), (a, 1), (a, 2)], vec![(b, 0), (b, 1), (c, 0)]]
+        );
+        assert_eq!(shape(8).len(), 1);
+
+        // Only a wallet with more positions than fit in a batch is split
+        assert_eq!(
+            shape(2),
+            vec![
+                vec![(a, 0), (a, 1)],
+                vec![(a, 2)],
+                vec![(b, 0), (b, 1)],
+                vec![(c, 0)],
+            ]
+        );
+    }
+}
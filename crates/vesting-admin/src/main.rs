@@ -0,0 +1,1058 @@
+//! Admin CLI for the vesting program.
+//!
+//! Mutating commands are signed by `--keypair` (the ICO authority, or the SOL treasury for
+//! `withdraw-sol`). With `--dry-run` they are simulated instead of sent, and the logs,
+//! decoded events and return data are printed.
+
+mod display;
+mod import;
+
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, AccountDeserialize, AnchorDeserialize};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use anchor_spl::token_interface::TokenAccount;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use vesting_client::rpc::{read_keypair_file, Keypair, RpcClient, Signer, DEFAULT_URL};
+use vesting_client::{accounts, events, instructions, pda, state, PROGRAM_ID};
+use vesting_program::BonusTier;
+
+#[derive(Parser)]
+#[command(
+    name = "vesting-admin",
+    version,
+    about = "Admin CLI for the vesting program"
+)]
+struct Cli {
+    /// JSON-RPC endpoint
+    #[arg(long, short = 'u', env = "VESTING_RPC_URL", default_value = DEFAULT_URL, global = true)]
+    url: String,
+
+    /// Signer keypair file [default: ~/.config/solana/id.json]
+    #[arg(long, short = 'k', env = "VESTING_KEYPAIR", global = true)]
+    keypair: Option<PathBuf>,
+
+    /// Simulate mutating commands and print their logs, events and return data
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the ICO configuration
+    Initialize {
+        #[arg(long)]
+        reward_mint: Pubkey,
+        #[arg(long)]
+        sol_treasury: Pubkey,
+        /// Reward token account owned by the vault authority [default: its ATA, created if missing]
+        #[arg(long)]
+        reward_treasury: Option<Pubkey>,
+        /// ICO start, unix seconds
+        #[arg(long)]
+        start: u64,
+        /// ICO end, unix seconds
+        #[arg(long)]
+        end: u64,
+        /// Token generation event, unix seconds
+        #[arg(long)]
+        tge: u64,
+        /// Minimum purchase, whole tokens
+        #[arg(long)]
+        min_buy: u64,
+        /// Maximum purchase, whole tokens
+        #[arg(long)]
+        max_buy: u64,
+        #[arg(long)]
+        tokens_per_sol: u64,
+        #[arg(long, default_value_t = 86_400)]
+        seconds_per_day: u64,
+        /// 0 = transfer from the treasury, 1 = mint on claim
+        #[arg(long, default_value_t = 0)]
+        reward_mode: u8,
+        /// Reward token base units, 0 = uncapped (mint mode only)
+        #[arg(long, default_value_t = 0)]
+        reward_supply_cap: u64,
+    },
+    /// Whitelist the positions listed in a CSV file, resuming an earlier run
+    ///
+    /// Columns: wallet,amount,vesting_type[,position_index][,revocable]. The vesting type is 0,
+    /// 1, immediate or linear; amounts are whole tokens. Positions already holding the same
+    /// allocation are skipped.
+    ImportWhitelist {
+        csv: PathBuf,
+        /// Results file, appended to and read back on resume [default: <CSV>.results.csv]
+        #[arg(long)]
+        results: Option<PathBuf>,
+        /// Entries per whitelist_investors_batch transaction
+        #[arg(long, default_value_t = 8)]
+        batch_size: usize,
+    },
+    /// Whitelist a wallet position with an allocation
+    Whitelist {
+        wallet: Pubkey,
+        /// Allocation, whole tokens
+        amount: u64,
+        /// 0 = Immediate, 1 = Linear
+        #[arg(long)]
+        vesting_type: u8,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        /// Allow the authority to revoke the unvested part later
+        #[arg(long)]
+        revocable: bool,
+    },
+    /// Set the ICO start and end, unix seconds
+    SetDates { start: u64, end: u64 },
+    /// Set the token generation event, unix seconds
+    SetTge { time: u64 },
+    /// Set the length of a vesting day in seconds
+    SetSecondsPerDay { seconds: u64 },
+    /// Pause purchases and claims
+    Pause,
+    /// Resume purchases and claims
+    Unpause,
+    /// Block a position from claiming
+    Block {
+        wallet: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        #[arg(long, default_value_t = 0)]
+        reason: u8,
+        /// Unix seconds after which the block lapses [default: until unblocked]
+        #[arg(long)]
+        expires_at: Option<u64>,
+    },
+    /// Lift the block of a position
+    Unblock {
+        wallet: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+    },
+    /// Remove a position and its allocation
+    Remove {
+        wallet: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+    },
+    /// Revoke a revocable grant, sending its unvested tokens to the clawback destination
+    Revoke {
+        wallet: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        /// Pay the vested, unclaimed tokens now, to the ATA of the position's claim destination
+        /// (created if missing)
+        #[arg(long)]
+        pay_out_vested: bool,
+        /// Token account holding the position token, needed to pay out a tokenized position
+        #[arg(long)]
+        position_token_account: Option<Pubkey>,
+        /// Reward token account of the clawback destination [default: its ATA]
+        #[arg(long)]
+        clawback_account: Option<Pubkey>,
+    },
+    /// Change the wallet receiving the unvested tokens of revoked grants
+    SetClawbackDestination { wallet: Pubkey },
+    /// Deposit reward tokens into the treasury
+    Deposit {
+        /// Reward token base units
+        amount: u64,
+        /// Source token account [default: the signer's ATA]
+        #[arg(long)]
+        from: Option<Pubkey>,
+    },
+    /// Withdraw SOL proceeds, signed by the SOL treasury
+    WithdrawSol {
+        /// Lamports
+        amount: u64,
+        recipient: Pubkey,
+    },
+    /// Withdraw reward tokens from the treasury
+    WithdrawPrize {
+        /// Reward token base units
+        amount: u64,
+        recipient_token_account: Pubkey,
+    },
+    /// Withdraw proceeds of a payment token
+    WithdrawPayment {
+        mint: Pubkey,
+        /// Payment token base units
+        amount: u64,
+        recipient_token_account: Pubkey,
+    },
+    /// Change the wallet receiving SOL proceeds
+    SetTreasury { wallet: Pubkey },
+    /// Replace the reward token mint
+    SetRewardMint { mint: Pubkey },
+    /// Accept an SPL or Token-2022 mint as payment
+    AddPaymentMint {
+        mint: Pubkey,
+        /// Reward tokens per whole payment token; ignored for wSOL, which uses tokens_per_sol
+        #[arg(long, default_value_t = 0)]
+        tokens_per_token: u64,
+        /// Signer-owned token account receiving the proceeds [default: the signer's ATA]
+        #[arg(long)]
+        treasury: Option<Pubkey>,
+        /// Unwrap wSOL proceeds into the SOL treasury (wSOL only)
+        #[arg(long)]
+        unwrap_to_sol_vault: bool,
+    },
+    /// Change a payment mint; options left out keep their current value
+    UpdatePaymentMint {
+        mint: Pubkey,
+        #[arg(long)]
+        tokens_per_token: Option<u64>,
+        #[arg(long)]
+        enabled: Option<bool>,
+        #[arg(long)]
+        unwrap_to_sol_vault: Option<bool>,
+        /// Signer-owned token account receiving the proceeds
+        #[arg(long)]
+        treasury: Option<Pubkey>,
+    },
+    /// Set the fee keepers earn on crank claims
+    SetKeeperFee {
+        /// Basis points of each claim
+        fee_bps: u16,
+        /// Reward token base units per claim, 0 = uncapped
+        #[arg(long, default_value_t = 0)]
+        cap: u64,
+    },
+    /// Replace the purchase bonus tiers
+    SetBonusTiers {
+        /// Volume tier as THRESHOLD:BPS, threshold in tokens bought; repeatable
+        #[arg(long = "volume-tier", value_parser = parse_bonus_tier)]
+        volume_tiers: Vec<BonusTier>,
+        /// Time tier as THRESHOLD:BPS, threshold in seconds since the ICO start; repeatable
+        #[arg(long = "time-tier", value_parser = parse_bonus_tier)]
+        time_tiers: Vec<BonusTier>,
+        /// Vesting type of bonus tokens [default: the purchase's]
+        #[arg(long)]
+        bonus_vesting_type: Option<u8>,
+    },
+    /// Set the referral bonus and proceeds share
+    SetReferralConfig {
+        /// Bonus tokens for the referrer, basis points of the purchase
+        bonus_bps: u16,
+        /// Share of each payment sent to the referrer, basis points
+        proceeds_bps: u16,
+        /// Bonus tokens per referrer, 0 = uncapped
+        #[arg(long, default_value_t = 0)]
+        bonus_cap: u64,
+    },
+    /// Allow or forbid position transfers; leaving out --enabled disables them
+    SetPositionTransferConfig {
+        #[arg(long)]
+        enabled: bool,
+        /// Transfers must be co-signed by the authority
+        #[arg(long)]
+        require_admin_cosign: bool,
+    },
+    /// Switch the sale to a Dutch auction; prices in lamports per whole token
+    ConfigureAuction {
+        start_price: u64,
+        floor_price: u64,
+        /// Whole tokens for sale
+        supply: u64,
+    },
+    /// Fix the auction's clearing price and send its proceeds to the SOL treasury
+    SettleAuction,
+    /// Switch the sale to commit-then-settle with a cap in whole tokens
+    ConfigureProRataSale { token_cap: u64 },
+    /// Fix the total demand of a pro-rata sale after it ends
+    FinalizeSale,
+    /// Hand the ICO authority to another wallet
+    TransferOwnership { new_authority: Pubkey },
+    /// Give up the ICO authority for good
+    RenounceOwnership,
+    /// Print the TokenIco configuration
+    ShowConfig,
+    /// Print an Investor position
+    ShowInvestor {
+        wallet: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+    },
+    /// Print the sale statistics (simulates get_sale_stats)
+    Stats {
+        /// Payment mints to include amounts raised for
+        #[arg(long = "payment-mint")]
+        payment_mints: Vec<Pubkey>,
+    },
+    /// Print the totals across a wallet's positions (simulates get_wallet_positions)
+    ShowPositions { wallet: Pubkey },
+}
+
+struct Admin {
+    rpc: RpcClient,
+    keypair: Option<PathBuf>,
+    dry_run: bool,
+}
+
+impl Admin {
+    fn signer(&self) -> Result<Keypair> {
+        let path = match &self.keypair {
+            Some(path) => path.clone(),
+            None => {
+                let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+                PathBuf::from(home).join(".config/solana/id.json")
+            }
+        };
+        read_keypair_file(&path).map_err(|e| anyhow!("reading keypair {}: {e}", path.display()))
+    }
+
+    fn config(&self) -> Result<state::TokenIco> {
+        let (address, _) = pda::config();
+        let data = self
+            .rpc
+            .get_account_data(&address)?
+            .ok_or_else(|| anyhow!("ICO config {address} does not exist"))?;
+        Ok(state::token_ico(&data)?)
+    }
+
+    fn token_program_of(&self, mint: &Pubkey) -> Result<Pubkey> {
+        self.rpc
+            .get_account_owner(mint)?
+            .ok_or_else(|| anyhow!("mint {mint} does not exist"))
+    }
+
+    fn payment_mint(&self, mint: &Pubkey) -> Result<state::PaymentMint> {
+        let data = self
+            .rpc
+            .get_account_data(&pda::payment_mint(mint).0)?
+            .ok_or_else(|| anyhow!("{mint} is not a payment mint"))?;
+        Ok(state::decode_account(&data)?)
+    }
+
+    // Wallet receiving the claims of a position: the holder of a tokenized position, read from
+    // `position_token_account`, otherwise the beneficiary or the destination it designated
+    fn claim_destination(
+        &self,
+        wallet: &Pubkey,
+        index: u32,
+        position_token_account: Option<&Pubkey>,
+    ) -> Result<Pubkey> {
+        let address = pda::investor(wallet, index).0;
+        let data = self
+            .rpc
+            .get_account_data(&address)?
+            .ok_or_else(|| anyhow!("position {index} of {wallet} does not exist"))?;
+        let investor = state::investor(&data)?;
+        if investor.position_mint == Pubkey::default() {
+            return Ok(if investor.claim_destination == Pubkey::default() {
+                investor.address
+            } else {
+                investor.claim_destination
+            });
+        }
+
+        let account = position_token_account
+            .ok_or_else(|| anyhow!("the position is tokenized, pass --position-token-account"))?;
+        let data = self
+            .rpc
+            .get_account_data(account)?
+            .ok_or_else(|| anyhow!("token account {account} does not exist"))?;
+        let holding = TokenAccount::try_deserialize(&mut data.as_slice())
+            .with_context(|| format!("decoding token account {account}"))?;
+        if holding.mint != investor.position_mint || holding.amount != 1 {
+            bail!("{account} does not hold the position token");
+        }
+        Ok(holding.owner)
+    }
+
+    // Simulate a view instruction and decode what it returns
+    fn view<R: AnchorDeserialize>(
+        &self,
+        name: &str,
+        instruction: Instruction,
+        signer: &Keypair,
+    ) -> Result<R> {
+        let transaction = self.rpc.signed_transaction(&[instruction], &[signer])?;
+        let simulation = self.rpc.simulate_transaction(&transaction)?;
+        if let Some(err) = simulation.err {
+            bail!("{name} failed: {err}");
+        }
+        let (_, data) = simulation
+            .return_data
+            .ok_or_else(|| anyhow!("{name} returned no data"))?;
+        Ok(events::decode_return(&data)?)
+    }
+
+    // Send the instructions signed by `signer`, or simulate them with --dry-run
+    fn execute<R: AnchorDeserialize + Debug>(
+        &self,
+        instructions: Vec<Instruction>,
+        signer: &Keypair,
+    ) -> Result<()> {
+        let transaction = self.rpc.signed_transaction(&instructions, &[signer])?;
+        if !self.dry_run {
+            let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+            println!("Signature: {signature}");
+            return Ok(());
+        }
+
+        let simulation = self.rpc.simulate_transaction(&transaction)?;
+        match &simulation.err {
+            None => println!("Simulation succeeded"),
+            Some(err) => println!("Simulation failed: {err}"),
+        }
+        if let Some(units) = simulation.units_consumed {
+            println!("Compute units: {units}");
+        }
+        println!("Logs:");
+        for line in &simulation.logs {
+            println!("  {line}");
+        }
+        let decoded = events::parse_logs(&simulation.logs);
+        if !decoded.is_empty() {
+            println!("Events:");
+            for event in &decoded {
+                println!("  {event:?}");
+            }
+        }
+        if let Some((program, data)) = &simulation.return_data {
+            if *program == PROGRAM_ID {
+                match events::decode_return::<R>(data) {
+                    Ok(value) => println!("Return data: {value:#?}"),
+                    Err(e) => println!("Return data: {} bytes, undecodable: {e}", data.len()),
+                }
+            }
+        }
+        if simulation.err.is_some() {
+            bail!("simulation failed");
+        }
+        Ok(())
+    }
+
+    fn run(&self, command: Command) -> Result<()> {
+        let (ico_config, _) = pda::config();
+        let (vault_authority, _) = pda::vault_authority();
+        match command {
+            Command::Initialize {
+                reward_mint,
+                sol_treasury,
+                reward_treasury,
+                start,
+                end,
+                tge,
+                min_buy,
+                max_buy,
+                tokens_per_sol,
+                seconds_per_day,
+                reward_mode,
+                reward_supply_cap,
+            } => {
+                let signer = self.signer()?;
+                let token_program = self.token_program_of(&reward_mint)?;
+                let mut ixs = Vec::new();
+                let reward_token_treasury = match reward_treasury {
+                    Some(treasury) => treasury,
+                    None => {
+                        ixs.push(create_associated_token_account_idempotent(
+                            &signer.pubkey(),
+                            &vault_authority,
+                            &reward_mint,
+                            &token_program,
+                        ));
+                        get_associated_token_address_with_program_id(
+                            &vault_authority,
+                            &reward_mint,
+                            &token_program,
+                        )
+                    }
+                };
+                ixs.push(instructions::initialize(
+                    accounts::InitializeIco {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        reward_token_mint: reward_mint,
+                        sol_treasury,
+                        reward_token_treasury,
+                        vault_authority,
+                        system_program: system_program::ID,
+                        token_program,
+                        rent: sysvar::rent::ID,
+                    },
+                    start,
+                    end,
+                    tge,
+                    min_buy,
+                    max_buy,
+                    tokens_per_sol,
+                    seconds_per_day,
+                    reward_mode,
+                    reward_supply_cap,
+                ));
+                self.execute::<()>(ixs, &signer)
+            }
+            Command::Whitelist {
+                wallet,
+                amount,
+                vesting_type,
+                index,
+                revocable,
+            } => {
+                let signer = self.signer()?;
+                let ix = instructions::whitelist_investor_by_admin(
+                    accounts::InvestorEntry {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        investor_details: pda::investor(&wallet, index).0,
+                        wallet_positions: pda::wallet_positions(&wallet).0,
+                        system_program: system_program::ID,
+                    },
+                    wallet,
+                    index,
+                    amount,
+                    vesting_type,
+                    revocable,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::ImportWhitelist {
+                csv,
+                results,
+                batch_size,
+            } => {
+                let signer = self.signer()?;
+                let results = results.unwrap_or_else(|| csv.with_extension("results.csv"));
+                import::Import {
+                    rpc: &self.rpc,
+                    signer: &signer,
+                    batch_size,
+                    dry_run: self.dry_run,
+                }
+                .run(&csv, &results)
+            }
+            Command::SetDates { start, end } => {
+                let signer = self.signer()?;
+                let ix = instructions::set_ico_dates(set_ico_config(&signer), start, end);
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SetTge { time } => {
+                let signer = self.signer()?;
+                let ix = instructions::set_tge_date(set_ico_config(&signer), time);
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SetSecondsPerDay { seconds } => {
+                let signer = self.signer()?;
+                let ix = instructions::reset_seconds_per_day(set_ico_config(&signer), seconds);
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::Pause | Command::Unpause => {
+                let signer = self.signer()?;
+                let paused = matches!(command, Command::Pause);
+                let ix = instructions::set_paused(
+                    accounts::SetPaused {
+                        ico_config,
+                        authority: signer.pubkey(),
+                    },
+                    paused,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::Block {
+                wallet,
+                index,
+                reason,
+                expires_at,
+            } => {
+                let signer = self.signer()?;
+                let ix = instructions::block_investor(
+                    accounts::BlockInvestor {
+                        ico_config,
+                        investor_details: pda::investor(&wallet, index).0,
+                        authority: signer.pubkey(),
+                        system_program: system_program::ID,
+                    },
+                    wallet,
+                    index,
+                    reason,
+                    expires_at,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::Unblock { wallet, index } => {
+                let signer = self.signer()?;
+                let ix = instructions::unblock_investor(
+                    accounts::UnblockInvestor {
+                        ico_config,
+                        investor_details: pda::investor(&wallet, index).0,
+                        authority: signer.pubkey(),
+                    },
+                    wallet,
+                    index,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::Remove { wallet, index } => {
+                let signer = self.signer()?;
+                let ix = instructions::remove_investor(
+                    accounts::RemoveInvestor {
+                        ico_config,
+                        investor_details: pda::investor(&wallet, index).0,
+                        wallet_positions: pda::wallet_positions(&wallet).0,
+                        authority: signer.pubkey(),
+                        system_program: system_program::ID,
+                    },
+                    wallet,
+                    index,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::Revoke {
+                wallet,
+                index,
+                pay_out_vested,
+                position_token_account,
+                clawback_account,
+            } => {
+                let signer = self.signer()?;
+                let config = self.config()?;
+                let mint = config.reward_token_mint;
+                let token_program = self.token_program_of(&mint)?;
+                let mut ixs = Vec::new();
+                let investor_token_account = if pay_out_vested {
+                    let destination =
+                        self.claim_destination(&wallet, index, position_token_account.as_ref())?;
+                    ixs.push(create_associated_token_account_idempotent(
+                        &signer.pubkey(),
+                        &destination,
+                        &mint,
+                        &token_program,
+                    ));
+                    Some(get_associated_token_address_with_program_id(
+                        &destination,
+                        &mint,
+                        &token_program,
+                    ))
+                } else {
+                    None
+                };
+                let clawback_token_account = clawback_account.unwrap_or_else(|| {
+                    get_associated_token_address_with_program_id(
+                        &config.clawback_destination,
+                        &mint,
+                        &token_program,
+                    )
+                });
+                ixs.push(instructions::revoke_vesting(
+                    accounts::RevokeVesting {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        investor_details: pda::investor(&wallet, index).0,
+                        vault_authority,
+                        reward_token_treasury: config.prize_treasury,
+                        reward_token_mint: mint,
+                        clawback_token_account,
+                        investor_token_account,
+                        position_token_account,
+                        token_program,
+                    },
+                    wallet,
+                    index,
+                    pay_out_vested,
+                ));
+                self.execute::<()>(ixs, &signer)
+            }
+            Command::SetClawbackDestination { wallet } => {
+                let signer = self.signer()?;
+                let ix = instructions::set_clawback_destination(set_ico_config(&signer), wallet);
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::Deposit { amount, from } => {
+                let signer = self.signer()?;
+                let config = self.config()?;
+                let mint = config.reward_token_mint;
+                let token_program = self.token_program_of(&mint)?;
+                let authority_token_account = from.unwrap_or_else(|| {
+                    get_associated_token_address_with_program_id(
+                        &signer.pubkey(),
+                        &mint,
+                        &token_program,
+                    )
+                });
+                let ix = instructions::deposit_prize(
+                    accounts::DepositPrize {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        reward_token_mint: mint,
+                        authority_token_account,
+                        vault_authority,
+                        reward_token_treasury: config.prize_treasury,
+                        token_program,
+                    },
+                    amount,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::WithdrawSol { amount, recipient } => {
+                let signer = self.signer()?;
+                let ix = instructions::withdraw_sol(
+                    accounts::WithdrawSol {
+                        ico_config,
+                        sol_treasury: signer.pubkey(),
+                        recipient,
+                        system_program: system_program::ID,
+                    },
+                    amount,
+                    recipient,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::WithdrawPrize {
+                amount,
+                recipient_token_account,
+            } => {
+                let signer = self.signer()?;
+                let config = self.config()?;
+                let token_program = self.token_program_of(&config.reward_token_mint)?;
+                let ix = instructions::withdraw_prize_tokens(
+                    accounts::PrizeTokensTransfer {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        recipient_token_account,
+                        vault_authority,
+                        reward_token_treasury: config.prize_treasury,
+                        reward_token_mint: config.reward_token_mint,
+                        token_program,
+                        system_program: system_program::ID,
+                    },
+                    amount,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::WithdrawPayment {
+                mint,
+                amount,
+                recipient_token_account,
+            } => {
+                let signer = self.signer()?;
+                let (payment_mint, _) = pda::payment_mint(&mint);
+                let settings = self.payment_mint(&mint)?;
+                let token_program = self.token_program_of(&mint)?;
+                let ix = instructions::withdraw_payment_tokens(
+                    accounts::PaymentTokensTransfer {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        payment_mint,
+                        mint,
+                        recipient_token_account,
+                        treasury: settings.treasury,
+                        token_program,
+                    },
+                    amount,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SetTreasury { wallet } => {
+                let signer = self.signer()?;
+                let ix = instructions::reset_sol_treasure(accounts::UpdateTreasuryWallet {
+                    authority: signer.pubkey(),
+                    ico_config,
+                    new_wallet: wallet,
+                    system_program: system_program::ID,
+                });
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SetRewardMint { mint } => {
+                let signer = self.signer()?;
+                let ix = instructions::set_mint_address(
+                    accounts::SetMintAddress {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        mint_account: mint,
+                        vault_authority,
+                    },
+                    0,
+                    mint,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::AddPaymentMint {
+                mint,
+                tokens_per_token,
+                treasury,
+                unwrap_to_sol_vault,
+            } => {
+                let signer = self.signer()?;
+                let token_program = self.token_program_of(&mint)?;
+                let mut ixs = Vec::new();
+                let treasury = match treasury {
+                    Some(treasury) => treasury,
+                    None => {
+                        ixs.push(create_associated_token_account_idempotent(
+                            &signer.pubkey(),
+                            &signer.pubkey(),
+                            &mint,
+                            &token_program,
+                        ));
+                        get_associated_token_address_with_program_id(
+                            &signer.pubkey(),
+                            &mint,
+                            &token_program,
+                        )
+                    }
+                };
+                ixs.push(instructions::add_payment_mint(
+                    accounts::AddPaymentMint {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        payment_mint: pda::payment_mint(&mint).0,
+                        mint,
+                        treasury,
+                        system_program: system_program::ID,
+                    },
+                    tokens_per_token,
+                    unwrap_to_sol_vault,
+                ));
+                self.execute::<()>(ixs, &signer)
+            }
+            Command::UpdatePaymentMint {
+                mint,
+                tokens_per_token,
+                enabled,
+                unwrap_to_sol_vault,
+                treasury,
+            } => {
+                let signer = self.signer()?;
+                let current = self.payment_mint(&mint)?;
+                let ix = instructions::update_payment_mint(
+                    accounts::UpdatePaymentMint {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        payment_mint: pda::payment_mint(&mint).0,
+                        treasury: treasury.unwrap_or(current.treasury),
+                    },
+                    tokens_per_token.unwrap_or(current.tokens_per_token),
+                    enabled.unwrap_or(current.enabled),
+                    unwrap_to_sol_vault.unwrap_or(current.unwrap_to_sol_vault),
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SetKeeperFee { fee_bps, cap } => {
+                let signer = self.signer()?;
+                let ix = instructions::set_keeper_fee(set_ico_config(&signer), fee_bps, cap);
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SetBonusTiers {
+                volume_tiers,
+                time_tiers,
+                bonus_vesting_type,
+            } => {
+                let signer = self.signer()?;
+                let ix = instructions::set_bonus_tiers(
+                    accounts::SetBonusTiers {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        bonus_tiers: pda::bonus_tiers().0,
+                        system_program: system_program::ID,
+                    },
+                    volume_tiers,
+                    time_tiers,
+                    bonus_vesting_type,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SetReferralConfig {
+                bonus_bps,
+                proceeds_bps,
+                bonus_cap,
+            } => {
+                let signer = self.signer()?;
+                let ix = instructions::set_referral_config(
+                    set_ico_config(&signer),
+                    bonus_bps,
+                    proceeds_bps,
+                    bonus_cap,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SetPositionTransferConfig {
+                enabled,
+                require_admin_cosign,
+            } => {
+                let signer = self.signer()?;
+                let ix = instructions::set_position_transfer_config(
+                    set_ico_config(&signer),
+                    enabled,
+                    require_admin_cosign,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::ConfigureAuction {
+                start_price,
+                floor_price,
+                supply,
+            } => {
+                let signer = self.signer()?;
+                let ix = instructions::configure_auction(
+                    accounts::ConfigureAuction {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        auction: pda::auction().0,
+                        system_program: system_program::ID,
+                    },
+                    start_price,
+                    floor_price,
+                    supply,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::SettleAuction => {
+                let signer = self.signer()?;
+                let ix = instructions::settle_auction(accounts::SettleAuction {
+                    ico_config,
+                    auction: pda::auction().0,
+                    sol_treasury: self.config()?.sol_treasury,
+                });
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::ConfigureProRataSale { token_cap } => {
+                let signer = self.signer()?;
+                let ix = instructions::configure_pro_rata_sale(
+                    accounts::ConfigureProRataSale {
+                        authority: signer.pubkey(),
+                        ico_config,
+                        pro_rata_sale: pda::pro_rata_sale().0,
+                        system_program: system_program::ID,
+                    },
+                    token_cap,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::FinalizeSale => {
+                let signer = self.signer()?;
+                let ix = instructions::finalize_sale(accounts::FinalizeSale {
+                    ico_config,
+                    pro_rata_sale: pda::pro_rata_sale().0,
+                });
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::TransferOwnership { new_authority } => {
+                let signer = self.signer()?;
+                let ix = instructions::transfer_ownership(
+                    accounts::TransferOwnership {
+                        authority: signer.pubkey(),
+                        ico_config,
+                    },
+                    new_authority,
+                );
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::RenounceOwnership => {
+                let signer = self.signer()?;
+                let ix = instructions::renounce_ownership(accounts::RenounceOwnership {
+                    authority: signer.pubkey(),
+                    ico_config,
+                });
+                self.execute::<()>(vec![ix], &signer)
+            }
+            Command::ShowConfig => {
+                display::print_config(&ico_config, &self.config()?);
+                Ok(())
+            }
+            Command::ShowInvestor { wallet, index } => {
+                let (address, _) = pda::investor(&wallet, index);
+                let data = self
+                    .rpc
+                    .get_account_data(&address)?
+                    .with_context(|| format!("no position {index} for {wallet}"))?;
+                display::print_investor(&address, &state::investor(&data)?);
+                Ok(())
+            }
+            Command::Stats { payment_mints } => {
+                let signer = self.signer()?;
+                let remaining_accounts = payment_mints
+                    .iter()
+                    .map(|mint| {
+                        anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
+                            pda::payment_mint(mint).0,
+                            false,
+                        )
+                    })
+                    .collect();
+                let ix = instructions::get_sale_stats(
+                    accounts::GetSaleStats { ico_config },
+                    remaining_accounts,
+                );
+                let stats: vesting_program::SaleStats = self.view("get_sale_stats", ix, &signer)?;
+                display::print_sale_stats(&stats);
+                Ok(())
+            }
+            Command::ShowPositions { wallet } => {
+                let signer = self.signer()?;
+                let wallet_positions = pda::wallet_positions(&wallet).0;
+                let data = self
+                    .rpc
+                    .get_account_data(&wallet_positions)?
+                    .with_context(|| format!("{wallet} has no positions"))?;
+                let count = state::decode_account::<state::WalletPositions>(&data)?.position_count;
+
+                // Positions moved away by transfer_position were closed
+                let metas = instructions::position_accounts(&wallet, 0..count);
+                let addresses: Vec<Pubkey> = metas.iter().map(|meta| meta.pubkey).collect();
+                let remaining_accounts = self
+                    .rpc
+                    .get_multiple_account_data(&addresses)?
+                    .into_iter()
+                    .zip(metas)
+                    .filter_map(|(data, meta)| data.map(|_| meta))
+                    .collect();
+                let ix = instructions::get_wallet_positions(
+                    accounts::GetWalletPositions {
+                        ico_config,
+                        wallet_positions,
+                    },
+                    wallet,
+                    remaining_accounts,
+                );
+                let summary: vesting_program::WalletPositionsSummary =
+                    self.view("get_wallet_positions", ix, &signer)?;
+                display::print_wallet_positions(&summary);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn set_ico_config(signer: &Keypair) -> accounts::SetIcoConfig {
+    accounts::SetIcoConfig {
+        authority: signer.pubkey(),
+        ico_config: pda::config().0,
+    }
+}
+
+// "THRESHOLD:BPS"
+fn parse_bonus_tier(value: &str) -> std::result::Result<BonusTier, String> {
+    let (threshold, bonus_bps) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected THRESHOLD:BPS, got {value:?}"))?;
+    Ok(BonusTier {
+        threshold: threshold
+            .parse()
+            .map_err(|e| format!("threshold {threshold:?}: {e}"))?,
+        bonus_bps: bonus_bps
+            .parse()
+            .map_err(|e| format!("bonus_bps {bonus_bps:?}: {e}"))?,
+    })
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let admin = Admin {
+        rpc: RpcClient::new(cli.url),
+        keypair: cli.keypair,
+        dry_run: cli.dry_run,
+    };
+    admin.run(cli.command)
+}
@@ -0,0 +1,375 @@
+//! Decoding of events and return data from transaction logs.
+//!
+//! `emit!` logs an event as `Program data: <base64>`, where the payload is the 8-byte
+//! event discriminator followed by the borsh-encoded event. View instructions log their
+//! result as `Program return: <program id> <base64>`.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use vesting_program::ID;
+
+const PROGRAM_DATA: &str = "Program data: ";
+const PROGRAM_RETURN: &str = "Program return: ";
+
+macro_rules! vesting_events {
+    ($($name:ident),* $(,)?) => {
+        /// An event emitted by the vesting program.
+        #[derive(Debug)]
+        pub enum VestingEvent {
+            $($name(vesting_program::$name),)*
+        }
+
+        impl VestingEvent {
+            /// Decode an event from its discriminator-prefixed bytes. Returns `None` for
+            /// data that is not a vesting program event.
+            pub fn decode(data: &[u8]) -> Option<Self> {
+                $(
+                    let discriminator = vesting_program::$name::DISCRIMINATOR;
+                    if let Some(mut payload) = data.strip_prefix(discriminator) {
+                        return vesting_program::$name::deserialize(&mut payload)
+                            .ok()
+                            .map(VestingEvent::$name);
+                    }
+                )*
+                None
+            }
+
+            /// Name of the event struct, e.g. `"TokensClaimed"`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(VestingEvent::$name(_) => stringify!($name),)*
+                }
+            }
+            // Name and logged bytes of one event of every kind, decoded from zeroed fields
+            #[cfg(test)]
+            fn samples() -> Vec<(&'static str, Vec<u8>)> {
+                vec![$(
+                    (
+                        stringify!($name),
+                        anchor_lang::Event::data(
+                            &vesting_program::$name::deserialize(&mut &[0u8; 1024][..]).unwrap(),
+                        ),
+                    ),
+                )*]
+            }
+        }
+    };
+}
+
+vesting_events!(
+    InitializeEvent,
+    InvestorWhitelisted,
+    TokenPurchaseEventForSol,
+    TokenPurchaseEventForToken,
+    PaymentMintUpdated,
+    ICODateChanged,
+    InvestorBlocked,
+    InvestorUnblocked,
+    VestingRevoked,
+    ClawbackDestinationUpdated,
+    PositionTransferConfigUpdated,
+    PositionTransferred,
+    PositionTokenized,
+    ClaimDestinationUpdated,
+    CrankClaimed,
+    AuctionConfigured,
+    AuctionBidPlaced,
+    AuctionSettled,
+    AuctionRebateClaimed,
+    ProRataSaleConfigured,
+    SaleCommitted,
+    SaleFinalized,
+    CommitmentSettled,
+    BonusTiersUpdated,
+    ReferralConfigUpdated,
+    ReferrerRegistered,
+    ReferralRecorded,
+    ReferralBonusClaimed,
+    KeeperFeeUpdated,
+    InvestorRemoved,
+    PrizeTokenUpdated,
+    WithdrawnSol,
+    PrizeWithdrawTokens,
+    PaymentTokensWithdrawn,
+    TokensClaimed,
+    PrizeDeposited,
+    SolTreasuryUpdated,
+    TGEDateChanged,
+    SecondsPerDayChanged,
+    OwnershipTransferred,
+    OwnershipRenounced,
+    PausedEvent,
+);
+
+/// Decode the events the vesting program emitted in a transaction.
+///
+/// Log lines are attributed to the program on top of the invocation stack, so events of
+/// other programs sharing the transaction, and lines that fail to decode, are skipped.
+pub fn parse_logs<S: AsRef<str>>(logs: &[S]) -> Vec<VestingEvent> {
+    let mut events = Vec::new();
+    let mut stack: Vec<Pubkey> = Vec::new();
+    for line in logs {
+        let line = line.as_ref();
+        if let Some(data) = line.strip_prefix(PROGRAM_DATA) {
+            if stack.last() == Some(&ID) {
+                if let Some(event) = STANDARD
+                    .decode(data)
+                    .ok()
+                    .and_then(|d| VestingEvent::decode(&d))
+                {
+                    events.push(event);
+                }
+            }
+        } else if let Some(program) = invoked_program(line) {
+            stack.push(program);
+        } else if is_program_exit(line) {
+            stack.pop();
+        }
+    }
+    events
+}
+
+/// Raw return data the vesting program set in a transaction, if any.
+pub fn return_data<S: AsRef<str>>(logs: &[S]) -> Option<Vec<u8>> {
+    logs.iter().rev().find_map(|line| {
+        let rest = line.as_ref().strip_prefix(PROGRAM_RETURN)?;
+        let (program, data) = rest.split_once(' ')?;
+        if program != ID.to_string() {
+            return None;
+        }
+        STANDARD.decode(data).ok()
+    })
+}
+
+/// Decode the typed result of a view instruction, e.g. `VestingBalance`, `SaleStats` or
+/// `WalletPositionsSummary`.
+pub fn decode_return<T: AnchorDeserialize>(data: &[u8]) -> std::io::Result<T> {
+    T::try_from_slice(data)
+}
+
+// "Program <id> invoke [<depth>]"
+fn invoked_program(line: &str) -> Option<Pubkey> {
+    let rest = line.strip_prefix("Program ")?;
+    let (program, tail) = rest.split_once(' ')?;
+    if !tail.starts_with("invoke [") {
+        return None;
+    }
+    program.parse().ok()
+}
+
+// "Program <id> success" or "Program <id> failed: <reason>"
+fn is_program_exit(line: &str) -> bool {
+    line.strip_prefix("Program ")
+        .and_then(|rest| rest.split_once(' '))
+        .is_some_and(|(_, tail)| tail == "success" || tail.starts_with("failed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSTEM: &str = "11111111111111111111111111111111";
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    // Another Anchor program in the same transaction
+    const OTHER: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+
+    fn claimed() -> vesting_program::TokensClaimed {
+        vesting_program::TokensClaimed {
+            investor: Pubkey::new_from_array([7; 32]),
+            position_index: 2,
+            destination: Pubkey::new_from_array([9; 32]),
+            amount: 1_500,
+            timestamp: 1_700_000_000,
+            remaining: 8_500,
+        }
+    }
+
+    fn data_line(data: &[u8]) -> String {
+        format!("{PROGRAM_DATA}{}", STANDARD.encode(data))
+    }
+
+    fn claimed_line() -> String {
+        data_line(&anchor_lang::Event::data(&claimed()))
+    }
+
+    fn borsh_bytes<T: anchor_lang::AnchorSerialize>(value: &T) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        value.serialize(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn names(events: &[VestingEvent]) -> Vec<&'static str> {
+        events.iter().map(VestingEvent::name).collect()
+    }
+
+    #[test]
+    fn event_fields_round_trip() {
+        let logs = [
+            format!("Program {ID} invoke [1]"),
+            "Program log: Instruction: ClaimTokens".to_string(),
+            claimed_line(),
+            format!("Program {ID} consumed 41250 of 200000 compute units"),
+            format!("Program {ID} success"),
+        ];
+        let events = parse_logs(&logs);
+        assert_eq!(events.len(), 1);
+        let VestingEvent::TokensClaimed(event) = &events[0] else {
+            panic!("decoded {}", events[0].name());
+        };
+        let expected = claimed();
+        assert_eq!(event.investor, expected.investor);
+        assert_eq!(event.position_index, expected.position_index);
+        assert_eq!(event.destination, expected.destination);
+        assert_eq!(event.amount, expected.amount);
+        assert_eq!(event.timestamp, expected.timestamp);
+        assert_eq!(event.remaining, expected.remaining);
+    }
+
+    #[test]
+    fn every_event_kind_decodes_to_itself() {
+        let samples = VestingEvent::samples();
+        assert_eq!(samples.len(), 42);
+        for (name, data) in &samples {
+            let event =
+                VestingEvent::decode(data).unwrap_or_else(|| panic!("{name} did not decode"));
+            assert_eq!(event.name(), *name);
+        }
+
+        // And through the logs, in order
+        let mut logs = vec![format!("Program {ID} invoke [1]")];
+        logs.extend(samples.iter().map(|(_, data)| data_line(data)));
+        logs.push(format!("Program {ID} success"));
+        let expected: Vec<&str> = samples.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names(&parse_logs(&logs)), expected);
+    }
+
+    #[test]
+    fn events_after_a_nested_invoke_belong_to_the_caller() {
+        let logs = [
+            format!("Program {ID} invoke [1]"),
+            "Program log: Instruction: ClaimTokens".to_string(),
+            format!("Program {TOKEN} invoke [2]"),
+            "Program log: Instruction: TransferChecked".to_string(),
+            format!("Program {TOKEN} consumed 6200 of 180000 compute units"),
+            format!("Program {TOKEN} success"),
+            claimed_line(),
+            format!("Program {ID} success"),
+        ];
+        assert_eq!(names(&parse_logs(&logs)), vec!["TokensClaimed"]);
+    }
+
+    #[test]
+    fn events_of_the_program_called_through_cpi_are_kept() {
+        let logs = [
+            format!("Program {OTHER} invoke [1]"),
+            format!("Program {ID} invoke [2]"),
+            claimed_line(),
+            format!("Program {ID} success"),
+            format!("Program {OTHER} success"),
+        ];
+        assert_eq!(names(&parse_logs(&logs)), vec!["TokensClaimed"]);
+    }
+
+    #[test]
+    fn program_data_of_other_programs_is_skipped() {
+        // Bytes that would decode as a vesting event, logged by another program
+        let logs = [
+            format!("Program {OTHER} invoke [1]"),
+            claimed_line(),
+            format!("Program {OTHER} success"),
+            format!("Program {ID} invoke [1]"),
+            format!("Program {OTHER} invoke [2]"),
+            claimed_line(),
+            format!("Program {OTHER} success"),
+            "Program data: not base64!".to_string(),
+            data_line(&[1, 2, 3]),
+            format!("Program {ID} success"),
+        ];
+        assert!(parse_logs(&logs).is_empty());
+    }
+
+    #[test]
+    fn a_failed_inner_invoke_is_popped() {
+        let logs = [
+            format!("Program {ID} invoke [1]"),
+            format!("Program {SYSTEM} invoke [2]"),
+            "Transfer: insufficient lamports 10, need 20".to_string(),
+            format!("Program {SYSTEM} failed: custom program error: 0x1"),
+            claimed_line(),
+            format!("Program {ID} failed: custom program error: 0x1"),
+            // Lines after the failed instruction are outside any invocation
+            claimed_line(),
+        ];
+        assert_eq!(names(&parse_logs(&logs)), vec!["TokensClaimed"]);
+    }
+
+    #[test]
+    fn return_data_is_read_from_the_vesting_program() {
+        let dates = vesting_program::IcoDates {
+            ico_start_time: 100,
+            ico_end_time: 200,
+            tge_time: 300,
+        };
+        let encoded = STANDARD.encode(borsh_bytes(&dates));
+        let logs = [
+            format!("Program {ID} invoke [1]"),
+            format!("Program {OTHER} invoke [2]"),
+            format!("{PROGRAM_RETURN}{OTHER} AQID"),
+            format!("Program {OTHER} success"),
+            format!("{PROGRAM_RETURN}{ID} {encoded}"),
+            format!("Program {ID} success"),
+        ];
+        let data = return_data(&logs).unwrap();
+        let decoded: vesting_program::IcoDates = decode_return(&data).unwrap();
+        assert_eq!(
+            (
+                decoded.ico_start_time,
+                decoded.ico_end_time,
+                decoded.tge_time
+            ),
+            (100, 200, 300)
+        );
+
+        // The vesting program's is the one reported even when another program returns later
+        let mut later = logs.to_vec();
+        later.push(format!("{PROGRAM_RETURN}{OTHER} AQID"));
+        assert_eq!(return_data(&later), Some(data));
+
+        assert_eq!(return_data(&logs[..4]), None);
+        assert!(decode_return::<vesting_program::IcoDates>(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn wallet_positions_summary_decodes() {
+        let summary = vesting_program::WalletPositionsSummary {
+            wallet: Pubkey::new_from_array([9; 32]),
+            position_count: 3,
+            positions_included: 2,
+            total_allocation: 5_000,
+            total_claimed: 1_200,
+            total_claimable: 800,
+        };
+        let logs = [
+            format!("Program {ID} invoke [1]"),
+            format!(
+                "{PROGRAM_RETURN}{ID} {}",
+                STANDARD.encode(borsh_bytes(&summary))
+            ),
+            format!("Program {ID} success"),
+        ];
+        let decoded: vesting_program::WalletPositionsSummary =
+            decode_return(&return_data(&logs).unwrap()).unwrap();
+        assert_eq!(decoded.wallet, summary.wallet);
+        assert_eq!((decoded.position_count, decoded.positions_included), (3, 2));
+        assert_eq!(
+            (
+                decoded.total_allocation,
+                decoded.total_claimed,
+                decoded.total_claimable
+            ),
+            (5_000, 1_200, 800)
+        );
+    }
+}
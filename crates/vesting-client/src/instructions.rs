@@ -0,0 +1,653 @@
+//! Typed builders for every instruction of the vesting program.
+//!
+//! Each builder takes the Anchor-generated accounts struct for the instruction plus its
+//! arguments, so a missing account or a mistyped argument is a compile error. The PDA
+//! helpers in [`crate::pda`] fill in the derived addresses.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use vesting_program::{accounts, instruction, BatchClaimEntry, BonusTier, WhitelistEntry};
+
+/// Build an instruction from an accounts struct and its instruction data.
+pub fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    build_with_remaining(accounts, data, Vec::new())
+}
+
+/// Build an instruction whose handler also reads `remaining_accounts`.
+pub fn build_with_remaining(
+    accounts: impl ToAccountMetas,
+    data: impl InstructionData,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(remaining_accounts);
+    Instruction {
+        program_id: vesting_program::ID,
+        accounts: metas,
+        data: data.data(),
+    }
+}
+
+// Mirrors the instruction, which takes the whole ICO configuration
+#[allow(clippy::too_many_arguments)]
+pub fn initialize(
+    accounts: accounts::InitializeIco,
+    ico_start_time: u64,
+    ico_end_time: u64,
+    tge_time: u64,
+    min_buy_amount: u64,
+    max_buy_amount: u64,
+    tokens_per_sol: u64,
+    seconds_per_day: u64,
+    reward_mode: u8,
+    reward_supply_cap: u64,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::Initialize {
+            ico_start_time,
+            ico_end_time,
+            tge_time,
+            min_buy_amount,
+            max_buy_amount,
+            tokens_per_sol,
+            seconds_per_day,
+            reward_mode,
+            reward_supply_cap,
+        },
+    )
+}
+
+pub fn whitelist_investor_by_admin(
+    accounts: accounts::InvestorEntry,
+    investor_address: Pubkey,
+    position_index: u32,
+    amount: u64,
+    vesting_type: u8,
+    revocable: bool,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::WhitelistInvestorByAdmin {
+            investor_address,
+            position_index,
+            amount,
+            vesting_type,
+            revocable,
+        },
+    )
+}
+
+/// Remaining accounts: `[investor PDA, wallet positions PDA]` per entry, all writable.
+pub fn whitelist_investors_batch(
+    accounts: accounts::WhitelistInvestorsBatch,
+    entries: Vec<WhitelistEntry>,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    build_with_remaining(
+        accounts,
+        instruction::WhitelistInvestorsBatch { entries },
+        remaining_accounts,
+    )
+}
+
+pub fn buy_tokens_with_sol(
+    accounts: accounts::BuyTokensWithSol,
+    investor_address: Pubkey,
+    position_index: u32,
+    amount: u64,
+    vesting_type: u8,
+    bonus_position_index: Option<u32>,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::BuyTokensWithSol {
+            investor_address,
+            position_index,
+            amount,
+            vesting_type,
+            bonus_position_index,
+        },
+    )
+}
+
+pub fn buy_with_token(
+    accounts: accounts::BuyWithToken,
+    investor_address: Pubkey,
+    position_index: u32,
+    amount: u64,
+    vesting_type: u8,
+    bonus_position_index: Option<u32>,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::BuyWithToken {
+            investor_address,
+            position_index,
+            amount,
+            vesting_type,
+            bonus_position_index,
+        },
+    )
+}
+
+pub fn configure_auction(
+    accounts: accounts::ConfigureAuction,
+    start_price: u64,
+    floor_price: u64,
+    supply: u64,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::ConfigureAuction {
+            start_price,
+            floor_price,
+            supply,
+        },
+    )
+}
+
+pub fn bid_auction(
+    accounts: accounts::BidAuction,
+    investor_address: Pubkey,
+    position_index: u32,
+    tokens: u64,
+    vesting_type: u8,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::BidAuction {
+            investor_address,
+            position_index,
+            tokens,
+            vesting_type,
+        },
+    )
+}
+
+pub fn settle_auction(accounts: accounts::SettleAuction) -> Instruction {
+    build(accounts, instruction::SettleAuction)
+}
+
+pub fn claim_auction_rebate(
+    accounts: accounts::ClaimAuctionRebate,
+    position_index: u32,
+) -> Instruction {
+    build(accounts, instruction::ClaimAuctionRebate { position_index })
+}
+
+pub fn configure_pro_rata_sale(
+    accounts: accounts::ConfigureProRataSale,
+    token_cap: u64,
+) -> Instruction {
+    build(accounts, instruction::ConfigureProRataSale { token_cap })
+}
+
+pub fn commit_to_sale(
+    accounts: accounts::CommitToSale,
+    investor_address: Pubkey,
+    position_index: u32,
+    amount: u64,
+    vesting_type: u8,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::CommitToSale {
+            investor_address,
+            position_index,
+            amount,
+            vesting_type,
+        },
+    )
+}
+
+pub fn finalize_sale(accounts: accounts::FinalizeSale) -> Instruction {
+    build(accounts, instruction::FinalizeSale)
+}
+
+pub fn settle(accounts: accounts::Settle, position_index: u32) -> Instruction {
+    build(accounts, instruction::Settle { position_index })
+}
+
+pub fn add_payment_mint(
+    accounts: accounts::AddPaymentMint,
+    tokens_per_token: u64,
+    unwrap_to_sol_vault: bool,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::AddPaymentMint {
+            tokens_per_token,
+            unwrap_to_sol_vault,
+        },
+    )
+}
+
+pub fn update_payment_mint(
+    accounts: accounts::UpdatePaymentMint,
+    tokens_per_token: u64,
+    enabled: bool,
+    unwrap_to_sol_vault: bool,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::UpdatePaymentMint {
+            tokens_per_token,
+            enabled,
+            unwrap_to_sol_vault,
+        },
+    )
+}
+
+pub fn set_ico_dates(
+    accounts: accounts::SetIcoConfig,
+    ico_start_time: u64,
+    ico_end_time: u64,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::SetIcoDates {
+            ico_start_time,
+            ico_end_time,
+        },
+    )
+}
+
+pub fn remove_investor(
+    accounts: accounts::RemoveInvestor,
+    investor_address: Pubkey,
+    position_index: u32,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::RemoveInvestor {
+            investor_address,
+            position_index,
+        },
+    )
+}
+
+pub fn reset_sol_treasure(accounts: accounts::UpdateTreasuryWallet) -> Instruction {
+    build(accounts, instruction::ResetSolTreasure)
+}
+
+pub fn set_mint_address(
+    accounts: accounts::SetMintAddress,
+    token_type: u8,
+    token: Pubkey,
+) -> Instruction {
+    build(accounts, instruction::SetMintAddress { token_type, token })
+}
+
+pub fn set_tge_date(accounts: accounts::SetIcoConfig, new_tge_time: u64) -> Instruction {
+    build(accounts, instruction::SetTgeDate { new_tge_time })
+}
+
+pub fn set_paused(accounts: accounts::SetPaused, paused: bool) -> Instruction {
+    build(accounts, instruction::SetPaused { paused })
+}
+
+pub fn reset_seconds_per_day(accounts: accounts::SetIcoConfig, nos_of_seconds: u64) -> Instruction {
+    build(accounts, instruction::ResetSecondsPerDay { nos_of_seconds })
+}
+
+pub fn withdraw_sol(
+    accounts: accounts::WithdrawSol,
+    amount: u64,
+    recipient_address: Pubkey,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::WithdrawSol {
+            amount,
+            recipient_address,
+        },
+    )
+}
+
+pub fn withdraw_prize_tokens(accounts: accounts::PrizeTokensTransfer, amount: u64) -> Instruction {
+    build(accounts, instruction::WithdrawPrizeTokens { amount })
+}
+
+pub fn withdraw_payment_tokens(
+    accounts: accounts::PaymentTokensTransfer,
+    amount: u64,
+) -> Instruction {
+    build(accounts, instruction::WithdrawPaymentTokens { amount })
+}
+
+pub fn block_investor(
+    accounts: accounts::BlockInvestor,
+    investor_address: Pubkey,
+    position_index: u32,
+    reason: u8,
+    expires_at: Option<u64>,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::BlockInvestor {
+            investor_address,
+            position_index,
+            reason,
+            expires_at,
+        },
+    )
+}
+
+pub fn unblock_investor(
+    accounts: accounts::UnblockInvestor,
+    investor_address: Pubkey,
+    position_index: u32,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::UnblockInvestor {
+            investor_address,
+            position_index,
+        },
+    )
+}
+
+pub fn deposit_prize(accounts: accounts::DepositPrize, amount: u64) -> Instruction {
+    build(accounts, instruction::DepositPrize { amount })
+}
+
+pub fn set_clawback_destination(
+    accounts: accounts::SetIcoConfig,
+    destination: Pubkey,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::SetClawbackDestination { destination },
+    )
+}
+
+pub fn revoke_vesting(
+    accounts: accounts::RevokeVesting,
+    investor_address: Pubkey,
+    position_index: u32,
+    pay_out_vested: bool,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::RevokeVesting {
+            investor_address,
+            position_index,
+            pay_out_vested,
+        },
+    )
+}
+
+pub fn set_keeper_fee(accounts: accounts::SetIcoConfig, fee_bps: u16, fee_cap: u64) -> Instruction {
+    build(accounts, instruction::SetKeeperFee { fee_bps, fee_cap })
+}
+
+pub fn set_bonus_tiers(
+    accounts: accounts::SetBonusTiers,
+    volume_tiers: Vec<BonusTier>,
+    time_tiers: Vec<BonusTier>,
+    bonus_vesting_type: Option<u8>,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::SetBonusTiers {
+            volume_tiers,
+            time_tiers,
+            bonus_vesting_type,
+        },
+    )
+}
+
+pub fn set_referral_config(
+    accounts: accounts::SetIcoConfig,
+    bonus_bps: u16,
+    proceeds_bps: u16,
+    bonus_cap: u64,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::SetReferralConfig {
+            bonus_bps,
+            proceeds_bps,
+            bonus_cap,
+        },
+    )
+}
+
+pub fn register_referrer(accounts: accounts::RegisterReferrer) -> Instruction {
+    build(accounts, instruction::RegisterReferrer)
+}
+
+pub fn claim_referral_bonus(accounts: accounts::ClaimReferralBonus) -> Instruction {
+    build(accounts, instruction::ClaimReferralBonus)
+}
+
+pub fn set_position_transfer_config(
+    accounts: accounts::SetIcoConfig,
+    enabled: bool,
+    require_admin_cosign: bool,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::SetPositionTransferConfig {
+            enabled,
+            require_admin_cosign,
+        },
+    )
+}
+
+pub fn transfer_position(
+    accounts: accounts::TransferPosition,
+    from_index: u32,
+    new_address: Pubkey,
+    new_index: u32,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::TransferPosition {
+            from_index,
+            new_address,
+            new_index,
+        },
+    )
+}
+
+pub fn set_claim_destination(
+    accounts: accounts::SetClaimDestination,
+    investor_address: Pubkey,
+    position_index: u32,
+    destination: Option<Pubkey>,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::SetClaimDestination {
+            investor_address,
+            position_index,
+            destination,
+        },
+    )
+}
+
+pub fn tokenize_position(
+    accounts: accounts::TokenizePosition,
+    investor_address: Pubkey,
+    position_index: u32,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::TokenizePosition {
+            investor_address,
+            position_index,
+        },
+    )
+}
+
+pub fn transfer_ownership(
+    accounts: accounts::TransferOwnership,
+    new_authority: Pubkey,
+) -> Instruction {
+    build(accounts, instruction::TransferOwnership { new_authority })
+}
+
+pub fn renounce_ownership(accounts: accounts::RenounceOwnership) -> Instruction {
+    build(accounts, instruction::RenounceOwnership)
+}
+
+pub fn claim_tokens(
+    accounts: accounts::ClaimTokens,
+    investor_address: Pubkey,
+    position_index: u32,
+    amount: Option<u64>,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::ClaimTokens {
+            investor_address,
+            position_index,
+            amount,
+        },
+    )
+}
+
+pub fn crank_claim(
+    accounts: accounts::CrankClaim,
+    investor_address: Pubkey,
+    position_index: u32,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::CrankClaim {
+            investor_address,
+            position_index,
+        },
+    )
+}
+
+/// Remaining accounts: `[investor PDA, investor's reward token ATA]` per entry, all writable.
+pub fn crank_claim_batch(
+    accounts: accounts::CrankClaimBatch,
+    entries: Vec<BatchClaimEntry>,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    build_with_remaining(
+        accounts,
+        instruction::CrankClaimBatch { entries },
+        remaining_accounts,
+    )
+}
+
+/// Remaining accounts: the wallet's writable Investor PDAs.
+pub fn claim_all_positions(
+    accounts: accounts::ClaimAllPositions,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    build_with_remaining(accounts, instruction::ClaimAllPositions, remaining_accounts)
+}
+
+pub fn get_ico_dates(accounts: accounts::GetIcoDates) -> Instruction {
+    build(accounts, instruction::GetIcoDates)
+}
+
+pub fn get_token_rate(accounts: accounts::GetTokenRate) -> Instruction {
+    build(accounts, instruction::GetTokenRate)
+}
+
+pub fn get_min_max_buy_amount(accounts: accounts::GetMinMaxBuyAmount) -> Instruction {
+    build(accounts, instruction::GetMinMaxBuyAmount)
+}
+
+pub fn get_seconds_per_day(accounts: accounts::GetSecondsPerDay) -> Instruction {
+    build(accounts, instruction::GetSecondsPerDay)
+}
+
+pub fn get_sol_balance(accounts: accounts::GetSOLBalance) -> Instruction {
+    build(accounts, instruction::GetSolBalance)
+}
+
+pub fn get_token_balance(accounts: accounts::GetTokenBalance) -> Instruction {
+    build(accounts, instruction::GetTokenBalance)
+}
+
+pub fn get_investor_address(accounts: accounts::GetInvestorAddress) -> Instruction {
+    build(accounts, instruction::GetInvestorAddress)
+}
+
+pub fn get_vesting_balance(
+    accounts: accounts::GetVestingBalance,
+    investor_address: Pubkey,
+    position_index: u32,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::GetVestingBalance {
+            investor_address,
+            position_index,
+        },
+    )
+}
+
+/// Remaining accounts: the PaymentMint PDAs to report amounts raised for.
+pub fn get_sale_stats(
+    accounts: accounts::GetSaleStats,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    build_with_remaining(accounts, instruction::GetSaleStats, remaining_accounts)
+}
+
+pub fn get_vesting_timeline(
+    accounts: accounts::GetVestingBalance,
+    investor_address: Pubkey,
+    position_index: u32,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::GetVestingTimeline {
+            investor_address,
+            position_index,
+        },
+    )
+}
+
+pub fn get_schedule_timeline(
+    accounts: accounts::GetScheduleTimeline,
+    vesting_type: u8,
+    total_allocation: u64,
+) -> Instruction {
+    build(
+        accounts,
+        instruction::GetScheduleTimeline {
+            vesting_type,
+            total_allocation,
+        },
+    )
+}
+
+/// Remaining accounts: the wallet's Investor PDAs, see [`position_accounts`]. Returns a
+/// `WalletPositionsSummary`.
+pub fn get_wallet_positions(
+    accounts: accounts::GetWalletPositions,
+    wallet: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    build_with_remaining(
+        accounts,
+        instruction::GetWalletPositions { wallet },
+        remaining_accounts,
+    )
+}
+
+/// Read-only metas of a wallet's Investor PDAs at `position_indexes`.
+pub fn position_accounts(
+    wallet: &Pubkey,
+    position_indexes: impl IntoIterator<Item = u32>,
+) -> Vec<AccountMeta> {
+    position_indexes
+        .into_iter()
+        .map(|index| AccountMeta::new_readonly(crate::pda::investor(wallet, index).0, false))
+        .collect()
+}
+
+pub fn get_linear_vesting_end_time(accounts: accounts::GetLinearVestingEndTime) -> Instruction {
+    build(accounts, instruction::GetLinearVestingEndTime)
+}
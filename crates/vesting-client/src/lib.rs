@@ -0,0 +1,20 @@
+//! Rust client for the vesting program.
+//!
+//! Builds on the program crate with the `no-entrypoint` feature, so it can be linked into
+//! off-chain binaries. It provides:
+//!
+//! - [`instructions`]: a typed builder for every instruction
+//! - [`pda`]: derivation of the program's PDAs
+//! - [`state`]: deserializers for `TokenIco`, `Investor` and the other program accounts
+//! - [`events`]: decoding of events and return data from transaction logs
+//! - `rpc` (feature `rpc`): a small JSON-RPC client to read accounts and simulate or send
+//!   transactions
+
+pub mod events;
+pub mod instructions;
+pub mod pda;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod state;
+
+pub use vesting_program::{accounts, instruction, ID as PROGRAM_ID};
@@ -0,0 +1,98 @@
+//! PDA derivation, mirroring the `seeds` constraints of the program.
+
+use anchor_lang::prelude::Pubkey;
+use vesting_program::ID;
+
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const VAULT_AUTHORITY_SEED: &[u8] = b"vault_authority";
+pub const INVESTOR_SEED: &[u8] = b"investor is my hero";
+pub const WALLET_POSITIONS_SEED: &[u8] = b"wallet positions";
+pub const PAYMENT_MINT_SEED: &[u8] = b"payment_mint";
+pub const WSOL_ESCROW_SEED: &[u8] = b"wsol_escrow";
+pub const POSITION_MINT_SEED: &[u8] = b"position_mint";
+pub const REFERRER_SEED: &[u8] = b"referrer";
+pub const BONUS_TIERS_SEED: &[u8] = b"bonus_tiers";
+pub const AUCTION_SEED: &[u8] = b"auction";
+pub const AUCTION_BID_SEED: &[u8] = b"auction_bid";
+pub const PRO_RATA_SALE_SEED: &[u8] = b"pro_rata_sale";
+pub const COMMITMENT_SEED: &[u8] = b"commitment";
+
+/// `TokenIco` configuration account.
+pub fn config() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], &ID)
+}
+
+/// Authority over the reward token treasury.
+pub fn vault_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_AUTHORITY_SEED], &ID)
+}
+
+/// `Investor` account of one position of a wallet.
+pub fn investor(wallet: &Pubkey, position_index: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            INVESTOR_SEED,
+            wallet.as_ref(),
+            &position_index.to_le_bytes(),
+        ],
+        &ID,
+    )
+}
+
+/// `WalletPositions` index of a wallet.
+pub fn wallet_positions(wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WALLET_POSITIONS_SEED, wallet.as_ref()], &ID)
+}
+
+/// `PaymentMint` settings of an accepted payment token.
+pub fn payment_mint(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PAYMENT_MINT_SEED, mint.as_ref()], &ID)
+}
+
+/// Temporary wSOL account used while unwrapping a purchase.
+pub fn wsol_escrow(buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WSOL_ESCROW_SEED, buyer.as_ref()], &ID)
+}
+
+/// Mint of a tokenized position, keyed by the `Investor` account.
+pub fn position_mint(investor: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POSITION_MINT_SEED, investor.as_ref()], &ID)
+}
+
+pub fn referrer(wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REFERRER_SEED, wallet.as_ref()], &ID)
+}
+
+pub fn bonus_tiers() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BONUS_TIERS_SEED], &ID)
+}
+
+pub fn auction() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUCTION_SEED], &ID)
+}
+
+pub fn auction_bid(wallet: &Pubkey, position_index: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            AUCTION_BID_SEED,
+            wallet.as_ref(),
+            &position_index.to_le_bytes(),
+        ],
+        &ID,
+    )
+}
+
+pub fn pro_rata_sale() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PRO_RATA_SALE_SEED], &ID)
+}
+
+pub fn commitment(wallet: &Pubkey, position_index: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            COMMITMENT_SEED,
+            wallet.as_ref(),
+            &position_index.to_le_bytes(),
+        ],
+        &ID,
+    )
+}
@@ -0,0 +1,416 @@
+//! Minimal JSON-RPC client for off-chain tools, enabled with the `rpc` feature.
+//!
+//! Covers what the admin tooling needs: reading accounts, simulating transactions (with
+//! their logs and return data), sending and confirming them, and listing and fetching
+//! landed ones.
+
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::hash::Hash;
+use anchor_lang::solana_program::instruction::Instruction;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+pub use solana_keypair::{read_keypair_file, Keypair};
+pub use solana_signer::Signer;
+use solana_transaction::Transaction;
+
+pub const DEFAULT_URL: &str = "http://127.0.0.1:8899";
+
+// getMultipleAccounts accepts at most 100 keys per request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub enum RpcError {
+    /// The request could not be sent or its response read.
+    Transport(String),
+    /// The node answered with a JSON-RPC error.
+    Rpc { code: i64, message: String },
+    /// The response did not have the expected shape.
+    Decode(String),
+    /// The transaction was processed and failed.
+    TransactionFailed { signature: String, err: Value },
+    /// The transaction was not confirmed in time.
+    Timeout(String),
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Transport(e) => write!(f, "RPC transport error: {e}"),
+            RpcError::Rpc { code, message } => write!(f, "RPC error {code}: {message}"),
+            RpcError::Decode(e) => write!(f, "unexpected RPC response: {e}"),
+            RpcError::TransactionFailed { signature, err } => {
+                write!(f, "transaction {signature} failed: {err}")
+            }
+            RpcError::Timeout(signature) => write!(f, "transaction {signature} not confirmed"),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+pub type Result<T> = std::result::Result<T, RpcError>;
+
+/// Outcome of `simulateTransaction`.
+#[derive(Debug)]
+pub struct Simulation {
+    /// The transaction error, `None` when the simulation succeeded.
+    pub err: Option<Value>,
+    pub logs: Vec<String>,
+    /// Return data set by the last program that set it, with that program's id.
+    pub return_data: Option<(Pubkey, Vec<u8>)>,
+    pub units_consumed: Option<u64>,
+}
+
+/// A landed transaction, as returned by `getTransaction`.
+#[derive(Debug)]
+pub struct ConfirmedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    /// The transaction error, `None` when it succeeded.
+    pub err: Option<Value>,
+    pub logs: Vec<String>,
+    pub return_data: Option<(Pubkey, Vec<u8>)>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    // `null` for lookups that found nothing, e.g. an unknown transaction
+    #[serde(default)]
+    result: Value,
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl ConfirmedTransaction {
+    /// Parse a `getTransaction` result with `json` encoding, e.g. one saved to a file.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let meta = &value["meta"];
+        Ok(ConfirmedTransaction {
+            signature: value["transaction"]["signatures"][0]
+                .as_str()
+                .map(str::to_owned)
+                .ok_or_else(|| RpcError::Decode("transaction: missing signature".into()))?,
+            slot: value["slot"]
+                .as_u64()
+                .ok_or_else(|| RpcError::Decode("transaction: missing slot".into()))?,
+            block_time: value["blockTime"].as_i64(),
+            err: transaction_error(&meta["err"]),
+            logs: log_messages(&meta["logMessages"]),
+            return_data: return_data(&meta["returnData"])?,
+        })
+    }
+}
+
+/// One entry of `getSignaturesForAddress`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureInfo {
+    pub signature: String,
+    pub slot: u64,
+    pub err: Option<Value>,
+    pub block_time: Option<i64>,
+}
+
+pub struct RpcClient {
+    url: String,
+    agent: ureq::Agent,
+    commitment: String,
+}
+
+impl RpcClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::new_with_commitment(url, "confirmed")
+    }
+
+    pub fn new_with_commitment(url: impl Into<String>, commitment: impl Into<String>) -> Self {
+        RpcClient {
+            url: url.into(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+            commitment: commitment.into(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Send a raw JSON-RPC request and return its `result`.
+    pub fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Response = match self.agent.post(&self.url).send_json(body) {
+            Ok(response) => response.into_json(),
+            // JSON-RPC errors may come with an HTTP error status
+            Err(ureq::Error::Status(_, response)) => response.into_json(),
+            Err(e) => return Err(RpcError::Transport(e.to_string())),
+        }
+        .map_err(|e| RpcError::Transport(e.to_string()))?;
+        if let Some(error) = response.error {
+            let message = match error.data {
+                Some(data) => format!("{} {}", error.message, data),
+                None => error.message,
+            };
+            return Err(RpcError::Rpc {
+                code: error.code,
+                message,
+            });
+        }
+        Ok(response.result)
+    }
+
+    pub fn get_slot(&self) -> Result<u64> {
+        self.get_slot_with_commitment(&self.commitment)
+    }
+
+    /// Current slot at another commitment, e.g. `finalized`.
+    pub fn get_slot_with_commitment(&self, commitment: &str) -> Result<u64> {
+        let result = self.request("getSlot", json!([{ "commitment": commitment }]))?;
+        result
+            .as_u64()
+            .ok_or_else(|| RpcError::Decode("getSlot: slot is not a number".into()))
+    }
+
+    /// Data of an account, `None` when it does not exist.
+    pub fn get_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>> {
+        let result = self.request(
+            "getAccountInfo",
+            json!([address.to_string(), { "encoding": "base64", "commitment": self.commitment }]),
+        )?;
+        account_data(&result["value"])
+    }
+
+    /// Data of several accounts, in the order of `addresses`.
+    pub fn get_multiple_account_data(&self, addresses: &[Pubkey]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let keys: Vec<String> = chunk.iter().map(Pubkey::to_string).collect();
+            let result = self.request(
+                "getMultipleAccounts",
+                json!([keys, { "encoding": "base64", "commitment": self.commitment }]),
+            )?;
+            let values = result["value"]
+                .as_array()
+                .ok_or_else(|| RpcError::Decode("getMultipleAccounts: missing value".into()))?;
+            for value in values {
+                accounts.push(account_data(value)?);
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Owner program of an account, `None` when it does not exist.
+    pub fn get_account_owner(&self, address: &Pubkey) -> Result<Option<Pubkey>> {
+        let result = self.request(
+            "getAccountInfo",
+            json!([address.to_string(), { "encoding": "base64", "commitment": self.commitment }]),
+        )?;
+        if result["value"].is_null() {
+            return Ok(None);
+        }
+        result["value"]["owner"]
+            .as_str()
+            .and_then(|owner| owner.parse().ok())
+            .map(Some)
+            .ok_or_else(|| RpcError::Decode("getAccountInfo: invalid owner".into()))
+    }
+
+    pub fn get_latest_blockhash(&self) -> Result<Hash> {
+        let result = self.request(
+            "getLatestBlockhash",
+            json!([{ "commitment": self.commitment }]),
+        )?;
+        result["value"]["blockhash"]
+            .as_str()
+            .and_then(|hash| hash.parse().ok())
+            .ok_or_else(|| RpcError::Decode("getLatestBlockhash: invalid blockhash".into()))
+    }
+
+    /// Build a transaction paid by the first signer and sign it with a fresh blockhash.
+    pub fn signed_transaction(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<Transaction> {
+        let payer = signers.first().map(|signer| signer.pubkey());
+        let blockhash = self.get_latest_blockhash()?;
+        Ok(Transaction::new_signed_with_payer(
+            instructions,
+            payer.as_ref(),
+            signers,
+            blockhash,
+        ))
+    }
+
+    /// Simulate a transaction without verifying its signatures.
+    pub fn simulate_transaction(&self, transaction: &Transaction) -> Result<Simulation> {
+        let result = self.request(
+            "simulateTransaction",
+            json!([encode_transaction(transaction)?, {
+                "encoding": "base64",
+                "sigVerify": false,
+                "replaceRecentBlockhash": true,
+                "commitment": self.commitment,
+            }]),
+        )?;
+        let value = &result["value"];
+        Ok(Simulation {
+            err: transaction_error(&value["err"]),
+            logs: log_messages(&value["logs"]),
+            return_data: return_data(&value["returnData"])?,
+            units_consumed: value["unitsConsumed"].as_u64(),
+        })
+    }
+
+    /// A landed transaction with its logs, `None` when the node does not know it.
+    pub fn get_transaction(&self, signature: &str) -> Result<Option<ConfirmedTransaction>> {
+        let result = self.request(
+            "getTransaction",
+            json!([signature, {
+                "encoding": "json",
+                "commitment": self.commitment,
+                "maxSupportedTransactionVersion": 0,
+            }]),
+        )?;
+        if result.is_null() {
+            return Ok(None);
+        }
+        ConfirmedTransaction::from_json(&result).map(Some)
+    }
+
+    /// Signatures of transactions that touched `address`, newest first. `before` and `until`
+    /// bound the page by signature, exclusively.
+    pub fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<&str>,
+        until: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>> {
+        let mut config = json!({ "limit": limit, "commitment": self.commitment });
+        if let Some(before) = before {
+            config["before"] = json!(before);
+        }
+        if let Some(until) = until {
+            config["until"] = json!(until);
+        }
+        let result = self.request(
+            "getSignaturesForAddress",
+            json!([address.to_string(), config]),
+        )?;
+        serde_json::from_value(result).map_err(|e| RpcError::Decode(e.to_string()))
+    }
+
+    /// Send a signed transaction and return its signature.
+    pub fn send_transaction(&self, transaction: &Transaction) -> Result<String> {
+        let result = self.request(
+            "sendTransaction",
+            json!([encode_transaction(transaction)?, {
+                "encoding": "base64",
+                "preflightCommitment": self.commitment,
+            }]),
+        )?;
+        result
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| RpcError::Decode("sendTransaction: invalid signature".into()))
+    }
+
+    /// Wait until the transaction reaches the client's commitment.
+    pub fn confirm_transaction(&self, signature: &str) -> Result<()> {
+        let started = Instant::now();
+        while started.elapsed() < CONFIRM_TIMEOUT {
+            let result = self.request(
+                "getSignatureStatuses",
+                json!([[signature], { "searchTransactionHistory": false }]),
+            )?;
+            let status = &result["value"][0];
+            if !status.is_null() {
+                if !status["err"].is_null() {
+                    return Err(RpcError::TransactionFailed {
+                        signature: signature.to_owned(),
+                        err: status["err"].clone(),
+                    });
+                }
+                let reached = match status["confirmationStatus"].as_str() {
+                    Some("finalized") => true,
+                    Some("confirmed") => self.commitment != "finalized",
+                    Some("processed") => self.commitment == "processed",
+                    _ => false,
+                };
+                if reached {
+                    return Ok(());
+                }
+            }
+            thread::sleep(CONFIRM_POLL_INTERVAL);
+        }
+        Err(RpcError::Timeout(signature.to_owned()))
+    }
+
+    pub fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<String> {
+        let signature = self.send_transaction(transaction)?;
+        self.confirm_transaction(&signature)?;
+        Ok(signature)
+    }
+}
+
+fn account_data(value: &Value) -> Result<Option<Vec<u8>>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    value["data"][0]
+        .as_str()
+        .and_then(|data| STANDARD.decode(data).ok())
+        .map(Some)
+        .ok_or_else(|| RpcError::Decode("account data is not base64".into()))
+}
+
+fn transaction_error(err: &Value) -> Option<Value> {
+    Some(err.clone()).filter(|err| !err.is_null())
+}
+
+fn log_messages(logs: &Value) -> Vec<String> {
+    logs.as_array()
+        .map(|logs| {
+            logs.iter()
+                .filter_map(|line| line.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// { "programId": "...", "data": ["<base64>", "base64"] }
+fn return_data(value: &Value) -> Result<Option<(Pubkey, Vec<u8>)>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    let program = value["programId"]
+        .as_str()
+        .and_then(|program| program.parse().ok())
+        .ok_or_else(|| RpcError::Decode("returnData: invalid programId".into()))?;
+    let data = value["data"][0]
+        .as_str()
+        .and_then(|data| STANDARD.decode(data).ok())
+        .ok_or_else(|| RpcError::Decode("returnData: invalid data".into()))?;
+    Ok(Some((program, data)))
+}
+
+fn encode_transaction(transaction: &Transaction) -> Result<String> {
+    let bytes = bincode::serialize(transaction).map_err(|e| RpcError::Decode(e.to_string()))?;
+    Ok(STANDARD.encode(bytes))
+}
@@ -0,0 +1,26 @@
+//! Deserializers for the program's accounts.
+//!
+//! Each function checks the 8-byte account discriminator, so passing the data of the wrong
+//! account type fails instead of decoding garbage.
+
+use anchor_lang::error::Error;
+use anchor_lang::AccountDeserialize;
+pub use vesting_program::{
+    Auction, AuctionBid, BonusTiers, Commitment, Investor, PaymentMint, ProRataSale, Referrer,
+    TokenIco, WalletPositions,
+};
+
+/// Deserialize any program account from its raw data.
+pub fn decode_account<T: AccountDeserialize>(data: &[u8]) -> Result<T, Error> {
+    T::try_deserialize(&mut &data[..])
+}
+
+/// Deserialize the `TokenIco` configuration account.
+pub fn token_ico(data: &[u8]) -> Result<TokenIco, Error> {
+    decode_account(data)
+}
+
+/// Deserialize an `Investor` position account.
+pub fn investor(data: &[u8]) -> Result<Investor, Error> {
+    decode_account(data)
+}
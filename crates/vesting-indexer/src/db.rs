@@ -0,0 +1,298 @@
+// SQLite schema and the queries shared by the sync, import and reconcile commands.
+//
+// Every row derived from an event references its transaction with ON DELETE CASCADE, so
+// rolling back a transaction dropped by a fork is a single DELETE. The positions and investors
+// tables are rebuilt from the event tables after each run instead of being updated in place,
+// which keeps them correct across rollbacks.
+
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Params};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    signature   TEXT PRIMARY KEY,
+    seq         INTEGER NOT NULL UNIQUE, -- Ingestion order, breaks ties within a slot
+    slot        INTEGER NOT NULL,
+    block_time  INTEGER,
+    failed      INTEGER NOT NULL,        -- Failed transactions are recorded without events
+    finalized   INTEGER NOT NULL
+);
+
+-- Every decoded event, with the wallet and position it concerns where it names one
+CREATE TABLE IF NOT EXISTS events (
+    signature       TEXT NOT NULL REFERENCES transactions(signature) ON DELETE CASCADE,
+    event_index     INTEGER NOT NULL,
+    name            TEXT NOT NULL,
+    investor        TEXT,
+    position_index  INTEGER,
+    amount          INTEGER,
+    data            TEXT NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE INDEX IF NOT EXISTS events_investor ON events(investor, name);
+
+-- kind: sol, wsol, token, auction_bid, pro_rata. payment_mint is NULL when paid in lamports.
+CREATE TABLE IF NOT EXISTS purchases (
+    signature        TEXT NOT NULL REFERENCES transactions(signature) ON DELETE CASCADE,
+    event_index      INTEGER NOT NULL,
+    kind             TEXT NOT NULL,
+    buyer            TEXT NOT NULL,
+    investor         TEXT NOT NULL,
+    position_index   INTEGER,
+    payment_mint     TEXT,
+    amount_paid      INTEGER NOT NULL,
+    amount_received  INTEGER NOT NULL,
+    tokens           INTEGER NOT NULL,
+    bonus_tokens     INTEGER NOT NULL,
+    referrer         TEXT,
+    PRIMARY KEY (signature, event_index)
+);
+
+-- kind: claim, referral_bonus, revocation (vested tokens paid out when a grant is revoked)
+CREATE TABLE IF NOT EXISTS claims (
+    signature       TEXT NOT NULL REFERENCES transactions(signature) ON DELETE CASCADE,
+    event_index     INTEGER NOT NULL,
+    kind            TEXT NOT NULL,
+    investor        TEXT NOT NULL,
+    position_index  INTEGER,
+    destination     TEXT,
+    amount          INTEGER NOT NULL,
+    keeper          TEXT,               -- Set for crank claims
+    keeper_fee      INTEGER,
+    PRIMARY KEY (signature, event_index)
+);
+
+CREATE TABLE IF NOT EXISTS admin_actions (
+    signature    TEXT NOT NULL REFERENCES transactions(signature) ON DELETE CASCADE,
+    event_index  INTEGER NOT NULL,
+    action       TEXT NOT NULL,
+    authority    TEXT,
+    target       TEXT,                  -- Investor, mint or wallet the action applies to
+    amount       INTEGER,
+    details      TEXT NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+
+-- Per position, from the events that name one
+CREATE TABLE IF NOT EXISTS positions (
+    wallet          TEXT NOT NULL,
+    position_index  INTEGER NOT NULL,
+    claimed_tokens  INTEGER NOT NULL,
+    claims          INTEGER NOT NULL,
+    blocked         INTEGER NOT NULL,  -- Last blocked and not unblocked since; expiries are not applied
+    removed         INTEGER NOT NULL,  -- Last removed and not whitelisted again since
+    revoked         INTEGER NOT NULL,
+    first_slot      INTEGER NOT NULL,
+    last_slot       INTEGER NOT NULL,
+    PRIMARY KEY (wallet, position_index)
+);
+
+-- Per wallet; purchase events do not name a position
+CREATE TABLE IF NOT EXISTS investors (
+    wallet             TEXT PRIMARY KEY,
+    positions          INTEGER NOT NULL,
+    purchased_tokens   INTEGER NOT NULL,
+    bonus_tokens       INTEGER NOT NULL,
+    sol_paid           INTEGER NOT NULL,
+    claimed_tokens     INTEGER NOT NULL,
+    claims             INTEGER NOT NULL,
+    blocked_positions  INTEGER NOT NULL,
+    removed_positions  INTEGER NOT NULL,
+    revoked_positions  INTEGER NOT NULL,
+    first_slot         INTEGER NOT NULL,
+    last_slot          INTEGER NOT NULL
+);
+
+-- cursor: newest signature at or below which every transaction is finalized
+CREATE TABLE IF NOT EXISTS sync_state (
+    id              INTEGER PRIMARY KEY CHECK (id = 1),
+    cursor          TEXT,
+    finalized_slot  INTEGER NOT NULL,
+    last_slot       INTEGER NOT NULL
+);
+INSERT OR IGNORE INTO sync_state (id, cursor, finalized_slot, last_slot) VALUES (1, NULL, 0, 0);
+";
+
+// Positions first, the investors table counts their statuses
+const REBUILD_SUMMARIES: &str = "
+DELETE FROM positions;
+INSERT INTO positions
+SELECT
+    w.investor,
+    w.position_index,
+    (SELECT COALESCE(SUM(amount), 0) FROM claims
+        WHERE investor = w.investor AND position_index = w.position_index
+          AND kind IN ('claim', 'revocation')),
+    (SELECT COUNT(*) FROM claims
+        WHERE investor = w.investor AND position_index = w.position_index
+          AND kind IN ('claim', 'revocation')),
+    COALESCE((SELECT e.name = 'InvestorBlocked' FROM events e JOIN transactions t USING (signature)
+        WHERE e.investor = w.investor AND e.position_index = w.position_index
+          AND e.name IN ('InvestorBlocked', 'InvestorUnblocked')
+        ORDER BY t.slot DESC, t.seq DESC, e.event_index DESC LIMIT 1), 0),
+    COALESCE((SELECT e.name = 'InvestorRemoved' FROM events e JOIN transactions t USING (signature)
+        WHERE e.investor = w.investor AND e.position_index = w.position_index
+          AND e.name IN ('InvestorRemoved', 'InvestorWhitelisted')
+        ORDER BY t.slot DESC, t.seq DESC, e.event_index DESC LIMIT 1), 0),
+    EXISTS (SELECT 1 FROM events
+        WHERE investor = w.investor AND position_index = w.position_index
+          AND name = 'VestingRevoked'),
+    MIN(t.slot),
+    MAX(t.slot)
+FROM events w JOIN transactions t USING (signature)
+WHERE w.investor IS NOT NULL AND w.position_index IS NOT NULL
+GROUP BY w.investor, w.position_index;
+
+DELETE FROM investors;
+INSERT INTO investors
+SELECT
+    w.investor,
+    (SELECT COUNT(DISTINCT position_index) FROM events
+        WHERE investor = w.investor AND name = 'InvestorWhitelisted'),
+    (SELECT COALESCE(SUM(tokens), 0) FROM purchases WHERE investor = w.investor),
+    (SELECT COALESCE(SUM(bonus_tokens), 0) FROM purchases WHERE investor = w.investor),
+    (SELECT COALESCE(SUM(amount_paid), 0) FROM purchases
+        WHERE investor = w.investor AND payment_mint IS NULL)
+        - (SELECT COALESCE(SUM(amount), 0) FROM events
+            WHERE investor = w.investor AND name = 'AuctionRebateClaimed'),
+    (SELECT COALESCE(SUM(amount), 0) FROM claims
+        WHERE investor = w.investor AND kind IN ('claim', 'revocation')),
+    (SELECT COUNT(*) FROM claims
+        WHERE investor = w.investor AND kind IN ('claim', 'revocation')),
+    (SELECT COUNT(*) FROM positions WHERE wallet = w.investor AND blocked),
+    (SELECT COUNT(*) FROM positions WHERE wallet = w.investor AND removed),
+    (SELECT COUNT(*) FROM positions WHERE wallet = w.investor AND revoked),
+    MIN(t.slot),
+    MAX(t.slot)
+FROM events w JOIN transactions t USING (signature)
+WHERE w.investor IS NOT NULL
+GROUP BY w.investor;
+";
+
+pub fn open(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+    init(conn)
+}
+
+#[cfg(test)]
+pub fn open_in_memory() -> Result<Connection> {
+    init(Connection::open_in_memory()?)
+}
+
+fn init(conn: Connection) -> Result<Connection> {
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+pub fn rebuild_summaries(conn: &Connection) -> Result<()> {
+    conn.execute_batch(REBUILD_SUMMARIES)?;
+    Ok(())
+}
+
+pub fn has_transaction(conn: &Connection, signature: &str) -> Result<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT 1 FROM transactions WHERE signature = ?1",
+            [signature],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+pub fn insert_transaction(
+    conn: &Connection,
+    signature: &str,
+    slot: u64,
+    block_time: Option<i64>,
+    failed: bool,
+    finalized: bool,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO transactions (signature, seq, slot, block_time, failed, finalized)
+         VALUES (?1, (SELECT COALESCE(MAX(seq), 0) + 1 FROM transactions), ?2, ?3, ?4, ?5)",
+        params![signature, slot, block_time, failed, finalized],
+    )?;
+    Ok(())
+}
+
+// A transaction seen again, possibly in another slot after a fork
+pub fn update_transaction(
+    conn: &Connection,
+    signature: &str,
+    slot: u64,
+    finalized: bool,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE transactions SET slot = ?2, finalized = ?3 WHERE signature = ?1",
+        params![signature, slot, finalized],
+    )?;
+    Ok(())
+}
+
+pub fn unfinalized_signatures(conn: &Connection) -> Result<Vec<String>> {
+    let mut statement =
+        conn.prepare("SELECT signature FROM transactions WHERE finalized = 0 ORDER BY seq")?;
+    let signatures = statement
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(signatures)
+}
+
+// Drops the transaction and, through the cascade, every row derived from it
+pub fn delete_transaction(conn: &Connection, signature: &str) -> Result<()> {
+    conn.execute("DELETE FROM transactions WHERE signature = ?1", [signature])?;
+    Ok(())
+}
+
+pub fn cursor(conn: &Connection) -> Result<Option<String>> {
+    Ok(
+        conn.query_row("SELECT cursor FROM sync_state WHERE id = 1", [], |row| {
+            row.get(0)
+        })?,
+    )
+}
+
+pub fn set_sync_state(
+    conn: &Connection,
+    cursor: Option<&str>,
+    finalized_slot: u64,
+    last_slot: u64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE sync_state SET cursor = ?1, finalized_slot = ?2, last_slot = MAX(last_slot, ?3)
+         WHERE id = 1",
+        params![cursor, finalized_slot, last_slot],
+    )?;
+    Ok(())
+}
+
+// Sum of an integer column over rows matching `filter`, 0 when there are none. Values in the
+// filter are bound through `params`, never formatted into it.
+pub fn sum<P: Params>(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    filter: &str,
+    params: P,
+) -> Result<u64> {
+    let total: i64 = conn.query_row(
+        &format!("SELECT COALESCE(SUM({column}), 0) FROM {table} WHERE {filter}"),
+        params,
+        |row| row.get(0),
+    )?;
+    Ok(total as u64)
+}
+
+pub fn count<P: Params>(conn: &Connection, table: &str, filter: &str, params: P) -> Result<u64> {
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {table} WHERE {filter}"),
+        params,
+        |row| row.get(0),
+    )?;
+    Ok(total as u64)
+}
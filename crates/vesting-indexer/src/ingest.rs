@@ -0,0 +1,367 @@
+// Decodes the events of one landed transaction into rows of the event tables.
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use vesting_client::events::{parse_logs, VestingEvent};
+use vesting_client::rpc::ConfirmedTransaction;
+
+use crate::db;
+
+// Columns of the generic events table: the wallet and position an event concerns, and its
+// headline amount
+struct Summary {
+    investor: Option<Pubkey>,
+    position_index: Option<u32>,
+    amount: Option<u64>,
+}
+
+impl Summary {
+    fn new(investor: Option<Pubkey>, position_index: Option<u32>, amount: Option<u64>) -> Self {
+        Summary {
+            investor,
+            position_index,
+            amount,
+        }
+    }
+}
+
+struct Purchase<'a> {
+    kind: &'a str,
+    buyer: Pubkey,
+    investor: Pubkey,
+    position_index: Option<u32>,
+    payment_mint: Option<Pubkey>,
+    amount_paid: u64,
+    amount_received: u64,
+    tokens: u64,
+    bonus_tokens: u64,
+    referrer: Option<Pubkey>,
+}
+
+/// Record a transaction and the events it emitted. Returns the number of events stored;
+/// failed transactions are recorded without any, their logs reverted with them.
+pub fn store_transaction(
+    conn: &Connection,
+    tx: &ConfirmedTransaction,
+    finalized: bool,
+) -> Result<usize> {
+    db::insert_transaction(
+        conn,
+        &tx.signature,
+        tx.slot,
+        tx.block_time,
+        tx.err.is_some(),
+        finalized,
+    )?;
+    if tx.err.is_some() {
+        return Ok(0);
+    }
+
+    let events = parse_logs(&tx.logs);
+    for (index, event) in events.iter().enumerate() {
+        store_event(conn, &tx.signature, index as i64, event)?;
+    }
+    Ok(events.len())
+}
+
+fn store_event(conn: &Connection, signature: &str, index: i64, event: &VestingEvent) -> Result<()> {
+    let summary = summarize(event);
+    conn.execute(
+        "INSERT INTO events (signature, event_index, name, investor, position_index, amount, data)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            signature,
+            index,
+            event.name(),
+            summary.investor.map(|k| k.to_string()),
+            summary.position_index,
+            summary.amount.map(|a| a as i64),
+            format!("{event:?}"),
+        ],
+    )?;
+
+    match event {
+        VestingEvent::TokenPurchaseEventForSol(e) => insert_purchase(
+            conn,
+            signature,
+            index,
+            Purchase {
+                kind: if e.wrapped { "wsol" } else { "sol" },
+                buyer: e.buyer,
+                investor: e.buyer,
+                position_index: None,
+                payment_mint: None,
+                amount_paid: e.sol_amount,
+                amount_received: e.sol_amount,
+                tokens: e.token_amount,
+                bonus_tokens: e.bonus_amount,
+                referrer: e.referrer,
+            },
+        ),
+        VestingEvent::TokenPurchaseEventForToken(e) => insert_purchase(
+            conn,
+            signature,
+            index,
+            Purchase {
+                kind: "token",
+                buyer: e.buyer,
+                investor: e.buyer,
+                position_index: None,
+                payment_mint: Some(e.mint),
+                amount_paid: e.token_amount_paid,
+                amount_received: e.token_amount_received,
+                tokens: e.token_amount,
+                bonus_tokens: e.bonus_amount,
+                referrer: e.referrer,
+            },
+        ),
+        // Lamports of a bid are held until settlement and partly rebated, so the sale
+        // counters only see them through AuctionSettled
+        VestingEvent::AuctionBidPlaced(e) => insert_purchase(
+            conn,
+            signature,
+            index,
+            Purchase {
+                kind: "auction_bid",
+                buyer: e.buyer,
+                investor: e.investor,
+                position_index: Some(e.position_index),
+                payment_mint: None,
+                amount_paid: e.sol_amount,
+                amount_received: e.sol_amount,
+                tokens: e.token_amount,
+                bonus_tokens: 0,
+                referrer: None,
+            },
+        ),
+        VestingEvent::CommitmentSettled(e) => insert_purchase(
+            conn,
+            signature,
+            index,
+            Purchase {
+                kind: "pro_rata",
+                buyer: e.investor,
+                investor: e.investor,
+                position_index: Some(e.position_index),
+                payment_mint: None,
+                amount_paid: e.sol_amount,
+                amount_received: e.sol_amount,
+                tokens: e.token_amount,
+                bonus_tokens: 0,
+                referrer: None,
+            },
+        ),
+        VestingEvent::TokensClaimed(e) => insert_claim(
+            conn,
+            signature,
+            index,
+            "claim",
+            &e.investor,
+            Some(e.position_index),
+            Some(&e.destination),
+            e.amount,
+        ),
+        VestingEvent::ReferralBonusClaimed(e) => insert_claim(
+            conn,
+            signature,
+            index,
+            "referral_bonus",
+            &e.referrer,
+            None,
+            None,
+            e.amount,
+        ),
+        VestingEvent::VestingRevoked(e) if e.paid_out > 0 => insert_claim(
+            conn,
+            signature,
+            index,
+            "revocation",
+            &e.investor,
+            Some(e.position_index),
+            None,
+            e.paid_out,
+        ),
+        // Emitted after the TokensClaimed of the same position in a crank
+        VestingEvent::CrankClaimed(e) => {
+            conn.execute(
+                "UPDATE claims SET keeper = ?1, keeper_fee = ?2
+                 WHERE signature = ?3 AND kind = 'claim' AND investor = ?4 AND position_index = ?5
+                   AND event_index = (SELECT MAX(event_index) FROM claims
+                        WHERE signature = ?3 AND kind = 'claim' AND investor = ?4
+                          AND position_index = ?5 AND event_index < ?6)",
+                params![
+                    e.keeper.to_string(),
+                    e.keeper_fee as i64,
+                    signature,
+                    e.investor.to_string(),
+                    e.position_index,
+                    index,
+                ],
+            )?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }?;
+
+    // Configuration and treasury events, plus revocations, which also pay out vested tokens
+    if let Some((authority, target, amount)) = admin_action(event) {
+        conn.execute(
+            "INSERT INTO admin_actions
+                (signature, event_index, action, authority, target, amount, details)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                signature,
+                index,
+                event.name(),
+                authority.map(|k| k.to_string()),
+                target.map(|k| k.to_string()),
+                amount.map(|a| a as i64),
+                format!("{event:?}"),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn insert_purchase(
+    conn: &Connection,
+    signature: &str,
+    index: i64,
+    purchase: Purchase<'_>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO purchases (signature, event_index, kind, buyer, investor, position_index,
+            payment_mint, amount_paid, amount_received, tokens, bonus_tokens, referrer)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            signature,
+            index,
+            purchase.kind,
+            purchase.buyer.to_string(),
+            purchase.investor.to_string(),
+            purchase.position_index,
+            purchase.payment_mint.map(|k| k.to_string()),
+            purchase.amount_paid as i64,
+            purchase.amount_received as i64,
+            purchase.tokens as i64,
+            purchase.bonus_tokens as i64,
+            purchase.referrer.map(|k| k.to_string()),
+        ],
+    )?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)] // One per column
+fn insert_claim(
+    conn: &Connection,
+    signature: &str,
+    index: i64,
+    kind: &str,
+    investor: &Pubkey,
+    position_index: Option<u32>,
+    destination: Option<&Pubkey>,
+    amount: u64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO claims
+            (signature, event_index, kind, investor, position_index, destination, amount)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            signature,
+            index,
+            kind,
+            investor.to_string(),
+            position_index,
+            destination.map(|k| k.to_string()),
+            amount as i64,
+        ],
+    )?;
+    Ok(())
+}
+
+fn summarize(event: &VestingEvent) -> Summary {
+    use VestingEvent::*;
+    match event {
+        InvestorWhitelisted(e) => {
+            Summary::new(Some(e.investor), Some(e.position_index), Some(e.allocation))
+        }
+        TokenPurchaseEventForSol(e) => Summary::new(Some(e.buyer), None, Some(e.token_amount)),
+        TokenPurchaseEventForToken(e) => Summary::new(Some(e.buyer), None, Some(e.token_amount)),
+        InvestorBlocked(e) => Summary::new(Some(e.investor), Some(e.position_index), None),
+        InvestorUnblocked(e) => Summary::new(Some(e.investor), Some(e.position_index), None),
+        InvestorRemoved(e) => Summary::new(Some(e.investor), Some(e.position_index), None),
+        VestingRevoked(e) => {
+            Summary::new(Some(e.investor), Some(e.position_index), Some(e.paid_out))
+        }
+        PositionTransferred(e) => {
+            Summary::new(Some(e.from), Some(e.from_index), Some(e.allocation))
+        }
+        PositionTokenized(e) => Summary::new(Some(e.investor), None, None),
+        ClaimDestinationUpdated(e) => Summary::new(Some(e.investor), Some(e.position_index), None),
+        CrankClaimed(e) => Summary::new(Some(e.investor), Some(e.position_index), Some(e.amount)),
+        TokensClaimed(e) => Summary::new(Some(e.investor), Some(e.position_index), Some(e.amount)),
+        AuctionBidPlaced(e) => Summary::new(
+            Some(e.investor),
+            Some(e.position_index),
+            Some(e.token_amount),
+        ),
+        AuctionRebateClaimed(e) => {
+            Summary::new(Some(e.investor), Some(e.position_index), Some(e.amount))
+        }
+        SaleCommitted(e) => {
+            Summary::new(Some(e.investor), Some(e.position_index), Some(e.sol_amount))
+        }
+        CommitmentSettled(e) => Summary::new(
+            Some(e.investor),
+            Some(e.position_index),
+            Some(e.token_amount),
+        ),
+        ReferralBonusClaimed(e) => Summary::new(Some(e.referrer), None, Some(e.amount)),
+        ReferrerRegistered(e) => Summary::new(Some(e.referrer), None, None),
+        ReferralRecorded(e) => Summary::new(Some(e.referrer), None, Some(e.bonus_tokens)),
+        AuctionSettled(e) => Summary::new(None, None, Some(e.proceeds)),
+        SaleFinalized(e) => Summary::new(None, None, Some(e.total_committed)),
+        PrizeDeposited(e) => Summary::new(None, None, Some(e.amount)),
+        WithdrawnSol(e) => Summary::new(None, None, Some(e.amount)),
+        PrizeWithdrawTokens(e) => Summary::new(None, None, Some(e.amount)),
+        PaymentTokensWithdrawn(e) => Summary::new(None, None, Some(e.amount)),
+        _ => Summary::new(None, None, None),
+    }
+}
+
+// Authority, target and amount of configuration and treasury events; None for the
+// investor-facing events stored in the other tables
+fn admin_action(event: &VestingEvent) -> Option<(Option<Pubkey>, Option<Pubkey>, Option<u64>)> {
+    use VestingEvent::*;
+    Some(match event {
+        InitializeEvent(e) => (Some(e.authority), None, None),
+        PaymentMintUpdated(e) => (Some(e.authority), Some(e.mint), None),
+        ICODateChanged(e) => (Some(e.authority), None, None),
+        InvestorBlocked(e) => (Some(e.blocked_by), Some(e.investor), None),
+        InvestorUnblocked(e) => (Some(e.unblocked_by), Some(e.investor), None),
+        InvestorRemoved(e) => (Some(e.removed_by), Some(e.investor), None),
+        VestingRevoked(e) => (Some(e.revoked_by), Some(e.investor), Some(e.clawed_back)),
+        ClawbackDestinationUpdated(e) => (Some(e.authority), Some(e.destination), None),
+        PositionTransferConfigUpdated(e) => (Some(e.authority), None, None),
+        AuctionConfigured(e) => (Some(e.authority), None, Some(e.supply)),
+        AuctionSettled(e) => (None, None, Some(e.proceeds)),
+        ProRataSaleConfigured(e) => (Some(e.authority), None, Some(e.token_cap)),
+        SaleFinalized(e) => (None, None, Some(e.total_committed)),
+        BonusTiersUpdated(e) => (Some(e.authority), None, None),
+        ReferralConfigUpdated(e) => (Some(e.authority), None, None),
+        KeeperFeeUpdated(e) => (Some(e.authority), None, None),
+        PrizeTokenUpdated(e) => (Some(e.authority), Some(e.token), None),
+        WithdrawnSol(e) => (Some(e.authority), Some(e.recipient), Some(e.amount)),
+        PrizeWithdrawTokens(e) => (Some(e.authority), None, Some(e.amount)),
+        PaymentTokensWithdrawn(e) => (Some(e.authority), Some(e.mint), Some(e.amount)),
+        PrizeDeposited(e) => (Some(e.depositor), None, Some(e.amount)),
+        SolTreasuryUpdated(e) => (Some(e.authority), Some(e.sol_treasury), None),
+        TGEDateChanged(e) => (Some(e.authority), None, None),
+        SecondsPerDayChanged(e) => (Some(e.authority), None, None),
+        OwnershipTransferred(e) => (Some(e.previous_owner), Some(e.new_owner), None),
+        OwnershipRenounced(e) => (Some(e.previous_owner), None, None),
+        PausedEvent(e) => (Some(e.authority), None, None),
+        _ => return None,
+    })
+}
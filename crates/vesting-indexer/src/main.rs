@@ -0,0 +1,116 @@
+//! Indexer for the vesting program's events.
+//!
+//! Reads the program's transactions from an RPC node, or from `getTransaction` results saved
+//! as JSON, decodes their Anchor events and materializes purchases, claims, admin actions
+//! and per-wallet investor totals into SQLite. Transactions that are not yet finalized are
+//! re-checked on every sync and rolled back if a fork drops them. `reconcile` compares the
+//! indexed totals with the counters stored on chain.
+
+mod db;
+mod ingest;
+mod reconcile;
+mod sync;
+
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
+use vesting_client::rpc::{RpcClient, DEFAULT_URL};
+
+#[derive(Parser)]
+#[command(
+    name = "vesting-indexer",
+    version,
+    about = "Indexes vesting program events into SQLite"
+)]
+struct Cli {
+    /// JSON-RPC endpoint
+    #[arg(long, short = 'u', env = "VESTING_RPC_URL", default_value = DEFAULT_URL, global = true)]
+    url: String,
+
+    /// SQLite database, created if missing
+    #[arg(
+        long,
+        env = "VESTING_INDEX_DB",
+        default_value = "vesting-index.sqlite",
+        global = true
+    )]
+    db: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Index the program's transactions since the last finalized one seen
+    Sync {
+        /// Keep polling instead of exiting after one pass
+        #[arg(long)]
+        follow: bool,
+        /// Seconds between passes with --follow
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+    /// Index getTransaction results saved as JSON files
+    Import {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Compare indexed totals with the on-chain sale counters
+    Reconcile,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut conn = db::open(&cli.db)?;
+    let client = RpcClient::new(cli.url);
+
+    match cli.command {
+        Command::Sync { follow, interval } => loop {
+            let report = sync::sync(&mut conn, &client)?;
+            println!(
+                "Indexed {} transactions ({} events), rolled back {}, {} pending; finalized slot {}",
+                report.transactions,
+                report.events,
+                report.rolled_back,
+                report.pending,
+                report.finalized_slot
+            );
+            if !follow {
+                break;
+            }
+            thread::sleep(Duration::from_secs(interval));
+        },
+        Command::Import { files } => {
+            let report = sync::import(&mut conn, &files)?;
+            println!(
+                "Indexed {} transactions ({} events)",
+                report.transactions, report.events
+            );
+        }
+        Command::Reconcile => {
+            let checks = reconcile::reconcile(&conn, &client)?;
+            println!(
+                "{:<58} {:>20} {:>20}  status",
+                "counter", "on-chain", "indexed"
+            );
+            for check in &checks {
+                println!(
+                    "{:<58} {:>20} {:>20}  {}",
+                    check.counter,
+                    check.on_chain,
+                    check.indexed,
+                    if check.matches() { "ok" } else { "MISMATCH" }
+                );
+            }
+            let mismatches = checks.iter().filter(|c| !c.matches()).count();
+            if mismatches > 0 {
+                bail!("{mismatches} counters do not match the index; sync first if it is behind");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// Compares totals derived from the indexed events with the counters the program keeps in
+// its TokenIco config and PaymentMint accounts.
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use vesting_client::rpc::RpcClient;
+use vesting_client::{pda, state};
+
+use crate::db;
+
+pub struct Check {
+    pub counter: String,
+    pub on_chain: u64,
+    pub indexed: u64,
+}
+
+impl Check {
+    pub fn matches(&self) -> bool {
+        self.on_chain == self.indexed
+    }
+}
+
+pub fn reconcile(conn: &Connection, client: &RpcClient) -> Result<Vec<Check>> {
+    let (config_address, _) = pda::config();
+    let data = client
+        .get_account_data(&config_address)?
+        .context("ICO config account not found")?;
+    let config = state::token_ico(&data).context("decoding the ICO config")?;
+
+    let mut checks = vec![
+        // Vesting claims, referral bonus claims and vested tokens paid out on revocation
+        Check {
+            counter: "total_claimed".into(),
+            on_chain: config.total_claimed,
+            indexed: db::sum(conn, "claims", "amount", "1", [])?,
+        },
+        Check {
+            counter: "claim_count".into(),
+            on_chain: config.claim_count,
+            indexed: db::count(conn, "claims", "1", [])?,
+        },
+        // Auction bids only count once settled, at the clearing price
+        Check {
+            counter: "sol_raised".into(),
+            on_chain: config.sol_raised,
+            indexed: db::sum(
+                conn,
+                "purchases",
+                "amount_paid",
+                "kind IN ('sol', 'wsol', 'pro_rata')",
+                [],
+            )? + db::sum(conn, "events", "amount", "name = 'AuctionSettled'", [])?,
+        },
+        // Base and bonus tokens of purchases; referral bonuses are tracked per referrer
+        Check {
+            counter: "total_user_allocated".into(),
+            on_chain: config.total_user_allocated,
+            indexed: db::sum(conn, "purchases", "tokens + bonus_tokens", "1", [])?,
+        },
+        Check {
+            counter: "total_prize_deposited".into(),
+            on_chain: config.total_prize_deposited,
+            indexed: db::sum(conn, "events", "amount", "name = 'PrizeDeposited'", [])?,
+        },
+    ];
+
+    let mints = {
+        let mut statement = conn.prepare(
+            "SELECT DISTINCT payment_mint FROM purchases WHERE payment_mint IS NOT NULL
+             ORDER BY payment_mint",
+        )?;
+        let mints = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        mints
+    };
+    for mint in mints {
+        let mint_key: Pubkey = mint.parse().context("invalid payment mint in the index")?;
+        let on_chain = match client.get_account_data(&pda::payment_mint(&mint_key).0)? {
+            Some(data) => {
+                state::decode_account::<state::PaymentMint>(&data)
+                    .with_context(|| format!("decoding the payment mint {mint}"))?
+                    .total_raised
+            }
+            None => 0,
+        };
+        checks.push(Check {
+            counter: format!("total_raised[{mint}]"),
+            on_chain,
+            indexed: db::sum(
+                conn,
+                "purchases",
+                "amount_received",
+                "payment_mint = ?1",
+                [&mint],
+            )?,
+        });
+    }
+    Ok(checks)
+}
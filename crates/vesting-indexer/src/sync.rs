@@ -0,0 +1,594 @@
+// Follows the program's transactions over RPC, or loads them from files.
+//
+// Transactions are read at `confirmed` commitment, so the newest ones can still be dropped
+// by a fork. They are stored as unfinalized and re-checked on every run: any that no longer
+// appear in the signature list are deleted with their rows. The cursor only advances over
+// finalized transactions, so the next run lists everything that can still change.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use serde_json::Value;
+use vesting_client::rpc::{ConfirmedTransaction, RpcClient, SignatureInfo};
+use vesting_client::PROGRAM_ID;
+
+use crate::{db, ingest};
+
+// getSignaturesForAddress page size, the RPC maximum
+const PAGE_LIMIT: usize = 1_000;
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub transactions: usize,
+    pub events: usize,
+    pub rolled_back: usize,
+    pub pending: usize,
+    pub finalized_slot: u64,
+}
+
+pub fn sync(conn: &mut Connection, client: &RpcClient) -> Result<Report> {
+    let finalized_slot = client
+        .get_slot_with_commitment("finalized")
+        .context("reading the finalized slot")?;
+    let cursor = db::cursor(conn)?;
+    let signatures = signatures_since(client, cursor.as_deref())?;
+
+    apply(conn, &signatures, finalized_slot, |signature| {
+        client
+            .get_transaction(signature)
+            .with_context(|| format!("fetching {signature}"))
+    })
+}
+
+// Bring the index in line with the signatures listed since the cursor, oldest first. `fetch`
+// returns a transaction not stored yet, or None while the node cannot serve it.
+fn apply(
+    conn: &mut Connection,
+    signatures: &[SignatureInfo],
+    finalized_slot: u64,
+    mut fetch: impl FnMut(&str) -> Result<Option<ConfirmedTransaction>>,
+) -> Result<Report> {
+    let cursor = db::cursor(conn)?;
+    let mut report = Report {
+        finalized_slot,
+        ..Report::default()
+    };
+    let tx = conn.transaction()?;
+
+    // Unfinalized transactions missing from the listing were on an abandoned fork
+    let listed: HashSet<&str> = signatures.iter().map(|s| s.signature.as_str()).collect();
+    for signature in db::unfinalized_signatures(&tx)? {
+        if !listed.contains(signature.as_str()) {
+            db::delete_transaction(&tx, &signature)?;
+            report.rolled_back += 1;
+        }
+    }
+
+    // Oldest first, advancing the cursor while every transaction so far is final
+    let mut new_cursor = cursor;
+    let mut settled = true;
+    let mut last_slot = 0;
+    for info in signatures {
+        let finalized = info.slot <= finalized_slot;
+        last_slot = last_slot.max(info.slot);
+        if db::has_transaction(&tx, &info.signature)? {
+            db::update_transaction(&tx, &info.signature, info.slot, finalized)?;
+        } else if !store(&tx, &mut fetch, info, finalized, &mut report)? {
+            // Listed but not yet served by getTransaction; retried on the next run
+            report.pending += 1;
+            settled = false;
+            continue;
+        }
+        settled &= finalized;
+        if settled {
+            new_cursor = Some(info.signature.clone());
+        }
+    }
+
+    db::set_sync_state(&tx, new_cursor.as_deref(), finalized_slot, last_slot)?;
+    db::rebuild_summaries(&tx)?;
+    tx.commit()?;
+    Ok(report)
+}
+
+// Fetch and store one listed transaction; false when the node cannot return it yet
+fn store(
+    conn: &Connection,
+    fetch: &mut impl FnMut(&str) -> Result<Option<ConfirmedTransaction>>,
+    info: &SignatureInfo,
+    finalized: bool,
+    report: &mut Report,
+) -> Result<bool> {
+    // A failed transaction leaves no events, so there is nothing to fetch
+    if info.err.is_some() {
+        db::insert_transaction(
+            conn,
+            &info.signature,
+            info.slot,
+            info.block_time,
+            true,
+            finalized,
+        )?;
+        report.transactions += 1;
+        return Ok(true);
+    }
+    let Some(transaction) = fetch(&info.signature)? else {
+        return Ok(false);
+    };
+    report.events += ingest::store_transaction(conn, &transaction, finalized)?;
+    report.transactions += 1;
+    Ok(true)
+}
+
+// Signatures newer than `until`, oldest first
+fn signatures_since(client: &RpcClient, until: Option<&str>) -> Result<Vec<SignatureInfo>> {
+    let mut signatures = Vec::new();
+    let mut before: Option<String> = None;
+    loop {
+        let page = client
+            .get_signatures_for_address(&PROGRAM_ID, before.as_deref(), until, PAGE_LIMIT)
+            .context("listing program signatures")?;
+        let full = page.len() == PAGE_LIMIT;
+        before = page.last().map(|s| s.signature.clone());
+        signatures.extend(page);
+        if !full {
+            break;
+        }
+    }
+    signatures.reverse();
+    Ok(signatures)
+}
+
+/// Load `getTransaction` results saved as JSON: a single result, an array of them, or full
+/// JSON-RPC responses. Transactions already indexed are skipped; the rest are stored as
+/// finalized, in slot order.
+pub fn import(conn: &mut Connection, files: &[PathBuf]) -> Result<Report> {
+    let mut transactions = Vec::new();
+    for file in files {
+        let text =
+            fs::read_to_string(file).with_context(|| format!("reading {}", file.display()))?;
+        let value: Value =
+            serde_json::from_str(&text).with_context(|| format!("parsing {}", file.display()))?;
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = if value.get("jsonrpc").is_some() {
+                &value["result"]
+            } else {
+                &value
+            };
+            if value.is_null() {
+                continue;
+            }
+            transactions.push(
+                ConfirmedTransaction::from_json(value)
+                    .with_context(|| format!("decoding a transaction in {}", file.display()))?,
+            );
+        }
+    }
+    transactions.sort_by_key(|t| t.slot);
+
+    let mut report = Report::default();
+    let tx = conn.transaction()?;
+    let mut last_slot = 0;
+    for transaction in &transactions {
+        last_slot = last_slot.max(transaction.slot);
+        if db::has_transaction(&tx, &transaction.signature)? {
+            continue;
+        }
+        report.events += ingest::store_transaction(&tx, transaction, true)?;
+        report.transactions += 1;
+    }
+    let cursor = db::cursor(&tx)?;
+    let finalized_slot = tx.query_row("SELECT finalized_slot FROM sync_state", [], |row| {
+        row.get::<_, i64>(0)
+    })? as u64;
+    db::set_sync_state(&tx, cursor.as_deref(), finalized_slot, last_slot)?;
+    db::rebuild_summaries(&tx)?;
+    tx.commit()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anchor_lang::prelude::Pubkey;
+    use anchor_lang::Event;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde_json::json;
+    use vesting_program::{
+        InvestorBlocked, InvestorUnblocked, InvestorWhitelisted, TokenPurchaseEventForSol,
+        TokenPurchaseEventForToken, VestingRevoked,
+    };
+
+    use super::*;
+
+    const FINALIZED_SLOT: u64 = 100;
+
+    fn whitelisted(investor: Pubkey, position_index: u32) -> Vec<u8> {
+        InvestorWhitelisted {
+            investor,
+            position_index,
+            allocation: 5_000,
+            vesting_type: 1,
+            cliff_end: 0,
+        }
+        .data()
+    }
+
+    fn blocked(investor: Pubkey, position_index: u32) -> Vec<u8> {
+        InvestorBlocked {
+            investor,
+            position_index,
+            blocked_by: Pubkey::new_unique(),
+            reason: 1,
+            expires_at: 0,
+            timestamp: 1_700_000_000,
+        }
+        .data()
+    }
+
+    fn unblocked(investor: Pubkey, position_index: u32) -> Vec<u8> {
+        InvestorUnblocked {
+            investor,
+            position_index,
+            unblocked_by: Pubkey::new_unique(),
+            reason: 1,
+            timestamp: 1_700_000_000,
+        }
+        .data()
+    }
+
+    fn revoked(investor: Pubkey, position_index: u32, paid_out: u64) -> Vec<u8> {
+        VestingRevoked {
+            investor,
+            position_index,
+            revoked_by: Pubkey::new_unique(),
+            vested: paid_out,
+            clawed_back: 1_000,
+            paid_out,
+            clawback_destination: Pubkey::new_unique(),
+            timestamp: 1_700_000_000,
+        }
+        .data()
+    }
+
+    fn bought_with_sol(buyer: Pubkey, tokens: u64) -> Vec<u8> {
+        TokenPurchaseEventForSol {
+            buyer,
+            sol_amount: tokens * 1_000,
+            token_amount: tokens,
+            bonus_amount: 0,
+            timestamp: 1_700_000_000,
+            wrapped: false,
+            referrer: None,
+        }
+        .data()
+    }
+
+    fn bought_with_token(buyer: Pubkey, mint: Pubkey, received: u64) -> Vec<u8> {
+        TokenPurchaseEventForToken {
+            buyer,
+            mint,
+            token_amount_paid: received,
+            token_amount_received: received,
+            token_amount: received * 2,
+            bonus_amount: 0,
+            timestamp: 1_700_000_000,
+            treasury: Pubkey::new_unique(),
+            referrer: None,
+        }
+        .data()
+    }
+
+    fn logs(events: &[Vec<u8>]) -> Vec<String> {
+        let mut logs = vec![format!("Program {PROGRAM_ID} invoke [1]")];
+        logs.extend(
+            events
+                .iter()
+                .map(|data| format!("Program data: {}", STANDARD.encode(data))),
+        );
+        logs.push(format!("Program {PROGRAM_ID} success"));
+        logs
+    }
+
+    fn transaction(signature: &str, slot: u64, events: &[Vec<u8>]) -> ConfirmedTransaction {
+        ConfirmedTransaction {
+            signature: signature.to_string(),
+            slot,
+            block_time: Some(1_700_000_000),
+            err: None,
+            logs: logs(events),
+            return_data: None,
+        }
+    }
+
+    fn listed(signature: &str, slot: u64) -> SignatureInfo {
+        SignatureInfo {
+            signature: signature.to_string(),
+            slot,
+            err: None,
+            block_time: Some(1_700_000_000),
+        }
+    }
+
+    // Serves each transaction once, then None as for a transaction the node does not have
+    fn serve(
+        mut transactions: HashMap<&'static str, ConfirmedTransaction>,
+    ) -> impl FnMut(&str) -> Result<Option<ConfirmedTransaction>> {
+        move |signature| Ok(transactions.remove(signature))
+    }
+
+    fn rows(conn: &Connection, table: &str, signature: &str) -> u64 {
+        db::count(conn, table, "signature = ?1", [signature]).unwrap()
+    }
+
+    fn investors(conn: &Connection) -> Vec<(String, u64)> {
+        let mut statement = conn
+            .prepare("SELECT wallet, purchased_tokens FROM investors ORDER BY wallet")
+            .unwrap();
+        statement
+            .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    fn finalized(conn: &Connection, signature: &str) -> bool {
+        conn.query_row(
+            "SELECT finalized FROM transactions WHERE signature = ?1",
+            [signature],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    fn last_slot(conn: &Connection) -> u64 {
+        conn.query_row("SELECT last_slot FROM sync_state", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .unwrap() as u64
+    }
+
+    #[test]
+    fn dropped_transactions_are_rolled_back() {
+        let mut conn = db::open_in_memory().unwrap();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let served = HashMap::from([
+            ("a", transaction("a", 90, &[whitelisted(alice, 0)])),
+            ("b", transaction("b", 101, &[bought_with_sol(bob, 700)])),
+            ("c", transaction("c", 102, &[bought_with_sol(alice, 300)])),
+        ]);
+        let report = apply(
+            &mut conn,
+            &[listed("a", 90), listed("b", 101), listed("c", 102)],
+            FINALIZED_SLOT,
+            serve(served),
+        )
+        .unwrap();
+        assert_eq!(report.transactions, 3);
+        assert_eq!(report.events, 3);
+        assert_eq!(
+            db::unfinalized_signatures(&conn).unwrap(),
+            ["b".to_string(), "c".to_string()]
+        );
+        let mut expected = vec![(alice.to_string(), 300), (bob.to_string(), 700)];
+        expected.sort();
+        assert_eq!(investors(&conn), expected);
+
+        // The next listing, from the cursor at `a`, no longer has `b`. Nothing is fetched again.
+        let report = apply(
+            &mut conn,
+            &[listed("c", 102)],
+            FINALIZED_SLOT,
+            serve(HashMap::new()),
+        )
+        .unwrap();
+        assert_eq!(report.rolled_back, 1);
+        assert_eq!((report.transactions, report.pending), (0, 0));
+        assert!(!db::has_transaction(&conn, "b").unwrap());
+        assert_eq!(rows(&conn, "events", "b"), 0);
+        assert_eq!(rows(&conn, "purchases", "b"), 0);
+        assert_eq!(rows(&conn, "purchases", "c"), 1);
+        assert_eq!(investors(&conn), [(alice.to_string(), 300)]);
+        assert_eq!(db::cursor(&conn).unwrap().as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn cursor_only_advances_over_finalized_transactions() {
+        let mut conn = db::open_in_memory().unwrap();
+        let buyer = Pubkey::new_unique();
+        let listing = [
+            listed("a", 90),
+            listed("b", 95),
+            listed("c", 96),
+            listed("d", 120),
+        ];
+
+        // `b` is listed but not served yet, which holds the cursor at `a`
+        let served = HashMap::from([
+            ("a", transaction("a", 90, &[bought_with_sol(buyer, 1)])),
+            ("c", transaction("c", 96, &[bought_with_sol(buyer, 2)])),
+            ("d", transaction("d", 120, &[bought_with_sol(buyer, 3)])),
+        ]);
+        let report = apply(&mut conn, &listing, FINALIZED_SLOT, serve(served)).unwrap();
+        assert_eq!(report.transactions, 3);
+        assert_eq!(report.pending, 1);
+        assert_eq!(db::cursor(&conn).unwrap().as_deref(), Some("a"));
+        assert_eq!(last_slot(&conn), 120);
+        assert!(finalized(&conn, "c"));
+        assert!(!finalized(&conn, "d"));
+
+        // Once `b` is served the cursor moves up to the last finalized transaction, `c`
+        let served = HashMap::from([("b", transaction("b", 95, &[bought_with_sol(buyer, 4)]))]);
+        let report = apply(&mut conn, &listing[1..], FINALIZED_SLOT, serve(served)).unwrap();
+        assert_eq!((report.transactions, report.pending), (1, 0));
+        assert_eq!(db::cursor(&conn).unwrap().as_deref(), Some("c"));
+
+        // And past `d` when its slot is finalized
+        let report = apply(&mut conn, &listing[3..], 130, serve(HashMap::new())).unwrap();
+        assert_eq!((report.transactions, report.rolled_back), (0, 0));
+        assert_eq!(db::cursor(&conn).unwrap().as_deref(), Some("d"));
+        assert!(finalized(&conn, "d"));
+        assert!(db::unfinalized_signatures(&conn).unwrap().is_empty());
+        assert_eq!(investors(&conn), [(buyer.to_string(), 10)]);
+    }
+
+    // (position_index, claimed_tokens, blocked, revoked) of every position of `wallet`
+    fn positions(conn: &Connection, wallet: &Pubkey) -> Vec<(u32, u64, bool, bool)> {
+        let mut statement = conn
+            .prepare(
+                "SELECT position_index, claimed_tokens, blocked, revoked FROM positions
+                 WHERE wallet = ?1 ORDER BY position_index",
+            )
+            .unwrap();
+        statement
+            .query_map([wallet.to_string()], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get::<_, i64>(1)? as u64,
+                    row.get(2)?,
+                    row.get(3)?,
+                ))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn status_is_tracked_per_position() {
+        let mut conn = db::open_in_memory().unwrap();
+        let wallet = Pubkey::new_unique();
+        let served = HashMap::from([
+            (
+                "a",
+                transaction("a", 90, &[whitelisted(wallet, 0), whitelisted(wallet, 1)]),
+            ),
+            (
+                "b",
+                transaction("b", 91, &[blocked(wallet, 0), blocked(wallet, 1)]),
+            ),
+            ("c", transaction("c", 92, &[unblocked(wallet, 0)])),
+            ("d", transaction("d", 93, &[revoked(wallet, 1, 250)])),
+        ]);
+        let listing = [
+            listed("a", 90),
+            listed("b", 91),
+            listed("c", 92),
+            listed("d", 93),
+        ];
+        apply(&mut conn, &listing, FINALIZED_SLOT, serve(served)).unwrap();
+
+        // Unblocking and revoking one position leaves the other as it was
+        assert_eq!(
+            positions(&conn, &wallet),
+            [(0, 0, false, false), (1, 250, true, true)]
+        );
+        let counts = conn
+            .query_row(
+                "SELECT positions, claimed_tokens, blocked_positions, removed_positions,
+                        revoked_positions
+                 FROM investors WHERE wallet = ?1",
+                [wallet.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, i64>(4)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(counts, (2, 250, 1, 0, 1));
+    }
+
+    // A saved file under the temp directory, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &Value) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "vesting-indexer-{}-{name}.json",
+                std::process::id()
+            ));
+            fs::write(&path, contents.to_string()).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn saved(signature: &str, slot: u64, events: &[Vec<u8>]) -> Value {
+        json!({
+            "slot": slot,
+            "blockTime": 1_700_000_000,
+            "transaction": { "signatures": [signature] },
+            "meta": { "err": null, "logMessages": logs(events), "returnData": null },
+        })
+    }
+
+    #[test]
+    fn import_loads_saved_transactions_once() {
+        let mut conn = db::open_in_memory().unwrap();
+        let (buyer, usdc, usdt) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let response = TempFile::new(
+            "response",
+            &json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": saved("b", 220, &[bought_with_token(buyer, usdc, 40)]),
+            }),
+        );
+        let array = TempFile::new(
+            "array",
+            &json!([
+                saved("a", 210, &[bought_with_token(buyer, usdc, 25)]),
+                null,
+                saved("c", 230, &[bought_with_token(buyer, usdt, 9)]),
+            ]),
+        );
+        let files = [response.0.clone(), array.0.clone()];
+
+        let report = import(&mut conn, &files).unwrap();
+        assert_eq!((report.transactions, report.events), (3, 3));
+        assert!(db::unfinalized_signatures(&conn).unwrap().is_empty());
+        assert_eq!(last_slot(&conn), 230);
+        assert_eq!(db::cursor(&conn).unwrap(), None);
+        let received = |mint: Pubkey| {
+            db::sum(
+                &conn,
+                "purchases",
+                "amount_received",
+                "payment_mint = ?1",
+                [mint.to_string()],
+            )
+            .unwrap()
+        };
+        assert_eq!(received(usdc), 65);
+        assert_eq!(received(usdt), 9);
+        assert_eq!(investors(&conn), [(buyer.to_string(), 148)]);
+
+        // Importing the same files again adds nothing
+        let report = import(&mut conn, &files).unwrap();
+        assert_eq!((report.transactions, report.events), (0, 0));
+        assert_eq!(db::count(&conn, "purchases", "1", []).unwrap(), 3);
+    }
+}
@@ -0,0 +1,391 @@
+//! Vesting schedule math, free of Anchor and Solana dependencies.
+//!
+//! The on-chain program and off-chain clients both call [`calculate_claimable_tokens`],
+//! so they always agree on what a position can claim.
+
+// Vesting Types
+pub const VESTING_TYPE_IMMEDIATE: u8 = 0; // 10% at TGE, rest daily over 365 days
+pub const VESTING_TYPE_LINEAR: u8 = 1; // 5% at TGE, then milestones at 8, 18, 24 and 36 months
+
+// Length of a vesting month in days
+pub const DAYS_PER_MONTH: u64 = 30;
+
+// Days over which immediate vesting releases the 90% left after TGE
+pub const IMMEDIATE_VESTING_DAYS: u64 = 365;
+
+// Linear vesting milestones: months after TGE and percentage of the allocation released
+pub const LINEAR_MILESTONES: [(u64, u64); 4] = [(8, 10), (18, 20), (24, 20), (36, 45)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VestingError {
+    InvalidVestingType,
+    ArithmeticOverflow,
+}
+
+impl std::fmt::Display for VestingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VestingError::InvalidVestingType => write!(f, "unsupported vesting type"),
+            VestingError::ArithmeticOverflow => write!(f, "arithmetic overflow"),
+        }
+    }
+}
+
+impl std::error::Error for VestingError {}
+
+pub type Result<T> = std::result::Result<T, VestingError>;
+
+// `percent` percent of `amount`, rounded down
+fn percent_of(amount: u64, percent: u64) -> Result<u64> {
+    amount
+        .checked_mul(percent)
+        .ok_or(VestingError::ArithmeticOverflow)?
+        .checked_div(100)
+        .ok_or(VestingError::ArithmeticOverflow)
+}
+
+/// Tokens released by `current_time` out of `total_allocation`. The schedule always ends
+/// fully vested, so rounding never leaves tokens locked.
+pub fn total_releasable(
+    vesting_type: u8,
+    total_allocation: u64,
+    tge_time: u64,
+    seconds_per_day: u64,
+    current_time: u64,
+) -> Result<u64> {
+    // Time elapsed since TGE
+    let time_elapsed = current_time.saturating_sub(tge_time);
+
+    match vesting_type {
+        // Immediate vesting - 10% at TGE, rest linearly over 365 days
+        VESTING_TYPE_IMMEDIATE => {
+            let initial_release = percent_of(total_allocation, 10)?;
+
+            // Remaining 90% for daily vesting
+            let daily_release = total_allocation
+                .checked_sub(initial_release)
+                .ok_or(VestingError::ArithmeticOverflow)?
+                / IMMEDIATE_VESTING_DAYS;
+
+            // Days passed since TGE
+            let days_passed = if time_elapsed == 0 {
+                0
+            } else {
+                time_elapsed
+                    .checked_div(seconds_per_day)
+                    .ok_or(VestingError::ArithmeticOverflow)?
+            };
+
+            // The last day releases whatever rounding held back
+            if days_passed >= IMMEDIATE_VESTING_DAYS {
+                return Ok(total_allocation);
+            }
+
+            initial_release
+                .checked_add(
+                    daily_release
+                        .checked_mul(days_passed)
+                        .ok_or(VestingError::ArithmeticOverflow)?,
+                )
+                .ok_or(VestingError::ArithmeticOverflow)
+        }
+
+        // Linear vesting with milestones
+        VESTING_TYPE_LINEAR => {
+            let month_in_seconds = seconds_per_day
+                .checked_mul(DAYS_PER_MONTH)
+                .ok_or(VestingError::ArithmeticOverflow)?;
+
+            // Start with TGE release (5%)
+            let mut vested_amount = percent_of(total_allocation, 5)?;
+
+            for (months, percent) in LINEAR_MILESTONES {
+                let milestone = month_in_seconds
+                    .checked_mul(months)
+                    .ok_or(VestingError::ArithmeticOverflow)?;
+                if time_elapsed < milestone {
+                    break;
+                }
+                vested_amount = vested_amount
+                    .checked_add(percent_of(total_allocation, percent)?)
+                    .ok_or(VestingError::ArithmeticOverflow)?;
+            }
+
+            // The last milestone releases whatever rounding held back
+            let last_milestone = month_in_seconds
+                .checked_mul(LINEAR_MILESTONES[LINEAR_MILESTONES.len() - 1].0)
+                .ok_or(VestingError::ArithmeticOverflow)?;
+            if time_elapsed >= last_milestone {
+                vested_amount = total_allocation;
+            }
+
+            Ok(vested_amount)
+        }
+
+        _ => Err(VestingError::InvalidVestingType),
+    }
+}
+
+/// Returns `(claimable, total_releasable)`: tokens released by `current_time` that have not
+/// been claimed yet, and all tokens released by `current_time`.
+pub fn calculate_claimable_tokens(
+    vesting_type: u8,
+    total_allocation: u64,
+    already_claimed: u64,
+    tge_time: u64,
+    seconds_per_day: u64,
+    current_time: u64,
+) -> Result<(u64, u64)> {
+    let total_releasable = total_releasable(
+        vesting_type,
+        total_allocation,
+        tge_time,
+        seconds_per_day,
+        current_time,
+    )?;
+
+    Ok((
+        total_releasable.saturating_sub(already_claimed),
+        total_releasable,
+    ))
+}
+
+/// One unlock on a vesting timeline
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimelinePoint {
+    pub timestamp: u64,
+    pub cumulative_vested: u64,
+    pub unlocked: u64, // Released since the previous point
+}
+
+/// Unlock schedule from TGE until fully vested. Immediate vesting releases daily, so its points are
+/// aggregated by month, plus the final day; linear vesting has a point at TGE and at each milestone.
+pub fn vesting_timeline(
+    vesting_type: u8,
+    total_allocation: u64,
+    tge_time: u64,
+    seconds_per_day: u64,
+) -> Result<Vec<TimelinePoint>> {
+    let month_in_seconds = seconds_per_day
+        .checked_mul(DAYS_PER_MONTH)
+        .ok_or(VestingError::ArithmeticOverflow)?;
+
+    // Offsets from TGE, in seconds
+    let mut offsets = vec![0];
+    match vesting_type {
+        VESTING_TYPE_IMMEDIATE => {
+            let end = seconds_per_day
+                .checked_mul(IMMEDIATE_VESTING_DAYS)
+                .ok_or(VestingError::ArithmeticOverflow)?;
+            let mut offset = month_in_seconds;
+            while offset > 0 && offset < end {
+                offsets.push(offset);
+                offset = offset
+                    .checked_add(month_in_seconds)
+                    .ok_or(VestingError::ArithmeticOverflow)?;
+            }
+            offsets.push(end);
+        }
+        VESTING_TYPE_LINEAR => {
+            for (months, _percent) in LINEAR_MILESTONES {
+                offsets.push(
+                    month_in_seconds
+                        .checked_mul(months)
+                        .ok_or(VestingError::ArithmeticOverflow)?,
+                );
+            }
+        }
+        _ => return Err(VestingError::InvalidVestingType),
+    }
+    offsets.dedup();
+
+    let mut points = Vec::with_capacity(offsets.len());
+    let mut previous_vested = 0;
+    for offset in offsets {
+        let timestamp = tge_time
+            .checked_add(offset)
+            .ok_or(VestingError::ArithmeticOverflow)?;
+        let cumulative_vested = total_releasable(
+            vesting_type,
+            total_allocation,
+            tge_time,
+            seconds_per_day,
+            timestamp,
+        )?;
+
+        points.push(TimelinePoint {
+            timestamp,
+            cumulative_vested,
+            unlocked: cumulative_vested.saturating_sub(previous_vested),
+        });
+        previous_vested = cumulative_vested;
+    }
+
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TGE: u64 = 1_700_000_000;
+    const DAY: u64 = 86_400;
+    const MONTH: u64 = DAY * DAYS_PER_MONTH;
+
+    // Leaves rounding dust in both schedules: 900_003 does not split evenly over 365 days and
+    // the linear percentages add up to 1_000_001
+    const ALLOCATION: u64 = 1_000_003;
+
+    fn released(vesting_type: u8, current_time: u64) -> u64 {
+        total_releasable(vesting_type, ALLOCATION, TGE, DAY, current_time).unwrap()
+    }
+
+    #[test]
+    fn immediate_releases_ten_percent_at_tge_then_daily() {
+        // 900_003 / 365 = 2_465 a day, with 278 held back until the last day
+        assert_eq!(released(VESTING_TYPE_IMMEDIATE, TGE), 100_000);
+        assert_eq!(released(VESTING_TYPE_IMMEDIATE, TGE + DAY - 1), 100_000);
+        assert_eq!(released(VESTING_TYPE_IMMEDIATE, TGE + DAY), 102_465);
+        assert_eq!(released(VESTING_TYPE_IMMEDIATE, TGE + 364 * DAY), 997_260);
+        assert_eq!(
+            released(VESTING_TYPE_IMMEDIATE, TGE + 365 * DAY),
+            ALLOCATION
+        );
+        assert_eq!(
+            released(VESTING_TYPE_IMMEDIATE, TGE + 1_000 * DAY),
+            ALLOCATION
+        );
+    }
+
+    #[test]
+    fn linear_releases_at_each_milestone() {
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE), 50_000);
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE + 8 * MONTH - 1), 50_000);
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE + 8 * MONTH), 150_000);
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE + 18 * MONTH), 350_000);
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE + 24 * MONTH), 550_000);
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE + 36 * MONTH - 1), 550_000);
+        // 5 + 10 + 20 + 20 + 45 percent would leave 2 tokens locked
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE + 36 * MONTH), ALLOCATION);
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE + 48 * MONTH), ALLOCATION);
+    }
+
+    #[test]
+    fn claimable_excludes_what_was_claimed() {
+        let now = TGE + 8 * MONTH;
+        assert_eq!(
+            calculate_claimable_tokens(VESTING_TYPE_LINEAR, ALLOCATION, 0, TGE, DAY, now),
+            Ok((150_000, 150_000))
+        );
+        assert_eq!(
+            calculate_claimable_tokens(VESTING_TYPE_LINEAR, ALLOCATION, 50_000, TGE, DAY, now),
+            Ok((100_000, 150_000))
+        );
+        // Never negative, even if more was claimed than the schedule releases
+        assert_eq!(
+            calculate_claimable_tokens(VESTING_TYPE_LINEAR, ALLOCATION, 200_000, TGE, DAY, now),
+            Ok((0, 150_000))
+        );
+    }
+
+    #[test]
+    fn before_tge_only_the_tge_release_is_counted() {
+        // The calculator does not gate on TGE; claims check it before calling
+        assert_eq!(released(VESTING_TYPE_IMMEDIATE, TGE - 10 * DAY), 100_000);
+        assert_eq!(released(VESTING_TYPE_LINEAR, TGE - 10 * DAY), 50_000);
+        assert_eq!(released(VESTING_TYPE_IMMEDIATE, 0), 100_000);
+    }
+
+    #[test]
+    fn zero_seconds_per_day_fails_once_days_are_counted() {
+        assert_eq!(
+            total_releasable(VESTING_TYPE_IMMEDIATE, ALLOCATION, TGE, 0, TGE),
+            Ok(100_000)
+        );
+        assert_eq!(
+            total_releasable(VESTING_TYPE_IMMEDIATE, ALLOCATION, TGE, 0, TGE + 1),
+            Err(VestingError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        assert_eq!(
+            total_releasable(VESTING_TYPE_IMMEDIATE, u64::MAX, TGE, DAY, TGE),
+            Err(VestingError::ArithmeticOverflow)
+        );
+        assert_eq!(
+            total_releasable(VESTING_TYPE_LINEAR, u64::MAX, TGE, DAY, TGE),
+            Err(VestingError::ArithmeticOverflow)
+        );
+        assert_eq!(
+            total_releasable(VESTING_TYPE_LINEAR, ALLOCATION, TGE, u64::MAX, TGE),
+            Err(VestingError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn unknown_vesting_type_is_rejected() {
+        assert_eq!(
+            calculate_claimable_tokens(2, ALLOCATION, 0, TGE, DAY, TGE),
+            Err(VestingError::InvalidVestingType)
+        );
+        assert_eq!(
+            vesting_timeline(u8::MAX, ALLOCATION, TGE, DAY),
+            Err(VestingError::InvalidVestingType)
+        );
+    }
+
+    #[test]
+    fn immediate_timeline_is_aggregated_by_month() {
+        let points = vesting_timeline(VESTING_TYPE_IMMEDIATE, ALLOCATION, TGE, DAY).unwrap();
+
+        // TGE, the end of each of the 12 whole months, and day 365
+        assert_eq!(points.len(), 14);
+        assert_eq!(
+            points[0],
+            TimelinePoint {
+                timestamp: TGE,
+                cumulative_vested: 100_000,
+                unlocked: 100_000,
+            }
+        );
+        for (month, point) in points[1..13].iter().enumerate() {
+            let month = month as u64 + 1;
+            assert_eq!(point.timestamp, TGE + month * MONTH);
+            assert_eq!(point.cumulative_vested, 100_000 + 2_465 * 30 * month);
+            assert_eq!(point.unlocked, 2_465 * 30);
+        }
+        // The last 5 days and the rounding dust
+        assert_eq!(
+            points[13],
+            TimelinePoint {
+                timestamp: TGE + 365 * DAY,
+                cumulative_vested: ALLOCATION,
+                unlocked: ALLOCATION - 987_400,
+            }
+        );
+        assert_eq!(points.iter().map(|p| p.unlocked).sum::<u64>(), ALLOCATION);
+    }
+
+    #[test]
+    fn linear_timeline_has_tge_and_milestone_points() {
+        let points = vesting_timeline(VESTING_TYPE_LINEAR, ALLOCATION, TGE, DAY).unwrap();
+
+        let timestamps: Vec<u64> = points.iter().map(|p| p.timestamp).collect();
+        assert_eq!(
+            timestamps,
+            vec![
+                TGE,
+                TGE + 8 * MONTH,
+                TGE + 18 * MONTH,
+                TGE + 24 * MONTH,
+                TGE + 36 * MONTH
+            ]
+        );
+        let unlocked: Vec<u64> = points.iter().map(|p| p.unlocked).collect();
+        assert_eq!(unlocked, vec![50_000, 100_000, 200_000, 200_000, 450_003]);
+        assert_eq!(unlocked.iter().sum::<u64>(), ALLOCATION);
+        assert_eq!(points[4].cumulative_vested, ALLOCATION);
+    }
+}
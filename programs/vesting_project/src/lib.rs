@@ -1,6 +1,20 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::compute_units::sol_remaining_compute_units;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::program_option::COption;
 use anchor_lang::system_program;
-use anchor_spl::token::{Mint, MintTo, Token, TokenAccount, Transfer};
+use anchor_spl::associated_token::{get_associated_token_address_with_program_id, AssociatedToken};
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::instruction::AuthorityType;
+use anchor_spl::token_interface::{
+    self, CloseAccount, InitializeAccount3, Mint, MintTo, SetAuthority, TokenAccount,
+    TokenInterface, TransferChecked,
+};
+use vesting_math::{VestingError, VESTING_TYPE_IMMEDIATE, VESTING_TYPE_LINEAR};
 // use anchor_lang::solana_program::program::invoke;
 // use anchor_lang::solana_program::system_instruction;
 use std::mem::size_of;
@@ -11,9 +25,52 @@ declare_id!("3XDaifPQuLu23EQ5SnQkkVssxDj4TfCApxtyxsKSA6GU");
 // Constant size
 pub const ANCHOR_DISCRIMINATOR_SIZE: usize = 8;
 
-// Vesting Types
-const VESTING_TYPE_IMMEDIATE: u8 = 0;
-const VESTING_TYPE_LINEAR: u8 = 1;
+// Reward Modes
+pub const REWARD_MODE_TRANSFER: u8 = 0; // Claims are paid from the pre-funded reward treasury
+pub const REWARD_MODE_MINT: u8 = 1; // Claims are minted by the vault authority
+
+// Sale Modes
+pub const SALE_MODE_FIXED_PRICE: u8 = 0; // Buy at the configured rates
+pub const SALE_MODE_DUTCH_AUCTION: u8 = 1; // Buy at a descending price, settle at a uniform clearing price
+pub const SALE_MODE_PRO_RATA: u8 = 2; // Commit SOL, then settle a pro-rata share of a capped supply
+
+// Block Reason Codes
+pub const BLOCK_REASON_UNSPECIFIED: u8 = 0;
+pub const BLOCK_REASON_COMPLIANCE: u8 = 1;
+pub const BLOCK_REASON_SUSPICIOUS_ACTIVITY: u8 = 2;
+pub const BLOCK_REASON_DISPUTE: u8 = 3;
+
+// Keeper fee for crank claims, in basis points of the claimed amount
+const MAX_KEEPER_FEE_BPS: u16 = 500;
+const BPS_DENOMINATOR: u64 = 10000;
+
+// Referral bonus and proceeds share, in basis points of a purchase
+const MAX_REFERRAL_BPS: u16 = 2000;
+
+// Purchase bonus tiers: at most this many of each kind, each worth at most this many basis points
+const MAX_BONUS_TIERS: usize = 8;
+const MAX_BONUS_BPS: u16 = 5000;
+const BONUS_TIERS_SPACE: usize = ANCHOR_DISCRIMINATOR_SIZE + 2 * (4 + MAX_BONUS_TIERS * 10) + 2;
+
+// Per-entry status returned by batch instructions
+pub const BATCH_STATUS_PROCESSED: u8 = 0;
+pub const BATCH_STATUS_SKIPPED: u8 = 1;
+pub const BATCH_STATUS_NOT_PROCESSED: u8 = 2;
+pub const BATCH_STATUS_INVALID: u8 = 3;
+
+// Compute units kept in reserve before starting the next batch entry
+const BATCH_MIN_COMPUTE_UNITS: u64 = 25000;
+
+// Token-2022 mint extensions the program works with; mints with any other extension are refused
+const SUPPORTED_MINT_EXTENSIONS: [ExtensionType; 7] = [
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+    ExtensionType::TokenGroupMember,
+];
 
 // Lamports per SOL and Token Decimals
 const LAMPORTS_PER_SOL: u64 = 1000000000;
@@ -32,25 +89,37 @@ pub mod vesting_program {
         min_buy_amount: u64,
         max_buy_amount: u64,
         tokens_per_sol: u64,  // tokens per SOL
-        tokens_per_usdc: u64, // tokens per USDC
-        tokens_per_usdt: u64, // tokens per USDT
         seconds_per_day: u64,
+        reward_mode: u8,
+        reward_supply_cap: u64, // reward token base units, 0 = uncapped (mint mode only)
     ) -> Result<()> {
+        require!(
+            reward_mode == REWARD_MODE_TRANSFER || reward_mode == REWARD_MODE_MINT,
+            CustomError::InvalidRewardMode
+        );
+
+        check_mint_extensions(&ctx.accounts.reward_token_mint)?;
+
+        // In mint mode the program must be able to mint the reward token
+        if reward_mode == REWARD_MODE_MINT {
+            require!(
+                ctx.accounts.reward_token_mint.mint_authority
+                    == COption::Some(ctx.accounts.vault_authority.key()),
+                CustomError::MintAuthorityNotVault
+            );
+
+            check_supply_cap(reward_supply_cap, ctx.accounts.reward_token_mint.supply, 0)?;
+        }
+
         let config_account = &mut ctx.accounts.ico_config;
 
         config_account.authority = ctx.accounts.authority.key();
 
         config_account.sol_treasury = ctx.accounts.sol_treasury.key();
-        config_account.usdc_treasury = ctx.accounts.usdc_treasury.key();
-        config_account.usdt_treasury = ctx.accounts.usdt_treasury.key();
         config_account.prize_treasury = ctx.accounts.reward_token_treasury.key();
 
-        config_account.usdc_mint = ctx.accounts.usdc_mint.key();
-        config_account.usdt_mint = ctx.accounts.usdt_mint.key();
         config_account.reward_token_mint = ctx.accounts.reward_token_mint.key();
 
-        config_account.usdc_decimals = ctx.accounts.usdc_mint.decimals;
-        config_account.usdt_decimals = ctx.accounts.usdt_mint.decimals;
         config_account.reward_token_decimals = ctx.accounts.reward_token_mint.decimals;
 
         config_account.ico_start_time = ico_start_time;
@@ -61,8 +130,6 @@ pub mod vesting_program {
         config_account.max_amount = max_buy_amount;
 
         config_account.tokens_per_sol = tokens_per_sol;
-        config_account.tokens_per_usdc = tokens_per_usdc;
-        config_account.tokens_per_usdt = tokens_per_usdt;
 
         config_account.seconds_per_day = seconds_per_day;
 
@@ -72,18 +139,34 @@ pub mod vesting_program {
         config_account.total_prize_deposited = 0;
 
         config_account.total_claimed = 0;
+        config_account.clawback_destination = Pubkey::default();
+        config_account.position_transfers_enabled = false;
+        config_account.transfer_requires_admin = false;
+        config_account.keeper_fee_bps = 0;
+        config_account.keeper_fee_cap = 0;
+        config_account.referral_bonus_bps = 0;
+        config_account.referral_proceeds_bps = 0;
+        config_account.referral_bonus_cap = 0;
+        config_account.sale_mode = SALE_MODE_FIXED_PRICE;
+        config_account.immediate_positions = 0;
+        config_account.linear_positions = 0;
+        config_account.wallet_count = 0;
+        config_account.sol_raised = 0;
+        config_account.claim_count = 0;
+        config_account.reward_mode = reward_mode;
+        config_account.reward_supply_cap = if reward_mode == REWARD_MODE_MINT {
+            reward_supply_cap
+        } else {
+            0
+        };
 
         msg!("=========ICO Config Initialized===========");
         msg!("Authority: {}", ctx.accounts.authority.key());
         msg!("Reward Token Mint: {}", config_account.reward_token_mint);
         msg!("SOL Treasury: {}", config_account.sol_treasury);
-        msg!("USDC Treasury: {}", config_account.usdc_treasury);
-        msg!("USDT Treasury: {}", config_account.usdt_treasury);
-        msg!("USDC Mint: {}", config_account.usdc_mint);
-        msg!("USDT Mint: {}", config_account.usdt_mint);
-        msg!("USDC Decimals: {}", ctx.accounts.usdc_mint.decimals);
-        msg!("USDT Decimals: {}", ctx.accounts.usdt_mint.decimals);
         msg!("Reward Token Decimals: {}", ctx.accounts.reward_token_mint.decimals);
+        msg!("Reward Mode: {}", config_account.reward_mode);
+        msg!("Reward Supply Cap: {}", config_account.reward_supply_cap);
         msg!("ICO Start Time: {}", config_account.ico_start_time);
         msg!("ICO End Time: {}", config_account.ico_end_time);
         msg!("TGE Time: {}", config_account.tge_time);
@@ -94,14 +177,6 @@ pub mod vesting_program {
             "Rate (SOL): {} tokens per SOL",
             config_account.tokens_per_sol
         );
-        msg!(
-            "Rate (USDC): {} tokens per USDC",
-            config_account.tokens_per_usdc
-        );
-        msg!(
-            "Rate (USDT): {} tokens per USDT",
-            config_account.tokens_per_usdt
-        );
 
         emit!(InitializeEvent {
             authority: ctx.accounts.authority.key(),
@@ -116,8 +191,10 @@ pub mod vesting_program {
     pub fn whitelist_investor_by_admin(
         ctx: Context<InvestorEntry>,
         investor_address: Pubkey,
+        position_index: u32,
         amount: u64,
         vesting_type: u8,
+        revocable: bool,
     ) -> Result<()> {
         // Get the authority directly from the ico_config account
         let config_authority = ctx.accounts.ico_config.authority;
@@ -153,8 +230,27 @@ pub mod vesting_program {
             CustomError::ICOPhaseInvalid
         );
 
+        register_position(
+            &mut ctx.accounts.wallet_positions,
+            investor_address,
+            position_index,
+        )?;
+
         let investor = &mut ctx.accounts.investor_details;
 
+        require!(!investor.revoked, CustomError::PositionRevoked);
+
+        // A top-up must keep the revocability the grant was created with
+        if investor.allocation > 0 {
+            require!(
+                investor.revocable == revocable,
+                CustomError::MismatchedRevocableFlag
+            );
+        }
+
+        let is_new_position = investor.allocation == 0;
+        let previous_vesting_type = investor.vesting_type;
+
         // Calculate tokens to allocate
         let tokens_to_allocate = amount;
 
@@ -171,6 +267,7 @@ pub mod vesting_program {
         );
 
         investor.address = investor_address;
+        investor.position_index = position_index;
         investor.allocation = total_allocation;
 
         config_account.total_allocated = config_account
@@ -181,6 +278,18 @@ pub mod vesting_program {
         msg!("tokens_to_allocate ====> {:?}", tokens_to_allocate);
 
         investor.vesting_type = vesting_type;
+        investor.revocable = revocable;
+
+        // A top-up may move the position to another vesting type
+        if !is_new_position {
+            forget_position(
+                config_account,
+                &mut ctx.accounts.wallet_positions,
+                previous_vesting_type,
+            );
+        }
+        record_new_position(config_account, &mut ctx.accounts.wallet_positions, vesting_type)?;
+
         investor.released_tokens = investor
             .released_tokens
             .checked_add(0)
@@ -194,7 +303,12 @@ pub mod vesting_program {
             .claimed_tokens
             .checked_add(0)
             .ok_or(CustomError::ArithmeticOverflow)?;
-        investor.blocked = false;
+        // A top-up leaves any block in place; only unblock_investor lifts one
+        if is_new_position {
+            investor.blocked = false;
+            investor.block_reason = BLOCK_REASON_UNSPECIFIED;
+            investor.blocked_until = 0;
+        }
         // investor.whitelisted_at = current_time;
 
         if investor.whitelisted_at == 0 {
@@ -210,9 +324,11 @@ pub mod vesting_program {
         msg!("Last Claimed: {}", investor.last_claimed);
         msg!("Claimed Tokens: {}", investor.claimed_tokens);
         msg!("Whitelisted At: {}", investor.whitelisted_at);
+        msg!("Revocable: {}", investor.revocable);
 
         emit!(InvestorWhitelisted {
             investor: investor.address,
+            position_index,
             allocation: investor.allocation,
             vesting_type: investor.vesting_type,
             cliff_end: investor.cliff_end,
@@ -221,14 +337,159 @@ pub mod vesting_program {
         Ok(())
     }
 
+    // Whitelist many investors at once.
+    // remaining_accounts: [investor PDA, wallet positions PDA] per entry, all writable.
+    // Entries whose position already has an allocation are skipped, so a batch can be resent safely.
+    pub fn whitelist_investors_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WhitelistInvestorsBatch<'info>>,
+        entries: Vec<WhitelistEntry>,
+    ) -> Result<BatchResult> {
+        let config = &mut ctx.accounts.ico_config;
+
+        require!(
+            ctx.accounts.authority.key() == config.authority,
+            CustomError::UnauthorizedAccess
+        );
+
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        // check the TGE start
+        require!(
+            current_time <= config.tge_time,
+            CustomError::TGEDateInvalid
+        );
+
+        require!(
+            (config.ico_start_time <= current_time && current_time <= config.ico_end_time),
+            CustomError::ICOPhaseInvalid
+        );
+
+        require!(
+            ctx.remaining_accounts.len() == entries.len() * 2,
+            CustomError::InvalidRemainingAccounts
+        );
+
+        let mut result = BatchResult::new(entries.len());
+
+        for (i, entry) in entries.iter().enumerate() {
+            if sol_remaining_compute_units() < BATCH_MIN_COMPUTE_UNITS {
+                break;
+            }
+
+            let investor_info = &ctx.remaining_accounts[2 * i];
+            let positions_info = &ctx.remaining_accounts[2 * i + 1];
+
+            let index_bytes = entry.position_index.to_le_bytes();
+            let (investor_pda, investor_bump) = Pubkey::find_program_address(
+                &[b"investor is my hero", entry.address.as_ref(), &index_bytes],
+                &crate::ID,
+            );
+            require_keys_eq!(
+                investor_info.key(),
+                investor_pda,
+                CustomError::InvalidPositionAccount
+            );
+
+            let (positions_pda, positions_bump) = Pubkey::find_program_address(
+                &[b"wallet positions", entry.address.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(
+                positions_info.key(),
+                positions_pda,
+                CustomError::InvalidPositionAccount
+            );
+
+            if !(entry.vesting_type == VESTING_TYPE_IMMEDIATE || entry.vesting_type == VESTING_TYPE_LINEAR)
+                || entry.amount < config.min_amount
+                || entry.amount > config.max_amount
+            {
+                result.set(i, BATCH_STATUS_INVALID);
+                continue;
+            }
+
+            init_program_account::<WalletPositions>(
+                &ctx.accounts.authority,
+                positions_info,
+                &ctx.accounts.system_program,
+                ANCHOR_DISCRIMINATOR_SIZE + size_of::<WalletPositions>(),
+                &[b"wallet positions", entry.address.as_ref(), &[positions_bump]],
+            )?;
+            let mut wallet_positions: Account<'info, WalletPositions> =
+                Account::try_from(positions_info)?;
+
+            if entry.position_index > wallet_positions.position_count {
+                result.set(i, BATCH_STATUS_INVALID);
+                continue;
+            }
+
+            init_program_account::<Investor>(
+                &ctx.accounts.authority,
+                investor_info,
+                &ctx.accounts.system_program,
+                ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+                &[b"investor is my hero", entry.address.as_ref(), &index_bytes, &[investor_bump]],
+            )?;
+            let mut investor: Account<'info, Investor> = Account::try_from(investor_info)?;
+
+            // Already whitelisted by an earlier run of this batch
+            if investor.allocation > 0 || investor.revoked {
+                result.set(i, BATCH_STATUS_SKIPPED);
+                continue;
+            }
+
+            register_position(&mut wallet_positions, entry.address, entry.position_index)?;
+
+            investor.address = entry.address;
+            investor.position_index = entry.position_index;
+            investor.allocation = entry.amount;
+            investor.vesting_type = entry.vesting_type;
+            investor.revocable = entry.revocable;
+            investor.blocked = false;
+            investor.whitelisted_at = current_time;
+            investor.exit(&crate::ID)?;
+
+            record_new_position(config, &mut wallet_positions, entry.vesting_type)?;
+            wallet_positions.exit(&crate::ID)?;
+
+            config.total_allocated = config
+                .total_allocated
+                .checked_add(entry.amount)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            emit!(InvestorWhitelisted {
+                investor: entry.address,
+                position_index: entry.position_index,
+                allocation: entry.amount,
+                vesting_type: entry.vesting_type,
+                cliff_end: investor.cliff_end,
+            });
+
+            result.set(i, BATCH_STATUS_PROCESSED);
+        }
+
+        msg!(
+            "Batch whitelist: {} processed, {} skipped, {} invalid, {} not processed",
+            result.processed,
+            result.skipped,
+            result.invalid,
+            result.not_processed
+        );
+
+        Ok(result)
+    }
+
     // ============== Buy Token functionality (investor Action) ==================
 
     // With Sol
     pub fn buy_tokens_with_sol(
         ctx: Context<BuyTokensWithSol>,
         investor_address: Pubkey,
+        position_index: u32,
         amount: u64,
         vesting_type: u8,
+        bonus_position_index: Option<u32>,
     ) -> Result<()> {
         let config = &mut ctx.accounts.ico_config;
 
@@ -237,6 +498,11 @@ pub mod vesting_program {
 
         require!(!config.paused, CustomError::ICOIsPaused);
 
+        require!(
+            config.sale_mode == SALE_MODE_FIXED_PRICE,
+            CustomError::WrongSaleMode
+        );
+
         // check the TGE start 
         require!(current_time <= config.tge_time, CustomError::TGEDateInvalid);
 
@@ -269,6 +535,16 @@ pub mod vesting_program {
             CustomError::InvalidVestingType
         );
 
+        let referrer_address = ctx.accounts.referrer.as_ref().map(|referrer| referrer.address);
+        check_referrer(referrer_address, ctx.accounts.buyer.key(), investor_address)?;
+
+        // The referrer's share of proceeds is paid out right away
+        let referral_cut = if referrer_address.is_some() {
+            bps_of(amount, config.referral_proceeds_bps)?
+        } else {
+            0
+        };
+
         // Transfer SOL from buyer to SOL treasury
         let cpi_accounts = system_program::Transfer {
             from: ctx.accounts.buyer.to_account_info(),
@@ -277,25 +553,55 @@ pub mod vesting_program {
         let cpi_context =
             CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
 
-        system_program::transfer(cpi_context, amount)?;
+        system_program::transfer(
+            cpi_context,
+            amount
+                .checked_sub(referral_cut)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+        )?;
+
+        if let (Some(referrer), true) = (referrer_address, referral_cut > 0) {
+            let referrer_wallet = ctx
+                .accounts
+                .referrer_wallet
+                .as_ref()
+                .ok_or(CustomError::ReferrerPayoutAccountRequired)?;
+            require_keys_eq!(
+                referrer_wallet.key(),
+                referrer,
+                CustomError::InvalidReferrerPayoutAccount
+            );
+
+            let cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.buyer.to_account_info(),
+                to: referrer_wallet.to_account_info(),
+            };
+            system_program::transfer(
+                CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts),
+                referral_cut,
+            )?;
+        }
 
         msg!("Transferred {} lamports from buyer to SOL treasury", amount);
 
+        register_position(
+            &mut ctx.accounts.wallet_positions,
+            investor_address,
+            position_index,
+        )?;
+
         // Get investor details
         let investor = &mut ctx.accounts.investor_details;
 
         // Validate vesting_type consistency
-        if investor.vesting_type != 0 {
-            require!(
-                investor.vesting_type == vesting_type,
-                CustomError::MismatchedVestingType
-            );
-        }
+        check_vesting_type(investor, vesting_type)?;
         // Apply whitelisting logic using the private helper function
         process_investor_whitelist(
             investor,
             ctx.accounts.buyer.key(),
-            config.tge_time,
+            position_index,
+            config,
+            &mut ctx.accounts.wallet_positions,
             vesting_type,
             tokens_to_allocate,
         )?;
@@ -313,6 +619,92 @@ pub mod vesting_program {
             .checked_add(tokens_to_allocate)
             .ok_or(CustomError::ArithmeticOverflow)?;
 
+        // Tiered purchase bonus, on top of the base allocation
+        let (bonus_tokens, bonus_vesting_type) = match ctx.accounts.bonus_tiers.as_ref() {
+            Some(bonus_tiers) => (
+                bonus_tiers.bonus_for(
+                    tokens_to_allocate,
+                    current_time.saturating_sub(config.ico_start_time),
+                )?,
+                bonus_tiers.bonus_vesting_type,
+            ),
+            None => (0, None),
+        };
+
+        if bonus_tokens > 0 {
+            match bonus_vesting_type {
+                // Bonus vests together with the purchased tokens
+                None => {
+                    investor.allocation = investor
+                        .allocation
+                        .checked_add(bonus_tokens)
+                        .ok_or(CustomError::CalculationOverflow)?;
+                }
+                // Bonus vests on its own schedule, in another position of the same wallet
+                Some(bonus_vesting_type) => {
+                    let bonus_position_index = bonus_position_index
+                        .filter(|index| *index != position_index)
+                        .ok_or(CustomError::BonusPositionRequired)?;
+                    let bonus_position = ctx
+                        .accounts
+                        .bonus_position
+                        .as_mut()
+                        .ok_or(CustomError::BonusPositionRequired)?;
+
+                    register_position(
+                        &mut ctx.accounts.wallet_positions,
+                        investor_address,
+                        bonus_position_index,
+                    )?;
+
+                    check_vesting_type(bonus_position, bonus_vesting_type)?;
+                    process_investor_whitelist(
+                        bonus_position,
+                        ctx.accounts.buyer.key(),
+                        bonus_position_index,
+                        config,
+                        &mut ctx.accounts.wallet_positions,
+                        bonus_vesting_type,
+                        bonus_tokens,
+                    )?;
+
+                    bonus_position.allocation = bonus_position
+                        .allocation
+                        .checked_add(bonus_tokens)
+                        .ok_or(CustomError::CalculationOverflow)?;
+                }
+            }
+
+            config.total_allocated = config
+                .total_allocated
+                .checked_add(bonus_tokens)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            msg!("Bonus tokens: {}", bonus_tokens);
+        }
+
+        if let Some(referrer) = ctx.accounts.referrer.as_mut() {
+            let bonus = credit_referral(config, referrer, tokens_to_allocate)?;
+
+            emit!(ReferralRecorded {
+                referrer: referrer.address,
+                buyer: ctx.accounts.buyer.key(),
+                payment_mint: Pubkey::default(),
+                token_amount: tokens_to_allocate,
+                bonus_tokens: bonus,
+                proceeds_cut: referral_cut,
+                timestamp: current_time,
+            });
+        }
+
+        record_purchase(
+            config,
+            tokens_to_allocate
+                .checked_add(bonus_tokens)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+            amount,
+        )?;
+
         // Fetch balances after transaction
         let buyer_balance_after = ctx.accounts.buyer.lamports();
 
@@ -329,43 +721,45 @@ pub mod vesting_program {
             buyer: ctx.accounts.buyer.key(),
             sol_amount: amount,
             token_amount: tokens_to_allocate,
+            bonus_amount: bonus_tokens,
             timestamp: current_time,
+            wrapped: false,
+            referrer: referrer_address,
         });
         Ok(())
     }
 
-    // Update the buy_token_with_usdc function to include vesting_type parameter
-    pub fn buy_token_with_usdc(
-        ctx: Context<BuyTokenWithUsdc>,
+    // Buy with any registered payment token. `amount` is in base units of the payment mint.
+    // wSOL is priced like native SOL and, if the payment mint is set to unwrap, lands in the SOL treasury.
+    pub fn buy_with_token(
+        ctx: Context<BuyWithToken>,
         investor_address: Pubkey,
+        position_index: u32,
         amount: u64,
         vesting_type: u8,
+        bonus_position_index: Option<u32>,
     ) -> Result<()> {
         let config = &mut ctx.accounts.ico_config;
+        let payment_mint = &ctx.accounts.payment_mint;
 
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp as u64;
 
         require!(!config.paused, CustomError::ICOIsPaused);
 
-        // check the TGE start 
-        require!(current_time <= config.tge_time, CustomError::TGEDateInvalid);
-
         require!(
-            current_time >= config.ico_start_time && current_time <= config.ico_end_time,
-            CustomError::ICOPhaseInvalid
+            config.sale_mode == SALE_MODE_FIXED_PRICE,
+            CustomError::WrongSaleMode
         );
 
-        // Calculate tokens to allocate
-        let tokens_to_allocate = amount
-            .checked_mul(config.tokens_per_usdc)
-            .ok_or(CustomError::CalculationOverflow)?
-            .checked_div(TOKEN_DECIMALS)
-            .ok_or(CustomError::CalculationOverflow)?;
+        require!(payment_mint.enabled, CustomError::PaymentMintDisabled);
+
+        // check the TGE start
+        require!(current_time <= config.tge_time, CustomError::TGEDateInvalid);
 
         require!(
-            tokens_to_allocate >= config.min_amount && tokens_to_allocate <= config.max_amount,
-            CustomError::InvalidBuyAmount
+            current_time >= config.ico_start_time && current_time <= config.ico_end_time,
+            CustomError::ICOPhaseInvalid
         );
 
         require!(
@@ -373,40 +767,230 @@ pub mod vesting_program {
             CustomError::InvalidVestingType
         );
 
+        let is_wrapped_sol = is_native_mint(&payment_mint.mint);
+        let unwrap = is_wrapped_sol && payment_mint.unwrap_to_sol_vault;
+
+        let referrer_address = ctx.accounts.referrer.as_ref().map(|referrer| referrer.address);
+        check_referrer(referrer_address, ctx.accounts.buyer.key(), investor_address)?;
+
+        // The referrer's share of proceeds is paid out right away, in the payment currency
+        let referral_cut = if referrer_address.is_some() {
+            bps_of(amount, config.referral_proceeds_bps)?
+        } else {
+            0
+        };
+        let treasury_share = amount
+            .checked_sub(referral_cut)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
         // Fetch balances before transaction
         let buyer_balance_before = ctx.accounts.buyer_token_account.amount;
+        let treasury_balance_before = ctx.accounts.treasury.amount;
+
+        msg!("Payment Mint: {}", payment_mint.mint);
+        msg!("Buyer's Pre Balance: {}", buyer_balance_before);
+
+        let mut received = if unwrap {
+            let wsol_escrow = ctx
+                .accounts
+                .wsol_escrow
+                .as_ref()
+                .ok_or(CustomError::UnwrapAccountsRequired)?;
+            let vault_authority = ctx
+                .accounts
+                .vault_authority
+                .as_ref()
+                .ok_or(CustomError::UnwrapAccountsRequired)?;
+            let sol_treasury = ctx
+                .accounts
+                .sol_treasury
+                .as_ref()
+                .ok_or(CustomError::UnwrapAccountsRequired)?;
+
+            let escrow_bump = ctx.bumps.wsol_escrow.ok_or(CustomError::UnwrapAccountsRequired)?;
+            let vault_bump = ctx
+                .bumps
+                .vault_authority
+                .ok_or(CustomError::UnwrapAccountsRequired)?;
+
+            let buyer_key = ctx.accounts.buyer.key();
+            let escrow_seeds = &[b"wsol_escrow".as_ref(), buyer_key.as_ref(), &[escrow_bump]];
+            let vault_seeds = &[b"vault_authority".as_ref(), &[vault_bump]];
+            let vault_signer = &[&vault_seeds[..]];
+
+            // Short-lived wSOL account owned by the vault authority; the buyer fronts its rent
+            let rent_paid = create_pda_account(
+                ctx.accounts.buyer.to_account_info(),
+                wsol_escrow.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                spl_token_2022::state::Account::LEN,
+                &ctx.accounts.token_program.key(),
+                &escrow_seeds[..],
+            )?;
+            require!(treasury_share >= rent_paid, CustomError::UnwrapAmountTooSmall);
+
+            let cpi_accounts = InitializeAccount3 {
+                account: wsol_escrow.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                authority: vault_authority.to_account_info(),
+            };
+            token_interface::initialize_account3(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+            ))?;
+
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.buyer_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: wsol_escrow.to_account_info(),
+                authority: ctx.accounts.buyer.to_account_info(),
+            };
+            token_interface::transfer_checked(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                treasury_share,
+                payment_mint.decimals,
+            )?;
+
+            // Refund the fronted rent in wSOL, so closing the escrow leaves the treasury share
+            let cpi_accounts = TransferChecked {
+                from: wsol_escrow.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.buyer_token_account.to_account_info(),
+                authority: vault_authority.to_account_info(),
+            };
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    cpi_accounts,
+                    vault_signer,
+                ),
+                rent_paid,
+                payment_mint.decimals,
+            )?;
+
+            // Closing unwraps straight into the SOL treasury
+            let cpi_accounts = CloseAccount {
+                account: wsol_escrow.to_account_info(),
+                destination: sol_treasury.to_account_info(),
+                authority: vault_authority.to_account_info(),
+            };
+            token_interface::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                vault_signer,
+            ))?;
+
+            msg!("Unwrapped {} lamports of wSOL into the SOL treasury", treasury_share);
+
+            treasury_share
+        } else {
+            // Transfer payment tokens from buyer token account to the mint's treasury
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.buyer_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.treasury.to_account_info(),
+                authority: ctx.accounts.buyer.to_account_info(),
+            };
+
+            let cpi_context =
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+
+            token_interface::transfer_checked(cpi_context, treasury_share, payment_mint.decimals)?;
 
-        msg!("Buyer's USDC Pre Balance: {}", buyer_balance_before);
+            // A transfer fee mint delivers less than was sent; only what arrived buys tokens
+            ctx.accounts.treasury.reload()?;
+            let received = ctx
+                .accounts
+                .treasury
+                .amount
+                .checked_sub(treasury_balance_before)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            msg!("Transferred {} from buyer to treasury, {} received", treasury_share, received);
 
-        // Transfer USDC from buyer token account to USDC treasury
-        let cpi_accounts = anchor_spl::token::Transfer {
-            from: ctx.accounts.buyer_token_account.to_account_info(),
-            to: ctx.accounts.usdc_treasury.to_account_info(),
-            authority: ctx.accounts.buyer.to_account_info(),
+            received
         };
 
-        let cpi_context =
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        // The referrer's share is paid in the payment mint, wSOL included
+        if let (Some(referrer), true) = (referrer_address, referral_cut > 0) {
+            let referrer_token_account = ctx
+                .accounts
+                .referrer_token_account
+                .as_mut()
+                .ok_or(CustomError::ReferrerPayoutAccountRequired)?;
+            require!(
+                referrer_token_account.mint == payment_mint.mint
+                    && referrer_token_account.owner == referrer,
+                CustomError::InvalidReferrerPayoutAccount
+            );
+
+            let referrer_balance_before = referrer_token_account.amount;
+
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.buyer_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: referrer_token_account.to_account_info(),
+                authority: ctx.accounts.buyer.to_account_info(),
+            };
+            token_interface::transfer_checked(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                referral_cut,
+                payment_mint.decimals,
+            )?;
+
+            // The referrer's share still counts as paid by the buyer
+            referrer_token_account.reload()?;
+            received = received
+                .checked_add(
+                    referrer_token_account
+                        .amount
+                        .checked_sub(referrer_balance_before)
+                        .ok_or(CustomError::ArithmeticOverflow)?,
+                )
+                .ok_or(CustomError::ArithmeticOverflow)?;
+        }
+
+        // wSOL uses the native SOL rate
+        let tokens_per_token = if is_wrapped_sol {
+            config.tokens_per_sol
+        } else {
+            payment_mint.tokens_per_token
+        };
+
+        // Calculate tokens to allocate; the price is per whole payment token
+        let tokens_to_allocate = received
+            .checked_mul(tokens_per_token)
+            .ok_or(CustomError::CalculationOverflow)?
+            .checked_div(
+                10u64
+                    .checked_pow(payment_mint.decimals as u32)
+                    .ok_or(CustomError::CalculationOverflow)?,
+            )
+            .ok_or(CustomError::CalculationOverflow)?;
 
-        anchor_spl::token::transfer(cpi_context, amount)?;
+        require!(
+            tokens_to_allocate >= config.min_amount && tokens_to_allocate <= config.max_amount,
+            CustomError::InvalidBuyAmount
+        );
 
-        msg!("Transferred {} USDC from buyer to USDC treasury", amount);
+        register_position(
+            &mut ctx.accounts.wallet_positions,
+            investor_address,
+            position_index,
+        )?;
 
         // Get investor details
         let investor = &mut ctx.accounts.investor_details;
 
         // Validate vesting_type consistency
-        if investor.vesting_type != 0 {
-            require!(
-                investor.vesting_type == vesting_type,
-                CustomError::MismatchedVestingType
-            );
-        }
+        check_vesting_type(investor, vesting_type)?;
         // Apply whitelisting logic using the private helper function
         process_investor_whitelist(
             investor,
             ctx.accounts.buyer.key(),
-            config.tge_time,
+            position_index,
+            config,
+            &mut ctx.accounts.wallet_positions,
             vesting_type,
             tokens_to_allocate,
         )?;
@@ -424,41 +1008,214 @@ pub mod vesting_program {
             .checked_add(tokens_to_allocate)
             .ok_or(CustomError::ArithmeticOverflow)?;
 
+        // Tiered purchase bonus, on top of the base allocation
+        let (bonus_tokens, bonus_vesting_type) = match ctx.accounts.bonus_tiers.as_ref() {
+            Some(bonus_tiers) => (
+                bonus_tiers.bonus_for(
+                    tokens_to_allocate,
+                    current_time.saturating_sub(config.ico_start_time),
+                )?,
+                bonus_tiers.bonus_vesting_type,
+            ),
+            None => (0, None),
+        };
+
+        if bonus_tokens > 0 {
+            match bonus_vesting_type {
+                // Bonus vests together with the purchased tokens
+                None => {
+                    investor.allocation = investor
+                        .allocation
+                        .checked_add(bonus_tokens)
+                        .ok_or(CustomError::CalculationOverflow)?;
+                }
+                // Bonus vests on its own schedule, in another position of the same wallet
+                Some(bonus_vesting_type) => {
+                    let bonus_position_index = bonus_position_index
+                        .filter(|index| *index != position_index)
+                        .ok_or(CustomError::BonusPositionRequired)?;
+                    let bonus_position = ctx
+                        .accounts
+                        .bonus_position
+                        .as_mut()
+                        .ok_or(CustomError::BonusPositionRequired)?;
+
+                    register_position(
+                        &mut ctx.accounts.wallet_positions,
+                        investor_address,
+                        bonus_position_index,
+                    )?;
+
+                    check_vesting_type(bonus_position, bonus_vesting_type)?;
+                    process_investor_whitelist(
+                        bonus_position,
+                        ctx.accounts.buyer.key(),
+                        bonus_position_index,
+                        config,
+                        &mut ctx.accounts.wallet_positions,
+                        bonus_vesting_type,
+                        bonus_tokens,
+                    )?;
+
+                    bonus_position.allocation = bonus_position
+                        .allocation
+                        .checked_add(bonus_tokens)
+                        .ok_or(CustomError::CalculationOverflow)?;
+                }
+            }
+
+            config.total_allocated = config
+                .total_allocated
+                .checked_add(bonus_tokens)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            msg!("Bonus tokens: {}", bonus_tokens);
+        }
+
+        if let Some(referrer) = ctx.accounts.referrer.as_mut() {
+            let bonus = credit_referral(config, referrer, tokens_to_allocate)?;
+
+            emit!(ReferralRecorded {
+                referrer: referrer.address,
+                buyer: ctx.accounts.buyer.key(),
+                payment_mint: ctx.accounts.mint.key(),
+                token_amount: tokens_to_allocate,
+                bonus_tokens: bonus,
+                proceeds_cut: referral_cut,
+                timestamp: current_time,
+            });
+        }
+
+        // wSOL counts towards SOL raised, other payment tokens are tracked per mint
+        record_purchase(
+            config,
+            tokens_to_allocate
+                .checked_add(bonus_tokens)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+            if is_wrapped_sol { received } else { 0 },
+        )?;
+        if !is_wrapped_sol {
+            ctx.accounts.payment_mint.total_raised = ctx
+                .accounts
+                .payment_mint
+                .total_raised
+                .checked_add(received)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+        }
+
         // We need to reload the account to get the updated balance
         let buyer_token_account = &mut ctx.accounts.buyer_token_account;
         buyer_token_account.reload()?;
 
-        let remaining_usdc_bal = buyer_token_account.amount;
-
-        msg!("Buyer USDC Post Balance: {}", remaining_usdc_bal);
+        msg!("Buyer's Post Balance: {}", buyer_token_account.amount);
         msg!("Total allocation now: {}", investor.allocation);
 
-        emit!(TokenPurchaseEventForUsdc {
-            buyer: ctx.accounts.buyer.key(),
-            usdc_amount: amount,
-            token_amount: tokens_to_allocate,
+        // wSOL purchases are reported like native SOL ones so SOL proceeds add up in one place
+        if is_wrapped_sol {
+            emit!(TokenPurchaseEventForSol {
+                buyer: ctx.accounts.buyer.key(),
+                sol_amount: received,
+                token_amount: tokens_to_allocate,
+                bonus_amount: bonus_tokens,
+                timestamp: current_time,
+                wrapped: true,
+                referrer: referrer_address,
+            });
+        } else {
+            emit!(TokenPurchaseEventForToken {
+                buyer: ctx.accounts.buyer.key(),
+                mint: ctx.accounts.mint.key(),
+                token_amount_paid: amount,
+                token_amount_received: received,
+                token_amount: tokens_to_allocate,
+                bonus_amount: bonus_tokens,
+                timestamp: current_time,
+                treasury: ctx.accounts.treasury.key(),
+                referrer: referrer_address,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Switch the sale to a Dutch auction paid in SOL. The price, in lamports per whole reward token,
+    // falls linearly from start_price at ico_start_time to floor_price at ico_end_time.
+    pub fn configure_auction(
+        ctx: Context<ConfigureAuction>,
+        start_price: u64,
+        floor_price: u64,
+        supply: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+
+        // Check that the caller is the authority
+        require!(
+            ctx.accounts.authority.key() == config.authority,
+            CustomError::UnauthorizedAccess
+        );
+
+        let current_time = Clock::get()?.unix_timestamp as u64;
+
+        // The sale mode cannot change once the sale is running
+        require!(current_time < config.ico_start_time, CustomError::ICOPhaseInvalid);
+
+        require!(
+            floor_price > 0 && start_price >= floor_price && supply > 0,
+            CustomError::InvalidAuctionConfig
+        );
+
+        let auction = &mut ctx.accounts.auction;
+        auction.start_price = start_price;
+        auction.floor_price = floor_price;
+        auction.supply = supply;
+        auction.tokens_sold = 0;
+        auction.total_paid = 0;
+        auction.clearing_price = 0;
+        auction.settled = false;
+
+        config.sale_mode = SALE_MODE_DUTCH_AUCTION;
+
+        msg!(
+            "Dutch auction configured: {} tokens, price {} -> {} lamports per token",
+            supply,
+            start_price,
+            floor_price
+        );
+
+        emit!(AuctionConfigured {
+            authority: ctx.accounts.authority.key(),
+            start_price,
+            floor_price,
+            supply,
             timestamp: current_time,
-            usdc_treasury: ctx.accounts.usdc_treasury.key(),
         });
 
         Ok(())
     }
 
-    // Update the buy_token_with_usdt function to include vesting_type parameter
-    pub fn buy_token_with_usdt(
-        ctx: Context<BuyTokenWithUsdt>,
+    // Buy `tokens` reward tokens at the current auction price. The last buyer is filled with
+    // whatever supply remains. Payments are held by the auction account until settlement.
+    pub fn bid_auction(
+        ctx: Context<BidAuction>,
         investor_address: Pubkey,
-        amount: u64,
+        position_index: u32,
+        tokens: u64,
         vesting_type: u8,
     ) -> Result<()> {
         let config = &mut ctx.accounts.ico_config;
+        let auction = &mut ctx.accounts.auction;
 
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp as u64;
 
         require!(!config.paused, CustomError::ICOIsPaused);
 
-        // check the TGE start  
+        require!(
+            config.sale_mode == SALE_MODE_DUTCH_AUCTION,
+            CustomError::WrongSaleMode
+        );
+
+        // check the TGE start
         require!(current_time <= config.tge_time, CustomError::TGEDateInvalid);
 
         require!(
@@ -467,94 +1224,556 @@ pub mod vesting_program {
         );
 
         require!(
-            (vesting_type == VESTING_TYPE_IMMEDIATE || vesting_type == VESTING_TYPE_LINEAR),
-            CustomError::InvalidVestingType
+            tokens >= config.min_amount && tokens <= config.max_amount,
+            CustomError::InvalidBuyAmount
         );
 
-        // Calculate tokens to allocate
-        let tokens_to_allocate = amount
-            .checked_mul(config.tokens_per_usdt)
-            .ok_or(CustomError::CalculationOverflow)?
-            .checked_div(TOKEN_DECIMALS)
-            .ok_or(CustomError::CalculationOverflow)?;
-
         require!(
-            tokens_to_allocate >= config.min_amount && tokens_to_allocate <= config.max_amount,
-            CustomError::InvalidBuyAmount
+            (vesting_type == VESTING_TYPE_IMMEDIATE || vesting_type == VESTING_TYPE_LINEAR),
+            CustomError::InvalidVestingType
         );
 
-        let buyer_balance_before = ctx.accounts.buyer_token_account.amount;
+        require!(auction.tokens_sold < auction.supply, CustomError::AuctionSoldOut);
 
-        msg!("Buyer's USDT Pre Balance: {}", buyer_balance_before);
+        let price = auction_price(
+            auction,
+            config.ico_start_time,
+            config.ico_end_time,
+            current_time,
+        );
+        let (tokens_to_allocate, cost) = auction_fill(auction, tokens, price)?;
 
-        // Transfer USDT from buyer token account to USDT treasury
-        let cpi_accounts = anchor_spl::token::Transfer {
-            from: ctx.accounts.buyer_token_account.to_account_info(),
-            to: ctx.accounts.usdt_treasury.to_account_info(),
-            authority: ctx.accounts.buyer.to_account_info(),
+        // Transfer SOL from buyer to the auction account
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.buyer.to_account_info(),
+            to: auction.to_account_info(),
         };
+        system_program::transfer(
+            CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts),
+            cost,
+        )?;
 
-        let cpi_context =
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        msg!(
+            "Bought {} tokens at {} lamports per token, paid {}",
+            tokens_to_allocate,
+            price,
+            cost
+        );
+
+        record_auction_bid(auction, tokens_to_allocate, cost, price)?;
+        if auction.tokens_sold == auction.supply {
+            msg!("Auction sold out at {} lamports per token", price);
+        }
 
-        anchor_spl::token::transfer(cpi_context, amount)?;
+        let bid = &mut ctx.accounts.auction_bid;
+        bid.wallet = investor_address;
+        bid.position_index = position_index;
+        bid.tokens = bid
+            .tokens
+            .checked_add(tokens_to_allocate)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        bid.paid = bid
+            .paid
+            .checked_add(cost)
+            .ok_or(CustomError::ArithmeticOverflow)?;
 
-        msg!("Transferred {} USDT from buyer to USDT treasury", amount);
+        register_position(
+            &mut ctx.accounts.wallet_positions,
+            investor_address,
+            position_index,
+        )?;
 
         // Get investor details
         let investor = &mut ctx.accounts.investor_details;
 
         // Validate vesting_type consistency
-        if investor.vesting_type != 0 {
-            require!(
-                investor.vesting_type == vesting_type,
-                CustomError::MismatchedVestingType
-            );
-        }
-        // Apply whitelisting logic using the private helper function
+        check_vesting_type(investor, vesting_type)?;
         process_investor_whitelist(
             investor,
             ctx.accounts.buyer.key(),
-            config.tge_time,
+            position_index,
+            config,
+            &mut ctx.accounts.wallet_positions,
             vesting_type,
             tokens_to_allocate,
         )?;
 
-        // Update allocation regardless of whether this is first purchase
-        let total_allocation = investor
+        investor.allocation = investor
             .allocation
             .checked_add(tokens_to_allocate)
             .ok_or(CustomError::CalculationOverflow)?;
 
-        investor.allocation = total_allocation;
-
-        config.total_user_allocated = config
-            .total_user_allocated
+        config.total_allocated = config
+            .total_allocated
             .checked_add(tokens_to_allocate)
             .ok_or(CustomError::ArithmeticOverflow)?;
 
-        msg!("Total tokens sold so far: {}", config.total_user_allocated);
+        // Proceeds are only known at settlement
+        record_purchase(config, tokens_to_allocate, 0)?;
 
-        // We need to reload the account to get the updated balance
-        let buyer_token_account = &mut ctx.accounts.buyer_token_account;
-        buyer_token_account.reload()?;
+        msg!("Total allocation now: {}", investor.allocation);
+
+        emit!(AuctionBidPlaced {
+            buyer: ctx.accounts.buyer.key(),
+            investor: investor_address,
+            position_index,
+            token_amount: tokens_to_allocate,
+            price,
+            sol_amount: cost,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Fix the clearing price and send the proceeds to the SOL treasury. Anyone can settle once the
+    // auction has sold out or ended; an auction that did not sell out clears at the floor price.
+    pub fn settle_auction(ctx: Context<SettleAuction>) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+        let auction = &mut ctx.accounts.auction;
 
-        let remaining_usdt_bal = buyer_token_account.amount;
+        let current_time = Clock::get()?.unix_timestamp as u64;
 
-        msg!("Buyer's USDT Post Balance: {}", remaining_usdt_bal);
-        msg!("Total allocation now: {}", investor.allocation);
+        require!(!auction.settled, CustomError::AuctionAlreadySettled);
+        require!(
+            auction.tokens_sold == auction.supply || current_time > config.ico_end_time,
+            CustomError::AuctionNotEnded
+        );
+
+        let proceeds = settle_auction_proceeds(auction)?;
+
+        auction.sub_lamports(proceeds)?;
+        ctx.accounts.sol_treasury.add_lamports(proceeds)?;
+
+        record_purchase(config, 0, proceeds)?;
+
+        msg!(
+            "Auction settled at {} lamports per token, {} lamports to the SOL treasury",
+            auction.clearing_price,
+            proceeds
+        );
+
+        emit!(AuctionSettled {
+            clearing_price: auction.clearing_price,
+            tokens_sold: auction.tokens_sold,
+            proceeds,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Refund what a position paid above the clearing price
+    pub fn claim_auction_rebate(ctx: Context<ClaimAuctionRebate>, position_index: u32) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        let bid = &mut ctx.accounts.auction_bid;
+
+        require!(auction.settled, CustomError::AuctionNotSettled);
+        require!(!bid.rebate_claimed, CustomError::NoRebateAvailable);
+
+        let rebate = auction_rebate(auction, bid)?;
+
+        require!(rebate > 0, CustomError::NoRebateAvailable);
+
+        bid.rebate_claimed = true;
+        auction.total_paid = auction
+            .total_paid
+            .checked_sub(rebate)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        auction.sub_lamports(rebate)?;
+        ctx.accounts.wallet.add_lamports(rebate)?;
+
+        msg!("Rebate of {} lamports paid to {}", rebate, bid.wallet);
+
+        emit!(AuctionRebateClaimed {
+            investor: bid.wallet,
+            position_index,
+            amount: rebate,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    // Switch the sale to commit-then-settle mode. Commitments are priced at tokens_per_sol and at most
+    // token_cap tokens are allocated; an oversubscribed sale is scaled down pro rata.
+    pub fn configure_pro_rata_sale(ctx: Context<ConfigureProRataSale>, token_cap: u64) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+
+        // Check that the caller is the authority
+        require!(
+            ctx.accounts.authority.key() == config.authority,
+            CustomError::UnauthorizedAccess
+        );
+
+        let current_time = Clock::get()?.unix_timestamp as u64;
+
+        // The sale mode cannot change once the sale is running
+        require!(current_time < config.ico_start_time, CustomError::ICOPhaseInvalid);
+
+        require!(token_cap > 0, CustomError::InvalidSaleConfig);
+
+        let sale = &mut ctx.accounts.pro_rata_sale;
+        sale.token_cap = token_cap;
+        sale.total_committed = 0;
+        sale.total_demand = 0;
+        sale.tokens_allocated = 0;
+        sale.finalized = false;
 
-        emit!(TokenPurchaseEventForUsdt {
+        config.sale_mode = SALE_MODE_PRO_RATA;
+
+        msg!("Pro-rata sale configured: cap of {} tokens", token_cap);
+
+        emit!(ProRataSaleConfigured {
+            authority: ctx.accounts.authority.key(),
+            token_cap,
+            tokens_per_sol: config.tokens_per_sol,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Commit `amount` lamports towards a position. Nothing is allocated until settlement.
+    pub fn commit_to_sale(
+        ctx: Context<CommitToSale>,
+        investor_address: Pubkey,
+        position_index: u32,
+        amount: u64,
+        vesting_type: u8,
+    ) -> Result<()> {
+        let config = &ctx.accounts.ico_config;
+
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        require!(!config.paused, CustomError::ICOIsPaused);
+
+        require!(
+            config.sale_mode == SALE_MODE_PRO_RATA,
+            CustomError::WrongSaleMode
+        );
+
+        require!(
+            current_time >= config.ico_start_time && current_time <= config.ico_end_time,
+            CustomError::ICOPhaseInvalid
+        );
+
+        require!(
+            (vesting_type == VESTING_TYPE_IMMEDIATE || vesting_type == VESTING_TYPE_LINEAR),
+            CustomError::InvalidVestingType
+        );
+
+        // Settled commitments are closed, so none may be reopened once the sale is finalized
+        require!(
+            !ctx.accounts.pro_rata_sale.finalized,
+            CustomError::SaleAlreadyFinalized
+        );
+
+        let commitment = &mut ctx.accounts.commitment;
+
+        if commitment.amount > 0 {
+            require!(
+                commitment.vesting_type == vesting_type,
+                CustomError::MismatchedVestingType
+            );
+        }
+
+        let total_commitment = commitment
+            .amount
+            .checked_add(amount)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        // Limits apply to the whole commitment of the position
+        let requested_tokens = committed_tokens(config.tokens_per_sol, total_commitment)?;
+        require!(
+            requested_tokens >= config.min_amount && requested_tokens <= config.max_amount,
+            CustomError::InvalidBuyAmount
+        );
+
+        // Transfer SOL from buyer to the sale account
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.buyer.to_account_info(),
+            to: ctx.accounts.pro_rata_sale.to_account_info(),
+        };
+        system_program::transfer(
+            CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        commitment.wallet = investor_address;
+        commitment.position_index = position_index;
+        commitment.vesting_type = vesting_type;
+        commitment.amount = total_commitment;
+
+        let sale = &mut ctx.accounts.pro_rata_sale;
+        sale.total_committed = sale
+            .total_committed
+            .checked_add(amount)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        msg!("Committed {} lamports, position total {}", amount, total_commitment);
+
+        emit!(SaleCommitted {
             buyer: ctx.accounts.buyer.key(),
-            usdt_amount: amount,
+            investor: investor_address,
+            position_index,
+            sol_amount: amount,
+            total_commitment,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Fix the total demand once the sale window has closed. Anyone can finalize.
+    pub fn finalize_sale(ctx: Context<FinalizeSale>) -> Result<()> {
+        let config = &ctx.accounts.ico_config;
+        let sale = &mut ctx.accounts.pro_rata_sale;
+
+        let current_time = Clock::get()?.unix_timestamp as u64;
+
+        require!(!sale.finalized, CustomError::SaleAlreadyFinalized);
+        require!(current_time > config.ico_end_time, CustomError::SaleNotEnded);
+
+        sale.total_demand = committed_tokens(config.tokens_per_sol, sale.total_committed)?;
+        sale.finalized = true;
+
+        msg!(
+            "Sale finalized: {} tokens demanded for a cap of {}",
+            sale.total_demand,
+            sale.token_cap
+        );
+
+        emit!(SaleFinalized {
+            total_committed: sale.total_committed,
+            total_demand: sale.total_demand,
+            token_cap: sale.token_cap,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Turn a commitment into its pro-rata allocation and refund the excess. Shares are rounded
+    // down, so the allocations never add up to more than the cap.
+    pub fn settle(ctx: Context<Settle>, position_index: u32) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+        let sale = &mut ctx.accounts.pro_rata_sale;
+        let commitment = &mut ctx.accounts.commitment;
+
+        let current_time = Clock::get()?.unix_timestamp as u64;
+
+        require!(sale.finalized, CustomError::SaleNotFinalized);
+        require!(!commitment.settled, CustomError::CommitmentAlreadySettled);
+
+        let (tokens_to_allocate, spent, refund) =
+            pro_rata_allocation(sale, config.tokens_per_sol, commitment.amount)?;
+
+        sale.tokens_allocated = sale
+            .tokens_allocated
+            .checked_add(tokens_to_allocate)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        require!(
+            sale.tokens_allocated <= sale.token_cap,
+            CustomError::SaleCapExceeded
+        );
+
+        commitment.settled = true;
+
+        sale.sub_lamports(
+            spent
+                .checked_add(refund)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+        )?;
+        ctx.accounts.sol_treasury.add_lamports(spent)?;
+        ctx.accounts.wallet.add_lamports(refund)?;
+
+        // A commitment scaled down to nothing opens no position, so its PDAs are only created here
+        if tokens_to_allocate > 0 {
+            let wallet_key = ctx.accounts.wallet.key();
+            let index_bytes = position_index.to_le_bytes();
+
+            let positions_info = ctx.accounts.wallet_positions.to_account_info();
+            init_program_account::<WalletPositions>(
+                &ctx.accounts.wallet,
+                &positions_info,
+                &ctx.accounts.system_program,
+                ANCHOR_DISCRIMINATOR_SIZE + size_of::<WalletPositions>(),
+                &[
+                    b"wallet positions",
+                    wallet_key.as_ref(),
+                    &[ctx.bumps.wallet_positions],
+                ],
+            )?;
+            let mut wallet_positions =
+                WalletPositions::try_deserialize(&mut &positions_info.try_borrow_data()?[..])?;
+            register_position(&mut wallet_positions, commitment.wallet, position_index)?;
+
+            let investor_info = ctx.accounts.investor_details.to_account_info();
+            init_program_account::<Investor>(
+                &ctx.accounts.wallet,
+                &investor_info,
+                &ctx.accounts.system_program,
+                ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+                &[
+                    b"investor is my hero",
+                    wallet_key.as_ref(),
+                    &index_bytes,
+                    &[ctx.bumps.investor_details],
+                ],
+            )?;
+            let mut investor_data =
+                Investor::try_deserialize(&mut &investor_info.try_borrow_data()?[..])?;
+            let investor = &mut investor_data;
+
+            check_vesting_type(investor, commitment.vesting_type)?;
+            process_investor_whitelist(
+                investor,
+                commitment.wallet,
+                position_index,
+                config,
+                &mut wallet_positions,
+                commitment.vesting_type,
+                tokens_to_allocate,
+            )?;
+
+            investor.allocation = investor
+                .allocation
+                .checked_add(tokens_to_allocate)
+                .ok_or(CustomError::CalculationOverflow)?;
+
+            config.total_allocated = config
+                .total_allocated
+                .checked_add(tokens_to_allocate)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            investor_data.try_serialize(&mut &mut investor_info.try_borrow_mut_data()?[..])?;
+            wallet_positions.try_serialize(&mut &mut positions_info.try_borrow_mut_data()?[..])?;
+        }
+
+        record_purchase(config, tokens_to_allocate, spent)?;
+
+        msg!(
+            "Settled {} tokens for {} lamports, refunded {}",
+            tokens_to_allocate,
+            spent,
+            refund
+        );
+
+        emit!(CommitmentSettled {
+            investor: commitment.wallet,
+            position_index,
             token_amount: tokens_to_allocate,
+            sol_amount: spent,
+            refund,
             timestamp: current_time,
-            usdt_treasury: ctx.accounts.usdt_treasury.key(),
         });
 
         Ok(())
     }
 
+    // Register an SPL (or Token-2022) mint as a payment token.
+    // tokens_per_token is the price: reward tokens allocated per whole payment token.
+    // wSOL always uses tokens_per_sol; unwrap_to_sol_vault sends its proceeds to the SOL treasury.
+    pub fn add_payment_mint(
+        ctx: Context<AddPaymentMint>,
+        tokens_per_token: u64,
+        unwrap_to_sol_vault: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.ico_config.authority,
+            CustomError::UnauthorizedAccess
+        );
+
+        let is_wrapped_sol = is_native_mint(&ctx.accounts.mint.key());
+
+        require!(
+            is_wrapped_sol || tokens_per_token > 0,
+            CustomError::InvalidTokenRate
+        );
+        require!(
+            is_wrapped_sol || !unwrap_to_sol_vault,
+            CustomError::UnwrapRequiresNativeMint
+        );
+
+        check_mint_extensions(&ctx.accounts.mint)?;
+
+        let payment_mint = &mut ctx.accounts.payment_mint;
+        payment_mint.mint = ctx.accounts.mint.key();
+        payment_mint.decimals = ctx.accounts.mint.decimals;
+        payment_mint.treasury = ctx.accounts.treasury.key();
+        payment_mint.tokens_per_token = if is_wrapped_sol { 0 } else { tokens_per_token };
+        payment_mint.enabled = true;
+        payment_mint.unwrap_to_sol_vault = unwrap_to_sol_vault;
+        payment_mint.total_raised = 0;
+
+        msg!("Payment mint {} added", payment_mint.mint);
+        msg!("Treasury: {}", payment_mint.treasury);
+        msg!("Rate: {} tokens per token", payment_mint.tokens_per_token);
+        msg!("Unwrap to SOL vault: {}", unwrap_to_sol_vault);
+
+        emit!(PaymentMintUpdated {
+            authority: ctx.accounts.authority.key(),
+            mint: payment_mint.mint,
+            treasury: payment_mint.treasury,
+            tokens_per_token: payment_mint.tokens_per_token,
+            enabled: true,
+            unwrap_to_sol_vault,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    // Change the price, treasury or enabled flag of a registered payment token
+    pub fn update_payment_mint(
+        ctx: Context<UpdatePaymentMint>,
+        tokens_per_token: u64,
+        enabled: bool,
+        unwrap_to_sol_vault: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.ico_config.authority,
+            CustomError::UnauthorizedAccess
+        );
+
+        let payment_mint = &mut ctx.accounts.payment_mint;
+        let is_wrapped_sol = is_native_mint(&payment_mint.mint);
+
+        require!(
+            is_wrapped_sol || tokens_per_token > 0,
+            CustomError::InvalidTokenRate
+        );
+        require!(
+            is_wrapped_sol || !unwrap_to_sol_vault,
+            CustomError::UnwrapRequiresNativeMint
+        );
+
+        payment_mint.treasury = ctx.accounts.treasury.key();
+        payment_mint.tokens_per_token = if is_wrapped_sol { 0 } else { tokens_per_token };
+        payment_mint.enabled = enabled;
+        payment_mint.unwrap_to_sol_vault = unwrap_to_sol_vault;
+
+        msg!("Payment mint {} updated", payment_mint.mint);
+        msg!("Treasury: {}", payment_mint.treasury);
+        msg!("Rate: {} tokens per token", payment_mint.tokens_per_token);
+        msg!("Enabled: {}", enabled);
+        msg!("Unwrap to SOL vault: {}", unwrap_to_sol_vault);
+
+        emit!(PaymentMintUpdated {
+            authority: ctx.accounts.authority.key(),
+            mint: payment_mint.mint,
+            treasury: payment_mint.treasury,
+            tokens_per_token: payment_mint.tokens_per_token,
+            enabled,
+            unwrap_to_sol_vault,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
 
     pub fn set_ico_dates(
         ctx: Context<SetIcoConfig>,
@@ -583,7 +1802,11 @@ pub mod vesting_program {
         Ok(())
     }
 
-    pub fn remove_investor(ctx: Context<RemoveInvestor>, investor_address: Pubkey) -> Result<()> {
+    pub fn remove_investor(
+        ctx: Context<RemoveInvestor>,
+        investor_address: Pubkey,
+        position_index: u32,
+    ) -> Result<()> {
         let config = &mut ctx.accounts.ico_config;
 
         // Ensure the signer is the authority from the config
@@ -593,7 +1816,8 @@ pub mod vesting_program {
         );
 
         msg!(
-            "Investor {} removed successfully by admin {}",
+            "Position {} of investor {} removed successfully by admin {}",
+            position_index,
             investor_address,
             ctx.accounts.authority.key()
         );
@@ -614,6 +1838,13 @@ pub mod vesting_program {
             .checked_sub(remaining_allocation)
             .ok_or(CustomError::ArithmeticOverflow)?;
 
+        if investor.allocation > 0 {
+            forget_position(
+                config,
+                &mut ctx.accounts.wallet_positions,
+                investor.vesting_type,
+            );
+        }
 
         **investor = Investor {
             address: Pubkey::default(),
@@ -624,12 +1855,21 @@ pub mod vesting_program {
             vesting_type: 0,
             cliff_end: 0,
             last_claimed: 0,
-            whitelisted_at: 0
+            whitelisted_at: 0,
+            block_reason: BLOCK_REASON_UNSPECIFIED,
+            blocked_until: 0,
+            revocable: false,
+            revoked: false,
+            revoked_at: 0,
+            position_mint: Pubkey::default(),
+            position_index: 0,
+            claim_destination: Pubkey::default(),
         };
 
         // Emit an event for the removal
         emit!(InvestorRemoved {
             investor: investor_address,
+            position_index,
             removed_by: ctx.accounts.authority.key(),
             timestamp: Clock::get()?.unix_timestamp as u64,
         });
@@ -654,7 +1894,7 @@ pub mod vesting_program {
         Ok(())
     }
 
-    // Reset mint address token_type =0 (prize), token_type = 1 (usdc), token_type = 2 (usdt)
+    // Reset mint address token_type =0 (prize); payment tokens are managed with update_payment_mint
     pub fn set_mint_address(
         ctx: Context<SetMintAddress>,
         token_type: u8,
@@ -667,11 +1907,20 @@ pub mod vesting_program {
             CustomError::UnauthorizedAccess
         );
 
-        // Check oken_type =0 (prize), token_type = 1 (usdc), token_type = 2 (usdt)
-        require!(
-            token_type >= 0 && token_type <= 2,
-            CustomError::InvalidTokenType
-        );
+        // Check token_type =0 (prize)
+        require!(token_type == 0, CustomError::InvalidTokenType);
+
+        // The new mint must pass the same checks as at initialize
+        check_mint_extensions(mint)?;
+
+        if ctx.accounts.ico_config.reward_mode == REWARD_MODE_MINT {
+            require!(
+                mint.mint_authority == COption::Some(ctx.accounts.vault_authority.key()),
+                CustomError::MintAuthorityNotVault
+            );
+
+            check_supply_cap(ctx.accounts.ico_config.reward_supply_cap, mint.supply, 0)?;
+        }
 
         if token_type == 0 {
             // Update the $Prize Token
@@ -684,28 +1933,6 @@ pub mod vesting_program {
                 token: token,
                 timestamp: Clock::get()?.unix_timestamp as u64,
             });
-        } else if token_type == 1 {
-            // Update the usdc_mint
-            ctx.accounts.ico_config.usdc_mint = token;
-            ctx.accounts.ico_config.usdc_decimals = mint.decimals;
-            msg!("Usdc Mint Address updated to {}", token);
-
-            emit!(UsdcAddressUpdated {
-                authority: ctx.accounts.authority.key(),
-                token: token,
-                timestamp: Clock::get()?.unix_timestamp as u64,
-            });
-        } else if token_type == 2 {
-            // Update the usdt_mint
-            ctx.accounts.ico_config.usdt_mint = token;
-            ctx.accounts.ico_config.usdt_decimals = mint.decimals;
-            msg!("Usdt Mint Address updated to {}", token);
-
-            emit!(UsdtAddressUpdated {
-                authority: ctx.accounts.authority.key(),
-                token: token,
-                timestamp: Clock::get()?.unix_timestamp as u64,
-            });
         }
 
         Ok(())
@@ -835,8 +2062,9 @@ pub mod vesting_program {
         let seeds = &[b"vault_authority".as_ref(), &[ctx.bumps.vault_authority]];
         let signer = &[&seeds[..]];
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.reward_token_treasury.to_account_info(),
+            mint: ctx.accounts.reward_token_mint.to_account_info(),
             to: ctx.accounts.recipient_token_account.to_account_info(),
             authority: ctx.accounts.vault_authority.to_account_info(),
         };
@@ -847,7 +2075,7 @@ pub mod vesting_program {
             signer
         );
 
-        anchor_spl::token::transfer(cpi_ctx, amount)?;
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.reward_token_mint.decimals)?;
 
         msg!("Withdrawn {} reward tokens from treasury", amount);
 
@@ -861,74 +2089,103 @@ pub mod vesting_program {
     }
 
 
-    pub fn withdraw_usdc_tokens(ctx: Context<UsdcTokensTransfer>, amount: u64) -> Result<()> {
-        let config = &mut ctx.accounts.ico_config;
-
+    pub fn withdraw_payment_tokens(
+        ctx: Context<PaymentTokensTransfer>,
+        amount: u64,
+    ) -> Result<()> {
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp as u64;
 
         require!(
-            ctx.accounts.usdc_treasury.amount >= amount,
+            ctx.accounts.treasury.amount >= amount,
             CustomError::InsufficientFunds
         );
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.usdc_treasury.to_account_info(),
-            to: ctx.accounts.recipient_usdc_account.to_account_info(),
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.treasury.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.recipient_token_account.to_account_info(),
             authority: ctx.accounts.authority.to_account_info(),
         };
 
         let cpi_context =
             CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
 
-        anchor_spl::token::transfer(cpi_context, amount)?;
+        token_interface::transfer_checked(cpi_context, amount, ctx.accounts.mint.decimals)?;
 
-        msg!("Withdrawn {} reward tokens from usdc treasury", amount);
+        msg!(
+            "Withdrawn {} of {} from treasury {}",
+            amount,
+            ctx.accounts.mint.key(),
+            ctx.accounts.treasury.key()
+        );
 
-        emit!(UsdcWithdrawTokens {
-            authority: ctx.accounts.usdc_treasury.key(),
-            amount: amount,
+        emit!(PaymentTokensWithdrawn {
+            authority: ctx.accounts.authority.key(),
+            mint: ctx.accounts.mint.key(),
+            treasury: ctx.accounts.treasury.key(),
+            amount,
             timestamp: current_time,
         });
 
         Ok(())
     }
 
-    pub fn withdraw_usdt_tokens(ctx: Context<UsdtTokensTransfer>, amount: u64) -> Result<()> {
+    // Block an investor from claiming. Vesting keeps accruing while blocked.
+    // expires_at = None blocks until unblock_investor is called.
+    pub fn block_investor(
+        ctx: Context<BlockInvestor>,
+        investor_address: Pubkey,
+        position_index: u32,
+        reason: u8,
+        expires_at: Option<u64>,
+    ) -> Result<()> {
         let config = &mut ctx.accounts.ico_config;
+        let investor = &mut ctx.accounts.investor_details;
 
-        let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp as u64;
+        require!(
+            ctx.accounts.authority.key() == config.authority,
+            CustomError::UnauthorizedAccess
+        );
 
         require!(
-            ctx.accounts.usdt_treasury.amount >= amount,
-            CustomError::InsufficientFunds
+            reason <= BLOCK_REASON_DISPUTE,
+            CustomError::InvalidBlockReason
         );
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.usdt_treasury.to_account_info(),
-            to: ctx.accounts.recipient_usdt_account.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
-        };
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
 
-        let cpi_context =
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        let blocked_until = expires_at.unwrap_or(0);
+        if expires_at.is_some() {
+            require!(blocked_until > current_time, CustomError::InvalidBlockExpiry);
+        }
 
-        anchor_spl::token::transfer(cpi_context, amount)?;
+        investor.blocked = true;
+        investor.block_reason = reason;
+        investor.blocked_until = blocked_until;
 
-        msg!("Withdrawn {} reward tokens from usdt treasury", amount);
+        msg!("Position {} of investor {} has been blocked", position_index, investor.address);
+        msg!("Block Reason: {}", reason);
+        msg!("Blocked Until: {}", blocked_until);
 
-        emit!(UsdtWithdrawTokens {
-            authority: ctx.accounts.usdt_treasury.key(),
-            amount: amount,
+        emit!(InvestorBlocked {
+            investor: investor.address,
+            position_index,
+            blocked_by: ctx.accounts.authority.key(),
+            reason,
+            expires_at: blocked_until,
             timestamp: current_time,
         });
-
         Ok(())
     }
 
-    pub fn block_investor(ctx: Context<BlockInvestor>, investor_address: Pubkey) -> Result<()> {
-        let config = &mut ctx.accounts.ico_config;
+    pub fn unblock_investor(
+        ctx: Context<UnblockInvestor>,
+        investor_address: Pubkey,
+        position_index: u32,
+    ) -> Result<()> {
+        let config = &ctx.accounts.ico_config;
         let investor = &mut ctx.accounts.investor_details;
 
         require!(
@@ -936,16 +2193,28 @@ pub mod vesting_program {
             CustomError::UnauthorizedAccess
         );
 
-        investor.blocked = true;
+        require!(investor.blocked, CustomError::InvestorNotBlocked);
 
-        msg!("Investor {} has been blocked", investor.address);
+        let reason = investor.block_reason;
+
+        investor.blocked = false;
+        investor.block_reason = BLOCK_REASON_UNSPECIFIED;
+        investor.blocked_until = 0;
+
+        msg!(
+            "Position {} of investor {} has been unblocked",
+            position_index,
+            investor_address
+        );
 
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp as u64;
 
-        emit!(InvestorBlocked {
+        emit!(InvestorUnblocked {
             investor: investor.address,
-            blocked_by: ctx.accounts.authority.key(),
+            position_index,
+            unblocked_by: ctx.accounts.authority.key(),
+            reason,
             timestamp: current_time,
         });
         Ok(())
@@ -959,9 +2228,12 @@ pub mod vesting_program {
             CustomError::UnauthorizedAccess
         );
 
+        let treasury_balance_before = ctx.accounts.reward_token_treasury.amount;
+
         // Transfer tokens from authority token account to reward token treasury
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.authority_token_account.to_account_info(),
+            mint: ctx.accounts.reward_token_mint.to_account_info(),
             to: ctx.accounts.reward_token_treasury.to_account_info(),
             authority: ctx.accounts.authority.to_account_info(),
         };
@@ -969,15 +2241,28 @@ pub mod vesting_program {
         let cpi_context =
             CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
 
-        anchor_spl::token::transfer(cpi_context, amount)?;
+        token_interface::transfer_checked(
+            cpi_context,
+            amount,
+            ctx.accounts.reward_token_mint.decimals,
+        )?;
+
+        // Count what the treasury actually received (transfer fee mints deliver less)
+        ctx.accounts.reward_token_treasury.reload()?;
+        let received = ctx
+            .accounts
+            .reward_token_treasury
+            .amount
+            .checked_sub(treasury_balance_before)
+            .ok_or(CustomError::ArithmeticOverflow)?;
 
         // Update the config's total_allocated
         config.total_prize_deposited = config
             .total_prize_deposited
-            .checked_add(amount)
+            .checked_add(received)
             .ok_or(CustomError::ArithmeticOverflow)?;
 
-        msg!("Deposited {} to treasury", amount);
+        msg!("Deposited {} to treasury, {} received", amount, received);
         msg!("Total prize tokens now: {}", config.total_prize_deposited);
 
         let clock = Clock::get()?;
@@ -985,908 +2270,3013 @@ pub mod vesting_program {
 
         emit!(PrizeDeposited {
             depositor: ctx.accounts.authority.key(),
-            amount: amount,
+            amount: received,
             timestamp: current_time,
         });
 
         Ok(())
     }
 
-    // Transfer Ownership =======================
-    pub fn transfer_ownership(
-        ctx: Context<TransferOwnership>,
-        new_authority: Pubkey,
+    pub fn set_clawback_destination(
+        ctx: Context<SetIcoConfig>,
+        destination: Pubkey,
     ) -> Result<()> {
-        let config = &mut ctx.accounts.ico_config;
+        // Check that the caller is the authority
         require!(
-            ctx.accounts.authority.key() == config.authority,
+            ctx.accounts.authority.key() == ctx.accounts.ico_config.authority,
             CustomError::UnauthorizedAccess
         );
+
         require!(
-            new_authority != Pubkey::default(),
+            destination != Pubkey::default(),
             CustomError::InvalidAddress
         );
-        config.authority = new_authority;
 
-        msg!("Ownership transferred to {}", new_authority);
+        ctx.accounts.ico_config.clawback_destination = destination;
 
-        let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp as u64;
+        msg!("Clawback destination updated to {}", destination);
 
-        emit!(OwnershipTransferred {
-            previous_owner: ctx.accounts.authority.key(),
-            new_owner: new_authority,
-            timestamp: current_time,
+        emit!(ClawbackDestinationUpdated {
+            authority: ctx.accounts.authority.key(),
+            destination,
+            timestamp: Clock::get()?.unix_timestamp as u64,
         });
 
         Ok(())
     }
 
-    // Renounce Ownership =========================
-
-    pub fn renounce_ownership(ctx: Context<RenounceOwnership>) -> Result<()> {
+    // Revoke a revocable grant. Tokens vested so far stay with the beneficiary
+    // (claimable later, or paid out now), the unvested rest goes to the clawback destination.
+    pub fn revoke_vesting(
+        ctx: Context<RevokeVesting>,
+        investor_address: Pubkey,
+        position_index: u32,
+        pay_out_vested: bool,
+    ) -> Result<()> {
         let config = &mut ctx.accounts.ico_config;
+        let investor = &mut ctx.accounts.investor_details;
+
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
         require!(
             ctx.accounts.authority.key() == config.authority,
             CustomError::UnauthorizedAccess
         );
 
-        config.authority = Pubkey::default();
-
-        msg!("Ownership renounced");
-
-        let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp as u64;
+        require!(investor.revocable, CustomError::PositionNotRevocable);
+        require!(!investor.revoked, CustomError::PositionRevoked);
 
-        emit!(OwnershipRenounced {
-            previous_owner: ctx.accounts.authority.key(),
-            timestamp: current_time,
-        });
+        require!(
+            config.clawback_destination != Pubkey::default(),
+            CustomError::ClawbackDestinationNotSet
+        );
 
-        Ok(())
-    }
+        // Nothing has vested before TGE
+        let vested = if current_time < config.tge_time {
+            investor.claimed_tokens
+        } else {
+            let (_claimable, total_releasable) = calculate_claimable_tokens(
+                investor.vesting_type,
+                investor.allocation,
+                investor.claimed_tokens,
+                config.tge_time,
+                config.seconds_per_day,
+                current_time,
+            )?;
+            std::cmp::max(total_releasable, investor.claimed_tokens)
+        };
 
-    pub fn claim_tokens(ctx: Context<ClaimTokens>, investor_address: Pubkey) -> Result<()> {
-        let config = &mut ctx.accounts.ico_config;
+        let unvested = investor
+            .allocation
+            .checked_sub(vested)
+            .ok_or(CustomError::ArithmeticOverflow)?;
 
-        let investor = &mut ctx.accounts.investor_details;
-        let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp as u64;
+        let vested_unclaimed = vested
+            .checked_sub(investor.claimed_tokens)
+            .ok_or(CustomError::ArithmeticOverflow)?;
 
-        // require_keys_eq!(ctx.accounts.investor.key(), investor.address, CustomError::UnauthorizedAccess);
+        let paid_out = if pay_out_vested { vested_unclaimed } else { 0 };
 
-        require!(
-            investor.address == ctx.accounts.investor.key(),
-            CustomError::UnauthorizedAccess
-        );
+        if config.reward_mode == REWARD_MODE_TRANSFER {
+            let required_funds = unvested
+                .checked_add(paid_out)
+                .ok_or(CustomError::ArithmeticOverflow)?
+                .checked_mul(TOKEN_DECIMALS)
+                .ok_or(CustomError::ArithmeticOverflow)?;
 
-        // Check if investor is blocked
-        require!(!investor.blocked, CustomError::InvestorIsBlocked);
+            require!(
+                ctx.accounts.reward_token_treasury.amount >= required_funds,
+                CustomError::InsufficientFunds
+            );
+        }
 
-        // Check if TGE has happened
-        require!(current_time >= config.tge_time, CustomError::TGEDateInvalid);
+        let seeds = &[b"vault_authority".as_ref(), &[ctx.bumps.vault_authority]];
+        let signer = &[&seeds[..]];
 
-        // Calculate claimable tokens based on vesting type
-        let (claimable_tokens, _released_tokens) = calculate_claimable_tokens(
-            investor.vesting_type,
-            investor.allocation,
-            investor.claimed_tokens,
-            config.tge_time,
-            config.seconds_per_day,
-            current_time,
-        )?;
+        // Return the unvested part to the clawback destination.
+        // In mint mode it was never minted, so there is nothing to return.
+        if unvested > 0 && config.reward_mode == REWARD_MODE_TRANSFER {
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.reward_token_treasury.to_account_info(),
+                mint: ctx.accounts.reward_token_mint.to_account_info(),
+                to: ctx.accounts.clawback_token_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
 
-        msg!("claimable_tokens =====> {}", claimable_tokens);
+            let cpi_context = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer,
+            );
 
-        // Check if there are tokens to claim
-        require!(claimable_tokens > 0, CustomError::NoTokensAvailableToClaim);
+            let clawback_amount = unvested
+                .checked_mul(TOKEN_DECIMALS)
+                .ok_or(CustomError::ArithmeticOverflow)?;
 
-        // Check if treasury has enough tokens
-        require!(
-            ctx.accounts.reward_token_treasury.amount >= claimable_tokens,
-            CustomError::InsufficientFunds
-        );
+            token_interface::transfer_checked(
+                cpi_context,
+                clawback_amount,
+                ctx.accounts.reward_token_mint.decimals,
+            )?;
+        }
 
-        let vault_auth = &ctx.bumps.vault_authority;
-        let seeds = &[b"vault_authority".as_ref(), &[ctx.bumps.vault_authority]];
-        let signer = &[&seeds[..]];
+        // Optionally settle everything the beneficiary is owed right away
+        if paid_out > 0 {
+            let investor_token_account = ctx
+                .accounts
+                .investor_token_account
+                .as_ref()
+                .ok_or(CustomError::InvestorTokenAccountRequired)?;
+
+            // Paid where a claim would go: the position token holder of a tokenized position,
+            // otherwise the beneficiary or the destination it designated
+            let holder = if investor.position_mint != Pubkey::default() {
+                let position_token_account = ctx
+                    .accounts
+                    .position_token_account
+                    .as_ref()
+                    .ok_or(CustomError::PositionTokenAccountRequired)?;
+
+                require!(
+                    position_token_account.mint == investor.position_mint
+                        && position_token_account.amount == 1,
+                    CustomError::NotPositionHolder
+                );
+                position_token_account.owner
+            } else {
+                investor.address
+            };
+            require_keys_eq!(
+                investor_token_account.owner,
+                claim_destination_of(investor, holder),
+                CustomError::InvalidClaimDestination
+            );
 
-        // Transfer tokens to investor
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.reward_token_treasury.to_account_info(),
-            to: ctx.accounts.investor_token_account.to_account_info(),
-            authority: ctx.accounts.vault_authority.to_account_info(),
-        };
+            let payout_amount = paid_out
+                .checked_mul(TOKEN_DECIMALS)
+                .ok_or(CustomError::ArithmeticOverflow)?;
 
-        let cpi_context = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-            signer,
-        );
+            let mut payer = RewardPayer {
+                reward_token_mint: &mut ctx.accounts.reward_token_mint,
+                reward_token_treasury: &mut ctx.accounts.reward_token_treasury,
+                vault_authority: &ctx.accounts.vault_authority,
+                token_program: &ctx.accounts.token_program,
+                vault_bump: ctx.bumps.vault_authority,
+            };
+            payer.pay(config, investor_token_account.to_account_info(), payout_amount)?;
 
-        let tokens_amount = claimable_tokens
-            .checked_mul(TOKEN_DECIMALS)
-            .ok_or(CustomError::ArithmeticOverflow)?;
+            investor.claimed_tokens = investor
+                .claimed_tokens
+                .checked_add(paid_out)
+                .ok_or(CustomError::ArithmeticOverflow)?;
 
-        anchor_spl::token::transfer(cpi_context, tokens_amount)?;
+            investor.released_tokens = investor
+                .released_tokens
+                .checked_add(paid_out)
+                .ok_or(CustomError::ArithmeticOverflow)?;
 
-        // Update investor claimed tokens
-        investor.claimed_tokens = investor
-            .claimed_tokens
-            .checked_add(claimable_tokens)
-            .ok_or(CustomError::ArithmeticOverflow)?;
+            investor.last_claimed = current_time;
 
-        // Update released tokens
-        investor.released_tokens = investor
-            .released_tokens
-            .checked_add(claimable_tokens)
-            .ok_or(CustomError::ArithmeticOverflow)?;
+            config.total_claimed = config
+                .total_claimed
+                .checked_add(paid_out)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+            config.claim_count = config
+                .claim_count
+                .checked_add(1)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+        }
 
-        // Update last claimed timestamp
-        investor.last_claimed = current_time;
-        investor.cliff_end = get_next_cliff_end(
-            investor.vesting_type,
-            investor.cliff_end,
-            config.seconds_per_day,
-            config.tge_time,
-            current_time,
-        )?;
+        // Freeze the grant at what has vested; the remainder is no longer allocated
+        investor.allocation = vested;
+        investor.revoked = true;
+        investor.revoked_at = current_time;
 
-        // Update total claimed in config
-        config.total_claimed = config
-            .total_claimed
-            .checked_add(claimable_tokens)
+        config.total_allocated = config
+            .total_allocated
+            .checked_sub(unvested)
             .ok_or(CustomError::ArithmeticOverflow)?;
 
-        msg!("Tokens claimed successfully!");
-        msg!("Investor: {}", investor.address);
-        msg!("Claimed amount: {}", claimable_tokens);
-        msg!("Total claimed so far: {}", investor.claimed_tokens);
         msg!(
-            "Remaining to claim: {}",
-            investor.allocation.saturating_sub(investor.claimed_tokens)
+            "Vesting revoked for position {} of investor {}",
+            position_index,
+            investor_address
         );
+        msg!("Vested: {}", vested);
+        msg!("Clawed back: {}", unvested);
+        msg!("Paid out: {}", paid_out);
 
-        emit!(TokensClaimed {
-            investor: investor.address,
-            amount: claimable_tokens,
+        emit!(VestingRevoked {
+            investor: investor_address,
+            position_index,
+            revoked_by: ctx.accounts.authority.key(),
+            vested,
+            clawed_back: unvested,
+            paid_out,
+            clawback_destination: config.clawback_destination,
             timestamp: current_time,
-            remaining: investor.allocation.saturating_sub(investor.claimed_tokens),
         });
 
         Ok(())
     }
 
-    // Helper function to calculate claimable tokens based on vesting type
-    pub fn determine_claimable_tokens(
-        ctx: Context<Calculate>,
-        vesting_type: u8,
-        total_allocation: u64,
-        already_claimed: u64,
-        tge_time: u64,
-        seconds_per_day: u64,
-        current_time: u64,
-    ) -> Result<(u64, u64)> {
-        // Calculate the total releasable tokens based on vesting type and current time
-        let total_releasable = match vesting_type {
-            // Immediate vesting - 10% at TGE, rest linearly over 365 days
-            VESTING_TYPE_IMMEDIATE => {
-                // 10% at TGE initially
-
-                let mut initial_release = total_allocation
-                    .checked_mul(10)
-                    .ok_or(CustomError::ArithmeticOverflow)?
-                    .checked_div(100)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
-
-                // Remaining 90% for daily vesting
-                let mut remaining_allocation = total_allocation
-                    .checked_sub(initial_release)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
+    // Fee paid to keepers calling crank_claim, in bps of the claim and capped in reward token base units (0 = no cap)
+    pub fn set_keeper_fee(ctx: Context<SetIcoConfig>, fee_bps: u16, fee_cap: u64) -> Result<()> {
+        // Check that the caller is the authority
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.ico_config.authority,
+            CustomError::UnauthorizedAccess
+        );
 
-                // Daily vesting amount
-                let daily_release = remaining_allocation
-                    .checked_div(365)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
+        require!(
+            fee_bps <= MAX_KEEPER_FEE_BPS,
+            CustomError::KeeperFeeTooHigh
+        );
 
-                // Days passed since TGE
-                let mut days_passed = if current_time <= tge_time {
-                    0
-                } else {
-                    current_time
-                        .checked_sub(tge_time)
-                        .ok_or(CustomError::ArithmeticOverflow)?
-                        .checked_div(seconds_per_day)
-                        .ok_or(CustomError::ArithmeticOverflow)?
-                };
+        ctx.accounts.ico_config.keeper_fee_bps = fee_bps;
+        ctx.accounts.ico_config.keeper_fee_cap = fee_cap;
 
-                msg!("days_passed =============> {:?}", days_passed);
+        msg!("Keeper fee updated to {} bps (cap {})", fee_bps, fee_cap);
 
-                // Cap at 365 days
-                let effective_days = std::cmp::min(days_passed, 365);
-                msg!("effective_days =============> {:?}", effective_days);
+        emit!(KeeperFeeUpdated {
+            authority: ctx.accounts.authority.key(),
+            fee_bps,
+            fee_cap,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
 
-                // Calculate vested tokens
-                let vested_from_daily = daily_release
-                    .checked_mul(effective_days)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
+        Ok(())
+    }
 
-                msg!("vested_from_daily =============> {:?}", vested_from_daily);
+    // Replace the purchase bonus tiers. Volume tiers are keyed by the base tokens bought, time tiers by
+    // seconds since ico_start_time; both lists must be sorted by ascending threshold. With a bonus vesting
+    // type, bonus tokens go to a separate position on that schedule instead of the purchased position.
+    pub fn set_bonus_tiers(
+        ctx: Context<SetBonusTiers>,
+        volume_tiers: Vec<BonusTier>,
+        time_tiers: Vec<BonusTier>,
+        bonus_vesting_type: Option<u8>,
+    ) -> Result<()> {
+        // Check that the caller is the authority
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.ico_config.authority,
+            CustomError::UnauthorizedAccess
+        );
 
-                // Total releasable: initial + vested from daily
-                initial_release
-                    .checked_add(vested_from_daily)
-                    .ok_or(CustomError::ArithmeticOverflow)?
-            }
+        check_bonus_tiers(&volume_tiers)?;
+        check_bonus_tiers(&time_tiers)?;
 
-            // Linear vesting with milestones
-            VESTING_TYPE_LINEAR => {
-                // Initial 5% at TGE
-                let mut initial_release = total_allocation
-                    .checked_mul(5)
-                    .ok_or(CustomError::ArithmeticOverflow)?
-                    .checked_div(100)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
+        if let Some(vesting_type) = bonus_vesting_type {
+            require!(
+                vesting_type == VESTING_TYPE_IMMEDIATE || vesting_type == VESTING_TYPE_LINEAR,
+                CustomError::InvalidVestingType
+            );
+        }
 
-                // Calculate seconds in months for different milestones
-                let mut month_in_seconds = seconds_per_day
-                    .checked_mul(30)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
+        let bonus_tiers = &mut ctx.accounts.bonus_tiers;
+        bonus_tiers.volume_tiers = volume_tiers.clone();
+        bonus_tiers.time_tiers = time_tiers.clone();
+        bonus_tiers.bonus_vesting_type = bonus_vesting_type;
 
-                // Define milestones in seconds from TGE
-                let mut milestone_8_months = month_in_seconds
-                    .checked_mul(8)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
-                let mut milestone_18_months = month_in_seconds
-                    .checked_mul(18)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
-                let mut milestone_24_months = month_in_seconds
-                    .checked_mul(24)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
-                let mut milestone_36_months = month_in_seconds
-                    .checked_mul(36)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
+        msg!(
+            "Bonus tiers updated: {} volume tiers, {} time tiers",
+            volume_tiers.len(),
+            time_tiers.len()
+        );
 
-                // Calculate time elapsed since TGE
-                let mut time_elapsed = if current_time <= tge_time {
-                    0
-                } else {
-                    current_time
-                        .checked_sub(tge_time)
-                        .ok_or(CustomError::ArithmeticOverflow)?
-                };
+        emit!(BonusTiersUpdated {
+            authority: ctx.accounts.authority.key(),
+            volume_tiers,
+            time_tiers,
+            bonus_vesting_type,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
 
-                // Calculate vested amount based on time passed
-                let mut vested_amount = initial_release; // Start with TGE release (5%)
-
-                // TGE + 8 months: 10%
-                if time_elapsed >= milestone_8_months {
-                    vested_amount = vested_amount
-                        .checked_add(
-                            total_allocation
-                                .checked_mul(10)
-                                .ok_or(CustomError::ArithmeticOverflow)?
-                                .checked_div(100)
-                                .ok_or(CustomError::ArithmeticOverflow)?,
-                        ).ok_or(CustomError::ArithmeticOverflow)?;
-                }
+        Ok(())
+    }
 
-                // TGE + 18 months: 20%
-                if time_elapsed >= milestone_18_months {
-                    vested_amount = vested_amount
-                        .checked_add(
-                            total_allocation
-                                .checked_mul(20)
-                                .ok_or(CustomError::ArithmeticOverflow)?
-                                .checked_div(100)
-                                .ok_or(CustomError::ArithmeticOverflow)?,
-                        ).ok_or(CustomError::ArithmeticOverflow)?;
-                }
+    // Referral terms for the sale: bonus reward tokens and share of proceeds, both in
+    // basis points of a referred purchase, plus a cap on bonus tokens per referrer (0 = uncapped)
+    pub fn set_referral_config(
+        ctx: Context<SetIcoConfig>,
+        bonus_bps: u16,
+        proceeds_bps: u16,
+        bonus_cap: u64,
+    ) -> Result<()> {
+        // Check that the caller is the authority
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.ico_config.authority,
+            CustomError::UnauthorizedAccess
+        );
 
-                // TGE + 24 months: 20%
-                if time_elapsed >= milestone_24_months {
-                    vested_amount = vested_amount
-                        .checked_add(
-                            total_allocation
-                                .checked_mul(20)
-                                .ok_or(CustomError::ArithmeticOverflow)?
-                                .checked_div(100)
-                                .ok_or(CustomError::ArithmeticOverflow)?,
-                        ).ok_or(CustomError::ArithmeticOverflow)?;
-                }
+        require!(
+            bonus_bps <= MAX_REFERRAL_BPS && proceeds_bps <= MAX_REFERRAL_BPS,
+            CustomError::ReferralBpsTooHigh
+        );
 
-                // TGE + 36 months: 45%
-                if time_elapsed >= milestone_36_months {
-                    vested_amount = vested_amount
-                        .checked_add(
-                            total_allocation
-                                .checked_mul(45)
-                                .ok_or(CustomError::ArithmeticOverflow)?
-                                .checked_div(100)
-                                .ok_or(CustomError::ArithmeticOverflow)?,
-                        ).ok_or(CustomError::ArithmeticOverflow)?;
-                }
+        ctx.accounts.ico_config.referral_bonus_bps = bonus_bps;
+        ctx.accounts.ico_config.referral_proceeds_bps = proceeds_bps;
+        ctx.accounts.ico_config.referral_bonus_cap = bonus_cap;
 
-                vested_amount
-            }
-            _ => return Err(CustomError::InvalidVestingType.into()),
-        };
+        msg!(
+            "Referral terms updated: bonus {} bps (cap {}), proceeds {} bps",
+            bonus_bps,
+            bonus_cap,
+            proceeds_bps
+        );
 
-        // Calculate claimable tokens (total releasable minus already claimed)
-        msg!("already_claimed ===========> {}", already_claimed);
-        msg!("total_releasable ===========> {}", total_releasable);
-        let claimable = total_releasable.saturating_sub(already_claimed);
-        msg!("claimable ===========> {}", claimable);
+        emit!(ReferralConfigUpdated {
+            authority: ctx.accounts.authority.key(),
+            bonus_bps,
+            proceeds_bps,
+            bonus_cap,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
 
-        Ok((claimable, total_releasable))
+        Ok(())
     }
 
-    // =========== get functions ========================
-
-    pub fn get_ico_dates(ctx: Context<GetIcoDates>) -> Result<()> {
-        let config = &ctx.accounts.ico_config;
-
-        msg!("ICO Start Time: {}", config.ico_start_time);
-        msg!("ICO End Time: {}", config.ico_end_time);
-        msg!("TGE Time: {}", config.tge_time);
+    // Open a referrer account for the signing wallet
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+        let referrer = &mut ctx.accounts.referrer_account;
 
-        Ok(())
-    }
+        referrer.address = ctx.accounts.referrer.key();
+        referrer.bonus_tokens = 0;
+        referrer.claimed_bonus = 0;
+        referrer.referred_tokens = 0;
+        referrer.referral_count = 0;
 
-    pub fn get_token_rate(ctx: Context<GetTokenRate>) -> Result<()> {
-        let config = &ctx.accounts.ico_config;
+        msg!("Referrer {} registered", referrer.address);
 
-        msg!("Token Rate per SOL: {}", config.tokens_per_sol);
-        msg!("Token Rate per USDC: {}", config.tokens_per_usdc);
-        msg!("Token Rate per USDT: {}", config.tokens_per_usdt);
+        emit!(ReferrerRegistered {
+            referrer: referrer.address,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
 
         Ok(())
     }
 
-    pub fn get_min_max_buy_amount(ctx: Context<GetMinMaxBuyAmount>) -> Result<()> {
-        let config = &ctx.accounts.ico_config;
+    // Claim referral bonus tokens. The bonus vests like an Immediate vesting position.
+    pub fn claim_referral_bonus(ctx: Context<ClaimReferralBonus>) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+        let referrer = &mut ctx.accounts.referrer_account;
 
-        msg!("Min Buy Amount: {}", config.min_amount);
-        msg!("Max Buy Amount: {}", config.max_amount);
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
 
-        Ok(())
-    }
+        // Check if TGE has happened
+        require!(current_time >= config.tge_time, CustomError::TGEDateInvalid);
 
-    pub fn get_seconds_per_day(ctx: Context<GetSecondsPerDay>) -> Result<()> {
-        let config = &ctx.accounts.ico_config;
+        let (claimable_tokens, _released_tokens) = calculate_claimable_tokens(
+            VESTING_TYPE_IMMEDIATE,
+            referrer.bonus_tokens,
+            referrer.claimed_bonus,
+            config.tge_time,
+            config.seconds_per_day,
+            current_time,
+        )?;
 
-        msg!("Seconds Per Day: {}", config.seconds_per_day);
+        require!(claimable_tokens > 0, CustomError::NoTokensAvailableToClaim);
 
-        Ok(())
+        let tokens_amount = claimable_tokens
+            .checked_mul(TOKEN_DECIMALS)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        let mut payer = RewardPayer {
+            reward_token_mint: &mut ctx.accounts.reward_token_mint,
+            reward_token_treasury: &mut ctx.accounts.reward_token_treasury,
+            vault_authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            vault_bump: ctx.bumps.vault_authority,
+        };
+        payer.pay(
+            config,
+            ctx.accounts.referrer_token_account.to_account_info(),
+            tokens_amount,
+        )?;
+
+        referrer.claimed_bonus = referrer
+            .claimed_bonus
+            .checked_add(claimable_tokens)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        config.total_claimed = config
+            .total_claimed
+            .checked_add(claimable_tokens)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        config.claim_count = config
+            .claim_count
+            .checked_add(1)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        msg!("Referrer {} claimed {} bonus tokens", referrer.address, claimable_tokens);
+
+        emit!(ReferralBonusClaimed {
+            referrer: referrer.address,
+            amount: claimable_tokens,
+            remaining: referrer.bonus_tokens.saturating_sub(referrer.claimed_bonus),
+            timestamp: current_time,
+        });
+
+        Ok(())
     }
 
-    pub fn get_sol_balance(ctx: Context<GetSOLBalance>) -> Result<()> {
-        let balance = ctx.accounts.sol_treasury.lamports();
-        msg!("SOL Treasury Address: {}", ctx.accounts.sol_treasury.key());
-        msg!("SOL Balance (Lamports): {}", balance);
+    // Allow or disallow beneficiaries to move positions to another wallet
+    pub fn set_position_transfer_config(
+        ctx: Context<SetIcoConfig>,
+        enabled: bool,
+        require_admin_cosign: bool,
+    ) -> Result<()> {
+        // Check that the caller is the authority
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.ico_config.authority,
+            CustomError::UnauthorizedAccess
+        );
+
+        ctx.accounts.ico_config.position_transfers_enabled = enabled;
+        ctx.accounts.ico_config.transfer_requires_admin = require_admin_cosign;
+
+        msg!("Position transfers enabled: {}", enabled);
+        msg!("Admin co-sign required: {}", require_admin_cosign);
+
+        emit!(PositionTransferConfigUpdated {
+            authority: ctx.accounts.authority.key(),
+            enabled,
+            require_admin_cosign,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
         Ok(())
     }
 
-    pub fn get_token_balance(ctx: Context<GetTokenBalance>) -> Result<u64> {
-        // Deserialize the TokenAccount from AccountInfo
-        let token_account: TokenAccount = TokenAccount::try_deserialize(&mut &ctx.accounts.token_account.data.borrow()[..])?;
-    
-        msg!("Balance of token: {}", token_account.amount);
-        // Return the balance
-        Ok(token_account.amount)
+    // Move a vesting position (allocation, claimed and schedule state) to a new wallet.
+    // If the new wallet already holds a position, both are merged when their schedules match.
+    pub fn transfer_position(
+        ctx: Context<TransferPosition>,
+        from_index: u32,
+        new_address: Pubkey,
+        new_index: u32,
+    ) -> Result<()> {
+        register_position(&mut ctx.accounts.to_wallet_positions, new_address, new_index)?;
+
+        let config = &mut ctx.accounts.ico_config;
+        let from = &mut ctx.accounts.from_investor;
+        let to = &mut ctx.accounts.to_investor;
+
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        require!(
+            config.position_transfers_enabled,
+            CustomError::PositionTransfersDisabled
+        );
+
+        let approved_by = match &ctx.accounts.admin {
+            Some(admin) => {
+                require!(
+                    admin.key() == config.authority,
+                    CustomError::UnauthorizedAccess
+                );
+                admin.key()
+            }
+            None => {
+                require!(
+                    !config.transfer_requires_admin,
+                    CustomError::AdminCosignRequired
+                );
+                Pubkey::default()
+            }
+        };
+
+        require!(
+            new_address != Pubkey::default() && new_address != from.address,
+            CustomError::InvalidAddress
+        );
+
+        require!(from.allocation > 0, CustomError::InvestorNotFound);
+        require!(
+            !is_block_active(from, current_time),
+            CustomError::InvestorIsBlocked
+        );
+        require!(!from.revoked, CustomError::PositionRevoked);
+
+        // Tokenized positions move with their position token instead
+        require!(
+            from.position_mint == Pubkey::default() && to.position_mint == Pubkey::default(),
+            CustomError::PositionIsTokenized
+        );
+
+        let merged = to.allocation > 0;
+
+        // The source position always leaves the beneficiary's wallet
+        forget_position(
+            config,
+            &mut ctx.accounts.from_wallet_positions,
+            from.vesting_type,
+        );
+
+        if merged {
+            merge_positions(to, from, current_time)?;
+        } else {
+            record_new_position(
+                config,
+                &mut ctx.accounts.to_wallet_positions,
+                from.vesting_type,
+            )?;
+
+            to.address = new_address;
+            to.position_index = new_index;
+            to.allocation = from.allocation;
+            to.vesting_type = from.vesting_type;
+            to.released_tokens = from.released_tokens;
+            to.cliff_end = from.cliff_end;
+            to.last_claimed = from.last_claimed;
+            to.claimed_tokens = from.claimed_tokens;
+            to.blocked = false;
+            to.whitelisted_at = from.whitelisted_at;
+            to.block_reason = BLOCK_REASON_UNSPECIFIED;
+            to.blocked_until = 0;
+            to.revocable = from.revocable;
+            to.revoked = false;
+            to.revoked_at = 0;
+            to.position_mint = Pubkey::default();
+            to.claim_destination = Pubkey::default();
+        }
+
+        msg!(
+            "Position {} of {} transferred to position {} of {}",
+            from_index,
+            from.address,
+            new_index,
+            new_address
+        );
+        msg!("Transferred allocation: {}", from.allocation);
+        msg!("Transferred claimed tokens: {}", from.claimed_tokens);
+        msg!("Merged into existing position: {}", merged);
+
+        emit!(PositionTransferred {
+            from: from.address,
+            from_index,
+            to: new_address,
+            to_index: new_index,
+            allocation: from.allocation,
+            claimed_tokens: from.claimed_tokens,
+            merged,
+            approved_by,
+            timestamp: current_time,
+        });
+
+        // The source account is closed to the beneficiary once the instruction completes
+        Ok(())
     }
 
-    pub fn get_investor_address(ctx: Context<GetInvestorAddress>) -> Result<()> {
-        let investor_address = ctx.accounts.investor_details.address;
-        msg!("Investor Address: {}", investor_address);
+    // Let the beneficiary route claims of a position to another wallet (e.g. cold storage).
+    // None resets claims back to the beneficiary itself.
+    pub fn set_claim_destination(
+        ctx: Context<SetClaimDestination>,
+        investor_address: Pubkey,
+        position_index: u32,
+        destination: Option<Pubkey>,
+    ) -> Result<()> {
+        let investor = &mut ctx.accounts.investor_details;
+
+        require!(
+            investor.address == ctx.accounts.beneficiary.key(),
+            CustomError::UnauthorizedAccess
+        );
+
+        // A tokenized position pays whoever holds the position token
+        require!(
+            investor.position_mint == Pubkey::default(),
+            CustomError::PositionIsTokenized
+        );
+
+        let destination = destination.unwrap_or_default();
+        investor.claim_destination = destination;
+
+        msg!(
+            "Claim destination of position {} of {} set to {}",
+            position_index,
+            investor_address,
+            destination
+        );
+
+        emit!(ClaimDestinationUpdated {
+            investor: investor.address,
+            position_index,
+            destination,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
         Ok(())
     }
 
-    // Function to get vesting balance information (read-only)
-    pub fn get_vesting_balance(
-        ctx: Context<GetVestingBalance>,
+    // Represent a position by a single-supply token minted by the program.
+    // From then on whoever holds that token can claim the position.
+    pub fn tokenize_position(
+        ctx: Context<TokenizePosition>,
         investor_address: Pubkey,
+        position_index: u32,
     ) -> Result<()> {
-        let config = &ctx.accounts.ico_config;
-        let investor = &ctx.accounts.investor_details;
+        let investor = &mut ctx.accounts.investor_details;
+
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp as u64;
-    
-        // Check if investor exists
+
+        require!(
+            investor.address == ctx.accounts.beneficiary.key(),
+            CustomError::UnauthorizedAccess
+        );
         require!(investor.allocation > 0, CustomError::InvestorNotFound);
-    
-        // Calculate claimable tokens based on vesting type
-        let (claimable_tokens, total_releasable) = calculate_claimable_tokens(
-            investor.vesting_type,
-            investor.allocation,
-            investor.claimed_tokens,
-            config.tge_time,
-            config.seconds_per_day,
-            current_time,
-        )?;
-    
-        // Calculate total allocated
-        let total_allocated = investor.allocation;
-    
-        // Calculate total remaining
-        let total_remaining = total_allocated.saturating_sub(investor.claimed_tokens);
-    
-        // Get next cliff end date
-        let next_cliff = get_next_cliff_end(
-            investor.vesting_type,
-            investor.cliff_end,
-            config.seconds_per_day,
-            config.tge_time,
-            current_time,
-        )?;
-    
-        // Log all information using msg! macros
-        msg!("===== Vesting Balance Information =====");
-        msg!("Investor Address: {}", investor.address);
-        msg!("Vesting Type: {}", investor.vesting_type);
-        msg!("Total Allocation: {}", total_allocated);
-        msg!("Claimed Tokens: {}", investor.claimed_tokens);
-        msg!("Claimable Now: {}", claimable_tokens);
-        msg!("Total Vested: {}", total_releasable);
-        msg!("Remaining Tokens: {}", total_remaining);
-        msg!("Next Cliff Timestamp: {}", next_cliff);
-        msg!("Last Claimed Timestamp: {}", investor.last_claimed);
-        msg!("Is Blocked: {}", investor.blocked);
-        msg!("======================================");
-    
+        require!(
+            investor.position_mint == Pubkey::default(),
+            CustomError::PositionIsTokenized
+        );
+        require!(
+            !is_block_active(investor, current_time),
+            CustomError::InvestorIsBlocked
+        );
+
+        let seeds = &[b"vault_authority".as_ref(), &[ctx.bumps.vault_authority]];
+        let signer = &[&seeds[..]];
+
+        // Mint the single position token to the beneficiary
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.position_mint.to_account_info(),
+            to: ctx.accounts.beneficiary_position_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+
+        token_interface::mint_to(cpi_context, 1)?;
+
+        // Drop the mint authority so the supply stays fixed at one
+        let cpi_accounts = SetAuthority {
+            current_authority: ctx.accounts.vault_authority.to_account_info(),
+            account_or_mint: ctx.accounts.position_mint.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+
+        token_interface::set_authority(cpi_context, AuthorityType::MintTokens, None)?;
+
+        investor.position_mint = ctx.accounts.position_mint.key();
+        investor.claim_destination = Pubkey::default();
+
+        msg!("Position {} of {} tokenized", position_index, investor_address);
+        msg!("Position Mint: {}", investor.position_mint);
+
+        emit!(PositionTokenized {
+            investor: investor.address,
+            position_mint: investor.position_mint,
+            holder: ctx.accounts.beneficiary.key(),
+            timestamp: current_time,
+        });
+
         Ok(())
     }
 
-        // Function to get linear vesting end time (read-only)
-    pub fn get_linear_vesting_end_time(
-        ctx: Context<GetLinearVestingEndTime>,
+    // Transfer Ownership =======================
+    pub fn transfer_ownership(
+        ctx: Context<TransferOwnership>,
+        new_authority: Pubkey,
     ) -> Result<()> {
-        let config = &ctx.accounts.ico_config;
+        let config = &mut ctx.accounts.ico_config;
+        require!(
+            ctx.accounts.authority.key() == config.authority,
+            CustomError::UnauthorizedAccess
+        );
+        require!(
+            new_authority != Pubkey::default(),
+            CustomError::InvalidAddress
+        );
+        config.authority = new_authority;
+
+        msg!("Ownership transferred to {}", new_authority);
+
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp as u64;
-            
-        // Calculate month in seconds
-        let month_in_seconds = config.seconds_per_day
-            .checked_mul(30)
-            .ok_or(CustomError::ArithmeticOverflow)?;
-            
-        // Calculate milestone timestamps
-        let tge_timestamp = config.tge_time;
-            
-        // Define milestones
-        let milestone_8_months = tge_timestamp.checked_add(
-            month_in_seconds
-                .checked_mul(8)
-                .ok_or(CustomError::ArithmeticOverflow)?
-            ).ok_or(CustomError::ArithmeticOverflow)?;
-            
-        let milestone_18_months = tge_timestamp.checked_add(
-            month_in_seconds
-                .checked_mul(18)
-                .ok_or(CustomError::ArithmeticOverflow)?
-            ).ok_or(CustomError::ArithmeticOverflow)?;
-            
-        let milestone_24_months = tge_timestamp.checked_add(
-            month_in_seconds
-                .checked_mul(24)
-                .ok_or(CustomError::ArithmeticOverflow)?
-            ).ok_or(CustomError::ArithmeticOverflow)?;
-            
-        let milestone_36_months = tge_timestamp.checked_add(
-            month_in_seconds
-                .checked_mul(36)
-                .ok_or(CustomError::ArithmeticOverflow)?
-            ).ok_or(CustomError::ArithmeticOverflow)?;
-            
-        let is_completed = current_time >= milestone_36_months;
-            
-        // Log all information using msg! macros
-        msg!("===== Linear Vesting Schedule =====");
-        msg!("TGE Timestamp: {}", tge_timestamp);
-        msg!("8-Month Milestone (15%): {}", milestone_8_months);
-        msg!("18-Month Milestone (35%): {}", milestone_18_months);
-        msg!("24-Month Milestone (55%): {}", milestone_24_months);
-        msg!("36-Month Milestone (100%): {}", milestone_36_months);
-        msg!("Vesting Completed: {}", is_completed);
-        msg!("==================================");
-            
+
+        emit!(OwnershipTransferred {
+            previous_owner: ctx.accounts.authority.key(),
+            new_owner: new_authority,
+            timestamp: current_time,
+        });
+
         Ok(())
     }
-}
 
-// Add this private helper function to handle vesting cliff logic
-fn get_next_cliff_end(
-    vesting_type: u8,
-    last_cliff_end: u64,
-    seconds_per_day: u64,
-    tge_date: u64,
-    current_time: u64,
-) -> Result<u64> {
-    match vesting_type {
-        VESTING_TYPE_IMMEDIATE => {
-            // Days passed since last cliff end
-            let days_passed = if current_time <= last_cliff_end {
-                0
-            } else {
-                current_time
-                    .checked_sub(last_cliff_end)
-                    .ok_or(CustomError::ArithmeticOverflow)?
-                    .checked_div(seconds_per_day)
-                    .ok_or(CustomError::ArithmeticOverflow)?
-            };
+    // Renounce Ownership =========================
 
-            println!("days_passed =============> {:?}", days_passed);
+    pub fn renounce_ownership(ctx: Context<RenounceOwnership>) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+        require!(
+            ctx.accounts.authority.key() == config.authority,
+            CustomError::UnauthorizedAccess
+        );
 
-            // Cap at 365 days
-            // To get the next cliff, increased by 1
-            let effective_days = std::cmp::min(days_passed + 1, 365);
-            println!("effective_days =============> {:?}", effective_days);
+        config.authority = Pubkey::default();
 
-            let total_seconds = seconds_per_day
-                .checked_mul(effective_days)
+        msg!("Ownership renounced");
+
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        emit!(OwnershipRenounced {
+            previous_owner: ctx.accounts.authority.key(),
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Claim vested tokens of a position. `amount` (whole tokens) claims part of what is
+    // vested; None claims everything currently claimable.
+    pub fn claim_tokens(
+        ctx: Context<ClaimTokens>,
+        investor_address: Pubkey,
+        position_index: u32,
+        amount: Option<u64>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+
+        let investor = &mut ctx.accounts.investor_details;
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        // require_keys_eq!(ctx.accounts.investor.key(), investor.address, CustomError::UnauthorizedAccess);
+
+        // A tokenized position is claimed by whoever holds its position token
+        if investor.position_mint != Pubkey::default() {
+            let position_token_account = ctx
+                .accounts
+                .position_token_account
+                .as_ref()
+                .ok_or(CustomError::PositionTokenAccountRequired)?;
+
+            require!(
+                position_token_account.mint == investor.position_mint
+                    && position_token_account.owner == ctx.accounts.investor.key()
+                    && position_token_account.amount == 1,
+                CustomError::NotPositionHolder
+            );
+        } else {
+            require!(
+                investor.address == ctx.accounts.investor.key(),
+                CustomError::UnauthorizedAccess
+            );
+        }
+
+        // Tokens go to the associated token account of the claim destination
+        let destination = claim_destination_of(investor, ctx.accounts.investor.key());
+        require_keys_eq!(
+            ctx.accounts.claim_recipient.key(),
+            destination,
+            CustomError::InvalidClaimDestination
+        );
+
+        // Check if investor is blocked (an expired block no longer applies)
+        require!(
+            !is_block_active(investor, current_time),
+            CustomError::InvestorIsBlocked
+        );
+
+        // Check if TGE has happened
+        require!(current_time >= config.tge_time, CustomError::TGEDateInvalid);
+
+        // Calculate claimable tokens based on vesting type
+        let (claimable_tokens, _released_tokens) =
+            calculate_investor_claimable(investor, config, current_time)?;
+
+        msg!("claimable_tokens =====> {}", claimable_tokens);
+
+        // Check if there are tokens to claim
+        require!(claimable_tokens > 0, CustomError::NoTokensAvailableToClaim);
+
+        let claim_amount = claim_amount_of(amount, claimable_tokens)?;
+
+        let vault_auth = &ctx.bumps.vault_authority;
+
+        let tokens_amount = claim_amount
+            .checked_mul(TOKEN_DECIMALS)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        // Transfer (or mint) tokens to investor
+        let mut payer = RewardPayer {
+            reward_token_mint: &mut ctx.accounts.reward_token_mint,
+            reward_token_treasury: &mut ctx.accounts.reward_token_treasury,
+            vault_authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            vault_bump: ctx.bumps.vault_authority,
+        };
+        payer.pay(
+            config,
+            ctx.accounts.investor_token_account.to_account_info(),
+            tokens_amount,
+        )?;
+
+        record_claim(config, investor, claim_amount, current_time)?;
+
+        msg!("Tokens claimed successfully!");
+        msg!("Investor: {}", investor.address);
+        msg!("Position: {}", position_index);
+        msg!("Destination: {}", destination);
+        msg!("Claimed amount: {}", claim_amount);
+        msg!(
+            "Still claimable: {}",
+            claimable_tokens.saturating_sub(claim_amount)
+        );
+        msg!("Total claimed so far: {}", investor.claimed_tokens);
+        msg!(
+            "Remaining to claim: {}",
+            investor.allocation.saturating_sub(investor.claimed_tokens)
+        );
+
+        emit!(TokensClaimed {
+            investor: investor.address,
+            position_index,
+            destination,
+            amount: claim_amount,
+            timestamp: current_time,
+            remaining: investor.allocation.saturating_sub(investor.claimed_tokens),
+        });
+
+        Ok(())
+    }
+
+    // Permissionless claim on behalf of an investor. Always pays the investor's own
+    // associated token account; the keeper may take the configured fee in reward tokens.
+    pub fn crank_claim(
+        ctx: Context<CrankClaim>,
+        investor_address: Pubkey,
+        position_index: u32,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+        let investor = &mut ctx.accounts.investor_details;
+
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        // The holder of a tokenized position is not known to the keeper
+        require!(
+            investor.position_mint == Pubkey::default(),
+            CustomError::PositionIsTokenized
+        );
+
+        require!(
+            !is_block_active(investor, current_time),
+            CustomError::InvestorIsBlocked
+        );
+
+        // Check if TGE has happened
+        require!(current_time >= config.tge_time, CustomError::TGEDateInvalid);
+
+        let (claimable_tokens, _released_tokens) =
+            calculate_investor_claimable(investor, config, current_time)?;
+
+        require!(claimable_tokens > 0, CustomError::NoTokensAvailableToClaim);
+
+        let tokens_amount = claimable_tokens
+            .checked_mul(TOKEN_DECIMALS)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        // Fee is only taken when the keeper can receive it
+        let keeper_fee = if ctx.accounts.keeper_token_account.is_some() {
+            keeper_fee_for(config, tokens_amount)?
+        } else {
+            0
+        };
+
+        let investor_amount = tokens_amount
+            .checked_sub(keeper_fee)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        let mut payer = RewardPayer {
+            reward_token_mint: &mut ctx.accounts.reward_token_mint,
+            reward_token_treasury: &mut ctx.accounts.reward_token_treasury,
+            vault_authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            vault_bump: ctx.bumps.vault_authority,
+        };
+
+        payer.pay(
+            config,
+            ctx.accounts.investor_token_account.to_account_info(),
+            investor_amount,
+        )?;
+
+        if let Some(keeper_token_account) = &ctx.accounts.keeper_token_account {
+            payer.pay(config, keeper_token_account.to_account_info(), keeper_fee)?;
+        }
+
+        record_claim(config, investor, claimable_tokens, current_time)?;
+
+        msg!(
+            "Crank claim of {} tokens for position {} of {}",
+            claimable_tokens,
+            position_index,
+            investor_address
+        );
+        msg!("Keeper fee (base units): {}", keeper_fee);
+
+        emit!(TokensClaimed {
+            investor: investor.address,
+            position_index,
+            destination: investor.address,
+            amount: claimable_tokens,
+            timestamp: current_time,
+            remaining: investor.allocation.saturating_sub(investor.claimed_tokens),
+        });
+
+        emit!(CrankClaimed {
+            investor: investor.address,
+            position_index,
+            keeper: ctx.accounts.keeper.key(),
+            amount: claimable_tokens,
+            keeper_fee,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Crank claims for many positions at once.
+    // remaining_accounts: [investor PDA, investor's reward token ATA] per entry, all writable.
+    // Positions with nothing to claim are skipped.
+    pub fn crank_claim_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankClaimBatch<'info>>,
+        entries: Vec<BatchClaimEntry>,
+    ) -> Result<BatchResult> {
+        let config = &mut ctx.accounts.ico_config;
+
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        // Check if TGE has happened
+        require!(current_time >= config.tge_time, CustomError::TGEDateInvalid);
+
+        require!(
+            ctx.remaining_accounts.len() == entries.len() * 2,
+            CustomError::InvalidRemainingAccounts
+        );
+
+        let mut payer = RewardPayer {
+            reward_token_mint: &mut ctx.accounts.reward_token_mint,
+            reward_token_treasury: &mut ctx.accounts.reward_token_treasury,
+            vault_authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            vault_bump: ctx.bumps.vault_authority,
+        };
+
+        let mut total_keeper_fee: u64 = 0;
+        let mut result = BatchResult::new(entries.len());
+
+        for (i, entry) in entries.iter().enumerate() {
+            if sol_remaining_compute_units() < BATCH_MIN_COMPUTE_UNITS {
+                break;
+            }
+
+            let investor_info = &ctx.remaining_accounts[2 * i];
+            let token_account_info = &ctx.remaining_accounts[2 * i + 1];
+
+            let (investor_pda, _bump) = Pubkey::find_program_address(
+                &[
+                    b"investor is my hero",
+                    entry.investor_address.as_ref(),
+                    &entry.position_index.to_le_bytes(),
+                ],
+                &crate::ID,
+            );
+            require_keys_eq!(
+                investor_info.key(),
+                investor_pda,
+                CustomError::InvalidPositionAccount
+            );
+
+            // Closed or never created
+            if investor_info.owner != &crate::ID {
+                result.set(i, BATCH_STATUS_INVALID);
+                continue;
+            }
+
+            let mut investor: Account<'info, Investor> = Account::try_from(investor_info)?;
+
+            require_keys_eq!(
+                token_account_info.key(),
+                get_associated_token_address_with_program_id(
+                    &investor.address,
+                    &config.reward_token_mint,
+                    &ctx.accounts.token_program.key(),
+                ),
+                CustomError::InvalidAddress
+            );
+
+            if token_account_info.owner != &ctx.accounts.token_program.key() {
+                result.set(i, BATCH_STATUS_INVALID);
+                continue;
+            }
+
+            if investor.position_mint != Pubkey::default()
+                || is_block_active(&investor, current_time)
+            {
+                result.set(i, BATCH_STATUS_SKIPPED);
+                continue;
+            }
+
+            let (claimable_tokens, _released_tokens) =
+                calculate_investor_claimable(&investor, config, current_time)?;
+
+            if claimable_tokens == 0 {
+                result.set(i, BATCH_STATUS_SKIPPED);
+                continue;
+            }
+
+            let tokens_amount = claimable_tokens
+                .checked_mul(TOKEN_DECIMALS)
                 .ok_or(CustomError::ArithmeticOverflow)?;
 
-            last_cliff_end
-                .checked_add(total_seconds)
-                .ok_or(CustomError::ArithmeticOverflow.into())
+            let keeper_fee = if ctx.accounts.keeper_token_account.is_some() {
+                keeper_fee_for(config, tokens_amount)?
+            } else {
+                0
+            };
+
+            payer.pay(
+                config,
+                token_account_info.clone(),
+                tokens_amount
+                    .checked_sub(keeper_fee)
+                    .ok_or(CustomError::ArithmeticOverflow)?,
+            )?;
+
+            record_claim(config, &mut investor, claimable_tokens, current_time)?;
+            investor.exit(&crate::ID)?;
+
+            total_keeper_fee = total_keeper_fee
+                .checked_add(keeper_fee)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            emit!(TokensClaimed {
+                investor: investor.address,
+                position_index: entry.position_index,
+                destination: investor.address,
+                amount: claimable_tokens,
+                timestamp: current_time,
+                remaining: investor.allocation.saturating_sub(investor.claimed_tokens),
+            });
+
+            emit!(CrankClaimed {
+                investor: investor.address,
+                position_index: entry.position_index,
+                keeper: ctx.accounts.keeper.key(),
+                amount: claimable_tokens,
+                keeper_fee,
+                timestamp: current_time,
+            });
+
+            result.set(i, BATCH_STATUS_PROCESSED);
         }
 
-        VESTING_TYPE_LINEAR => {
-            let month_in_seconds = seconds_per_day
-                .checked_mul(30)
+        if let Some(keeper_token_account) = &ctx.accounts.keeper_token_account {
+            payer.pay(config, keeper_token_account.to_account_info(), total_keeper_fee)?;
+        }
+
+        msg!(
+            "Batch claim: {} processed, {} skipped, {} invalid, {} not processed",
+            result.processed,
+            result.skipped,
+            result.invalid,
+            result.not_processed
+        );
+
+        Ok(result)
+    }
+
+    // Claim every position of the signing wallet in one transfer.
+    // remaining_accounts: the wallet's Investor PDAs (writable). Tokenized and blocked
+    // positions are skipped; tokenized ones are claimed through claim_tokens. Only positions
+    // whose claim destination is claim_recipient are included.
+    pub fn claim_all_positions<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimAllPositions<'info>>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.ico_config;
+        let wallet = ctx.accounts.investor.key();
+        let destination = ctx.accounts.claim_recipient.key();
+
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        // Check if TGE has happened
+        require!(current_time >= config.tge_time, CustomError::TGEDateInvalid);
+
+        let mut total_claimable: u64 = 0;
+
+        for account in ctx.remaining_accounts.iter() {
+            let mut investor = load_wallet_position(account, wallet)?;
+
+            if investor.position_mint != Pubkey::default()
+                || is_block_active(&investor, current_time)
+                || claim_destination_of(&investor, wallet) != destination
+            {
+                continue;
+            }
+
+            let (claimable_tokens, _released_tokens) =
+                calculate_investor_claimable(&investor, config, current_time)?;
+
+            if claimable_tokens == 0 {
+                continue;
+            }
+
+            record_claim(config, &mut investor, claimable_tokens, current_time)?;
+            investor.exit(&crate::ID)?;
+
+            total_claimable = total_claimable
+                .checked_add(claimable_tokens)
                 .ok_or(CustomError::ArithmeticOverflow)?;
 
-            const MILESTONE_PERIODS: [u64; 4] = [8, 10, 6, 12];
-            let mut cumulative_months = 0;
+            emit!(TokensClaimed {
+                investor: wallet,
+                position_index: investor.position_index,
+                destination,
+                amount: claimable_tokens,
+                timestamp: current_time,
+                remaining: investor.allocation.saturating_sub(investor.claimed_tokens),
+            });
+        }
+
+        require!(total_claimable > 0, CustomError::NoTokensAvailableToClaim);
+
+        let tokens_amount = total_claimable
+            .checked_mul(TOKEN_DECIMALS)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+
+        let mut payer = RewardPayer {
+            reward_token_mint: &mut ctx.accounts.reward_token_mint,
+            reward_token_treasury: &mut ctx.accounts.reward_token_treasury,
+            vault_authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            vault_bump: ctx.bumps.vault_authority,
+        };
+        payer.pay(
+            config,
+            ctx.accounts.investor_token_account.to_account_info(),
+            tokens_amount,
+        )?;
+
+        msg!("Claimed {} tokens across positions of {}", total_claimable, wallet);
+
+        Ok(())
+    }
+
+    // =========== get functions ========================
+
+    // View instructions return their results through return data; simulate them and decode with the IDL
+
+    pub fn get_ico_dates(ctx: Context<GetIcoDates>) -> Result<IcoDates> {
+        let config = &ctx.accounts.ico_config;
+
+        Ok(IcoDates {
+            ico_start_time: config.ico_start_time,
+            ico_end_time: config.ico_end_time,
+            tge_time: config.tge_time,
+        })
+    }
+
+    pub fn get_token_rate(ctx: Context<GetTokenRate>) -> Result<RateInfo> {
+        let config = &ctx.accounts.ico_config;
+        let payment_mint = ctx.accounts.payment_mint.as_ref();
+
+        Ok(RateInfo {
+            tokens_per_sol: config.tokens_per_sol,
+            payment_mint: payment_mint.map(|payment_mint| payment_mint.mint),
+            tokens_per_token: payment_mint.map_or(0, |payment_mint| payment_mint.tokens_per_token),
+            payment_mint_enabled: payment_mint.is_some_and(|payment_mint| payment_mint.enabled),
+        })
+    }
+
+    pub fn get_min_max_buy_amount(ctx: Context<GetMinMaxBuyAmount>) -> Result<BuyLimits> {
+        let config = &ctx.accounts.ico_config;
+
+        Ok(BuyLimits {
+            min_amount: config.min_amount,
+            max_amount: config.max_amount,
+        })
+    }
+
+    pub fn get_seconds_per_day(ctx: Context<GetSecondsPerDay>) -> Result<u64> {
+        Ok(ctx.accounts.ico_config.seconds_per_day)
+    }
+
+    // Lamports held by the SOL treasury
+    pub fn get_sol_balance(ctx: Context<GetSOLBalance>) -> Result<u64> {
+        Ok(ctx.accounts.sol_treasury.lamports())
+    }
+
+    pub fn get_token_balance(ctx: Context<GetTokenBalance>) -> Result<u64> {
+        // Deserialize the TokenAccount from AccountInfo
+        let token_account: TokenAccount = TokenAccount::try_deserialize(&mut &ctx.accounts.token_account.data.borrow()[..])?;
+    
+        msg!("Balance of token: {}", token_account.amount);
+        // Return the balance
+        Ok(token_account.amount)
+    }
+
+    pub fn get_investor_address(ctx: Context<GetInvestorAddress>) -> Result<Pubkey> {
+        Ok(ctx.accounts.investor_details.address)
+    }
+
+    // Function to get vesting balance information (read-only)
+    pub fn get_vesting_balance(
+        ctx: Context<GetVestingBalance>,
+        investor_address: Pubkey,
+        position_index: u32,
+    ) -> Result<VestingBalance> {
+        let config = &ctx.accounts.ico_config;
+        let investor = &ctx.accounts.investor_details;
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+    
+        // Check if investor exists
+        require!(investor.allocation > 0, CustomError::InvestorNotFound);
+    
+        // Calculate claimable tokens based on vesting type
+        let (claimable_tokens, total_releasable) =
+            calculate_investor_claimable(investor, config, current_time)?;
+    
+        // Calculate total allocated
+        let total_allocated = investor.allocation;
+    
+        // Calculate total remaining
+        let total_remaining = total_allocated.saturating_sub(investor.claimed_tokens);
+    
+        // Get next cliff end date
+        let next_cliff = get_next_cliff_end(
+            investor.vesting_type,
+            investor.cliff_end,
+            config.seconds_per_day,
+            config.tge_time,
+            current_time,
+        )?;
+    
+        Ok(VestingBalance {
+            investor: investor.address,
+            position_index,
+            vesting_type: investor.vesting_type,
+            total_allocation: total_allocated,
+            claimed_tokens: investor.claimed_tokens,
+            claimable_tokens,
+            total_vested: total_releasable,
+            remaining_tokens: total_remaining,
+            next_cliff,
+            last_claimed: investor.last_claimed,
+            blocked: is_block_active(investor, current_time),
+            block_reason: investor.block_reason,
+            blocked_until: investor.blocked_until,
+            revoked: investor.revoked,
+        })
+    }
+
+    // Sale-wide totals (read-only).
+    // remaining_accounts: PaymentMint PDAs whose amounts raised should be included.
+    pub fn get_sale_stats<'info>(
+        ctx: Context<'_, '_, 'info, 'info, GetSaleStats<'info>>,
+    ) -> Result<SaleStats> {
+        let config = &ctx.accounts.ico_config;
+
+        let mut token_raised = Vec::with_capacity(ctx.remaining_accounts.len());
+        for account in ctx.remaining_accounts.iter() {
+            let payment_mint: Account<'info, PaymentMint> = Account::try_from(account)?;
+            token_raised.push(TokenRaised {
+                mint: payment_mint.mint,
+                total_raised: payment_mint.total_raised,
+            });
+        }
+
+        Ok(SaleStats {
+            total_allocated: config.total_allocated,
+            total_user_allocated: config.total_user_allocated,
+            total_prize_deposited: config.total_prize_deposited,
+            total_claimed: config.total_claimed,
+            claim_count: config.claim_count,
+            position_count: config
+                .immediate_positions
+                .checked_add(config.linear_positions)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+            immediate_positions: config.immediate_positions,
+            linear_positions: config.linear_positions,
+            wallet_count: config.wallet_count,
+            sol_raised: config.sol_raised,
+            token_raised,
+        })
+    }
+
+    // Future unlock schedule of a position (read-only)
+    pub fn get_vesting_timeline(
+        ctx: Context<GetVestingBalance>,
+        investor_address: Pubkey,
+        position_index: u32,
+    ) -> Result<VestingTimeline> {
+        let config = &ctx.accounts.ico_config;
+        let investor = &ctx.accounts.investor_details;
+
+        // A revoked grant was frozen at what had vested, so nothing unlocks after revocation
+        let points = if investor.revoked {
+            vec![TimelinePoint {
+                timestamp: investor.revoked_at,
+                cumulative_vested: investor.allocation,
+                unlocked: investor.allocation,
+            }]
+        } else {
+            // Check if investor exists
+            require!(investor.allocation > 0, CustomError::InvestorNotFound);
+            timeline_points(investor.vesting_type, investor.allocation, config)?
+        };
+
+        Ok(VestingTimeline {
+            investor: investor_address,
+            position_index,
+            vesting_type: investor.vesting_type,
+            total_allocation: investor.allocation,
+            claimed_tokens: investor.claimed_tokens,
+            points,
+        })
+    }
+
+    // Unlock schedule of a hypothetical allocation under the sale's TGE (read-only)
+    pub fn get_schedule_timeline(
+        ctx: Context<GetScheduleTimeline>,
+        vesting_type: u8,
+        total_allocation: u64,
+    ) -> Result<VestingTimeline> {
+        let config = &ctx.accounts.ico_config;
+
+        Ok(VestingTimeline {
+            investor: Pubkey::default(),
+            position_index: 0,
+            vesting_type,
+            total_allocation,
+            claimed_tokens: 0,
+            points: timeline_points(vesting_type, total_allocation, config)?,
+        })
+    }
+
+    // Summary across all positions of a wallet (read-only).
+    // remaining_accounts: the wallet's Investor PDAs.
+    pub fn get_wallet_positions<'info>(
+        ctx: Context<'_, '_, 'info, 'info, GetWalletPositions<'info>>,
+        wallet: Pubkey,
+    ) -> Result<WalletPositionsSummary> {
+        let config = &ctx.accounts.ico_config;
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+
+        let mut total_allocation: u64 = 0;
+        let mut total_claimed: u64 = 0;
+        let mut total_claimable: u64 = 0;
+
+        for account in ctx.remaining_accounts.iter() {
+            let investor = load_wallet_position(account, wallet)?;
+
+            let (claimable_tokens, _total_releasable) =
+                calculate_investor_claimable(&investor, config, current_time)?;
+
+            total_allocation = total_allocation
+                .checked_add(investor.allocation)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+            total_claimed = total_claimed
+                .checked_add(investor.claimed_tokens)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+            total_claimable = total_claimable
+                .checked_add(claimable_tokens)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+        }
+
+        Ok(WalletPositionsSummary {
+            wallet,
+            position_count: ctx.accounts.wallet_positions.position_count,
+            positions_included: ctx.remaining_accounts.len() as u32,
+            total_allocation,
+            total_claimed,
+            total_claimable,
+        })
+    }
+
+        // Function to get linear vesting end time (read-only)
+    pub fn get_linear_vesting_end_time(
+        ctx: Context<GetLinearVestingEndTime>,
+    ) -> Result<MilestoneSchedule> {
+        let config = &ctx.accounts.ico_config;
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp as u64;
+            
+        // Calculate month in seconds
+        let month_in_seconds = config.seconds_per_day
+            .checked_mul(30)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+            
+        // Calculate milestone timestamps
+        let tge_timestamp = config.tge_time;
+            
+        // Define milestones
+        let milestone_8_months = tge_timestamp.checked_add(
+            month_in_seconds
+                .checked_mul(8)
+                .ok_or(CustomError::ArithmeticOverflow)?
+            ).ok_or(CustomError::ArithmeticOverflow)?;
+            
+        let milestone_18_months = tge_timestamp.checked_add(
+            month_in_seconds
+                .checked_mul(18)
+                .ok_or(CustomError::ArithmeticOverflow)?
+            ).ok_or(CustomError::ArithmeticOverflow)?;
+            
+        let milestone_24_months = tge_timestamp.checked_add(
+            month_in_seconds
+                .checked_mul(24)
+                .ok_or(CustomError::ArithmeticOverflow)?
+            ).ok_or(CustomError::ArithmeticOverflow)?;
+            
+        let milestone_36_months = tge_timestamp.checked_add(
+            month_in_seconds
+                .checked_mul(36)
+                .ok_or(CustomError::ArithmeticOverflow)?
+            ).ok_or(CustomError::ArithmeticOverflow)?;
+            
+        let is_completed = current_time >= milestone_36_months;
+            
+        Ok(MilestoneSchedule {
+            tge_time: tge_timestamp,
+            month_8: milestone_8_months,
+            month_18: milestone_18_months,
+            month_24: milestone_24_months,
+            month_36: milestone_36_months,
+            completed: is_completed,
+        })
+    }
+}
+
+// Add this private helper function to handle vesting cliff logic
+fn get_next_cliff_end(
+    vesting_type: u8,
+    last_cliff_end: u64,
+    seconds_per_day: u64,
+    tge_date: u64,
+    current_time: u64,
+) -> Result<u64> {
+    match vesting_type {
+        VESTING_TYPE_IMMEDIATE => {
+            // Days passed since last cliff end
+            let days_passed = if current_time <= last_cliff_end {
+                0
+            } else {
+                current_time
+                    .checked_sub(last_cliff_end)
+                    .ok_or(CustomError::ArithmeticOverflow)?
+                    .checked_div(seconds_per_day)
+                    .ok_or(CustomError::ArithmeticOverflow)?
+            };
+
+            println!("days_passed =============> {:?}", days_passed);
+
+            // Cap at 365 days
+            // To get the next cliff, increased by 1
+            let effective_days = std::cmp::min(days_passed + 1, 365);
+            println!("effective_days =============> {:?}", effective_days);
+
+            let total_seconds = seconds_per_day
+                .checked_mul(effective_days)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            last_cliff_end
+                .checked_add(total_seconds)
+                .ok_or(CustomError::ArithmeticOverflow.into())
+        }
+
+        VESTING_TYPE_LINEAR => {
+            let month_in_seconds = seconds_per_day
+                .checked_mul(30)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            const MILESTONE_PERIODS: [u64; 4] = [8, 10, 6, 12];
+            let mut cumulative_months = 0;
+
+            for &period in &MILESTONE_PERIODS {
+                cumulative_months += period;
+
+                let milestone_seconds = month_in_seconds
+                    .checked_mul(cumulative_months)
+                    .ok_or(CustomError::ArithmeticOverflow)?;
+
+                let milestone_date = tge_date
+                    .checked_add(milestone_seconds)
+                    .ok_or(CustomError::ArithmeticOverflow)?;
+
+                if milestone_date > current_time {
+                    return Ok(milestone_date);
+                }
+            }
+
+            // If passed all milestones
+            let total_months: u64 = MILESTONE_PERIODS.iter().sum();
+            let final_seconds = month_in_seconds
+                .checked_mul(total_months)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            tge_date
+                .checked_add(final_seconds)
+                .ok_or(CustomError::ArithmeticOverflow.into())
+        }
+
+        _ => Err(CustomError::InvalidVestingType.into()),
+    }
+}
+
+// Positions of a wallet are numbered from 0; a new position must take the next free index
+fn register_position(
+    wallet_positions: &mut WalletPositions,
+    wallet: Pubkey,
+    position_index: u32,
+) -> Result<()> {
+    if wallet_positions.address == Pubkey::default() {
+        wallet_positions.address = wallet;
+    }
+
+    require!(
+        position_index <= wallet_positions.position_count,
+        CustomError::InvalidPositionIndex
+    );
+
+    if position_index == wallet_positions.position_count {
+        wallet_positions.position_count = wallet_positions
+            .position_count
+            .checked_add(1)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+    }
+
+    Ok(())
+}
+
+// Load an Investor PDA passed through remaining_accounts and check it belongs to the wallet
+fn load_wallet_position<'info>(
+    account: &'info AccountInfo<'info>,
+    wallet: Pubkey,
+) -> Result<Account<'info, Investor>> {
+    let investor: Account<'info, Investor> = Account::try_from(account)?;
+
+    let (expected, _bump) = Pubkey::find_program_address(
+        &[
+            b"investor is my hero",
+            wallet.as_ref(),
+            &investor.position_index.to_le_bytes(),
+        ],
+        &crate::ID,
+    );
+
+    require_keys_eq!(
+        account.key(),
+        expected,
+        CustomError::InvalidPositionAccount
+    );
+
+    Ok(investor)
+}
+
+// Accounts used to pay reward tokens out of the program
+struct RewardPayer<'a, 'info> {
+    reward_token_mint: &'a mut InterfaceAccount<'info, Mint>,
+    reward_token_treasury: &'a mut InterfaceAccount<'info, TokenAccount>,
+    vault_authority: &'a AccountInfo<'info>,
+    token_program: &'a Interface<'info, TokenInterface>,
+    vault_bump: u8,
+}
+
+impl<'info> RewardPayer<'_, 'info> {
+    // Pay `amount` base units to `to`: transferred from the reward treasury,
+    // or minted by the vault authority in mint mode.
+    // A transfer fee is added on top, so `to` always receives `amount`.
+    fn pay(&mut self, config: &TokenIco, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let seeds = &[b"vault_authority".as_ref(), &[self.vault_bump]];
+        let signer = &[&seeds[..]];
+
+        if config.reward_mode == REWARD_MODE_MINT {
+            check_supply_cap(config.reward_supply_cap, self.reward_token_mint.supply, amount)?;
+
+            let cpi_accounts = MintTo {
+                mint: self.reward_token_mint.to_account_info(),
+                to,
+                authority: self.vault_authority.clone(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer,
+            );
+
+            token_interface::mint_to(cpi_context, amount)?;
+
+            // Keep the cached supply current for the next payment in this instruction
+            self.reward_token_mint.reload()?;
+        } else {
+            let gross_amount = amount
+                .checked_add(transfer_fee_for_net(self.reward_token_mint, amount)?)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+
+            require!(
+                self.reward_token_treasury.amount >= gross_amount,
+                CustomError::InsufficientFunds
+            );
+
+            let cpi_accounts = TransferChecked {
+                from: self.reward_token_treasury.to_account_info(),
+                mint: self.reward_token_mint.to_account_info(),
+                to,
+                authority: self.vault_authority.clone(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer,
+            );
+
+            token_interface::transfer_checked(
+                cpi_context,
+                gross_amount,
+                self.reward_token_mint.decimals,
+            )?;
+
+            // Keep the cached balance current for the next payment in this instruction
+            self.reward_token_treasury.reload()?;
+        }
+
+        Ok(())
+    }
+}
+
+// Wrapped SOL mint of either token program
+fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == anchor_spl::token::spl_token::native_mint::ID || *mint == spl_token_2022::native_mint::ID
+}
+
+// Refuse Token-2022 mints carrying extensions the program does not handle
+// (transfer hooks, permanent delegates, non-transferable, confidential transfers, ...)
+fn check_mint_extensions(mint: &InterfaceAccount<'_, Mint>) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    for extension in mint_state.get_extension_types()? {
+        require!(
+            SUPPORTED_MINT_EXTENSIONS.contains(&extension),
+            CustomError::UnsupportedMintExtension
+        );
+    }
+
+    Ok(())
+}
+
+// Transfer fee to add on top of `net_amount` so that the recipient receives exactly `net_amount`
+fn transfer_fee_for_net(mint: &InterfaceAccount<'_, Mint>, net_amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(fee_config) => Ok(fee_config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, net_amount)
+            .ok_or(CustomError::ArithmeticOverflow)?),
+        Err(_) => Ok(0),
+    }
+}
+
+// Minting `amount` on top of `supply` must stay within `cap` base units (0 = uncapped)
+fn check_supply_cap(cap: u64, supply: u64, amount: u64) -> Result<()> {
+    if cap > 0 {
+        let new_supply = supply
+            .checked_add(amount)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        require!(new_supply <= cap, CustomError::RewardSupplyCapExceeded);
+    }
+    Ok(())
+}
+
+// `bps` basis points of `amount`, rounded down
+fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    Ok(amount
+        .checked_mul(bps as u64)
+        .ok_or(CustomError::ArithmeticOverflow)?
+        .checked_div(BPS_DENOMINATOR)
+        .ok_or(CustomError::ArithmeticOverflow)?)
+}
+
+// Keeper fee in reward token base units for a crank claim of `tokens_amount` base units
+fn keeper_fee_for(config: &TokenIco, tokens_amount: u64) -> Result<u64> {
+    let fee = bps_of(tokens_amount, config.keeper_fee_bps)?;
+
+    if config.keeper_fee_cap > 0 {
+        Ok(std::cmp::min(fee, config.keeper_fee_cap))
+    } else {
+        Ok(fee)
+    }
+}
+
+// Reward tokens bought by `lamports` at the fixed SOL rate
+fn committed_tokens(tokens_per_sol: u64, lamports: u64) -> Result<u64> {
+    Ok(lamports
+        .checked_mul(tokens_per_sol)
+        .ok_or(CustomError::CalculationOverflow)?
+        .checked_div(LAMPORTS_PER_SOL)
+        .ok_or(CustomError::CalculationOverflow)?)
+}
+
+// Allocation of a settled commitment of `amount` lamports: the tokens it receives, the lamports
+// spent on them and the refund. An oversubscribed sale scales every commitment down to its share
+// of the cap, rounding tokens down and the lamports they cost up.
+fn pro_rata_allocation(sale: &ProRataSale, tokens_per_sol: u64, amount: u64) -> Result<(u64, u64, u64)> {
+    let requested_tokens = committed_tokens(tokens_per_sol, amount)?;
+
+    let (tokens, spent) = if sale.total_demand <= sale.token_cap {
+        (requested_tokens, amount)
+    } else {
+        let tokens = (requested_tokens as u128 * sale.token_cap as u128
+            / sale.total_demand as u128) as u64;
+
+        // Lamports that buy `tokens` at the sale rate, rounded up
+        let spent = (tokens as u128 * LAMPORTS_PER_SOL as u128).div_ceil(tokens_per_sol as u128)
+            as u64;
+
+        (tokens, std::cmp::min(spent, amount))
+    };
+
+    let refund = amount
+        .checked_sub(spent)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    Ok((tokens, spent, refund))
+}
+
+// Auction price in lamports per whole reward token, falling linearly over the sale window
+fn auction_price(auction: &Auction, start_time: u64, end_time: u64, current_time: u64) -> u64 {
+    let duration = end_time.saturating_sub(start_time);
+    let elapsed = current_time.saturating_sub(start_time);
+
+    if duration == 0 || elapsed >= duration {
+        return auction.floor_price;
+    }
+
+    let decay = (auction.start_price - auction.floor_price) as u128 * elapsed as u128
+        / duration as u128;
+
+    auction.start_price - decay as u64
+}
+
+// Tokens a bid for `tokens` receives at `price`, capped by the unsold supply, and its cost
+fn auction_fill(auction: &Auction, tokens: u64, price: u64) -> Result<(u64, u64)> {
+    let remaining = auction
+        .supply
+        .checked_sub(auction.tokens_sold)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    let tokens = std::cmp::min(tokens, remaining);
+    let cost = tokens
+        .checked_mul(price)
+        .ok_or(CustomError::CalculationOverflow)?;
+    Ok((tokens, cost))
+}
+
+// Count a filled bid. The price at which supply sells out clears the auction.
+fn record_auction_bid(auction: &mut Auction, tokens: u64, cost: u64, price: u64) -> Result<()> {
+    auction.tokens_sold = auction
+        .tokens_sold
+        .checked_add(tokens)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    auction.total_paid = auction
+        .total_paid
+        .checked_add(cost)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    if auction.tokens_sold == auction.supply {
+        auction.clearing_price = price;
+    }
+    Ok(())
+}
+
+// Fix the clearing price, at the floor if supply did not sell out, and take the proceeds out of
+// the bid balance. What remains of total_paid pays the rebates.
+fn settle_auction_proceeds(auction: &mut Auction) -> Result<u64> {
+    if auction.tokens_sold < auction.supply {
+        auction.clearing_price = auction.floor_price;
+    }
+
+    let proceeds = auction
+        .tokens_sold
+        .checked_mul(auction.clearing_price)
+        .ok_or(CustomError::CalculationOverflow)?;
+
+    auction.total_paid = auction
+        .total_paid
+        .checked_sub(proceeds)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    auction.settled = true;
+    Ok(proceeds)
+}
+
+// What a position paid above the clearing price
+fn auction_rebate(auction: &Auction, bid: &AuctionBid) -> Result<u64> {
+    Ok(bid
+        .paid
+        .checked_sub(
+            bid.tokens
+                .checked_mul(auction.clearing_price)
+                .ok_or(CustomError::CalculationOverflow)?,
+        )
+        .ok_or(CustomError::ArithmeticOverflow)?)
+}
+
+// Tier lists are short, sorted by strictly ascending threshold and within the bonus limit
+fn check_bonus_tiers(tiers: &[BonusTier]) -> Result<()> {
+    require!(
+        tiers.len() <= MAX_BONUS_TIERS
+            && tiers.iter().all(|tier| tier.bonus_bps <= MAX_BONUS_BPS)
+            && tiers.windows(2).all(|pair| pair[0].threshold < pair[1].threshold),
+        CustomError::InvalidBonusTiers
+    );
+    Ok(())
+}
+
+// A buyer cannot refer their own purchase
+fn check_referrer(referrer: Option<Pubkey>, buyer: Pubkey, investor: Pubkey) -> Result<()> {
+    if let Some(referrer) = referrer {
+        require!(
+            referrer != buyer && referrer != investor,
+            CustomError::SelfReferral
+        );
+    }
+    Ok(())
+}
+
+// Credit the referral bonus for a purchase of `tokens` reward tokens, up to the per-referrer cap.
+// Returns the bonus credited.
+fn credit_referral(config: &mut TokenIco, referrer: &mut Referrer, tokens: u64) -> Result<u64> {
+    let mut bonus = bps_of(tokens, config.referral_bonus_bps)?;
+    if config.referral_bonus_cap > 0 {
+        bonus = std::cmp::min(
+            bonus,
+            config.referral_bonus_cap.saturating_sub(referrer.bonus_tokens),
+        );
+    }
+
+    referrer.bonus_tokens = referrer
+        .bonus_tokens
+        .checked_add(bonus)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    referrer.referred_tokens = referrer
+        .referred_tokens
+        .checked_add(tokens)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    referrer.referral_count = referrer
+        .referral_count
+        .checked_add(1)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    // Bonus tokens are owed like any other allocation
+    config.total_allocated = config
+        .total_allocated
+        .checked_add(bonus)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    Ok(bonus)
+}
+
+// System program call needed to bring a PDA into existence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AccountInitStep {
+    Create { lamports: u64 },
+    Transfer { lamports: u64 },
+    Allocate,
+    Assign,
+}
+
+// Steps that leave an address holding `current_lamports` rent-exempt at `rent_minimum`,
+// allocated and assigned. create_account refuses an address that already holds lamports, and
+// anyone can send lamports to a PDA, so like Anchor's `init` such an address is topped up,
+// then allocated and assigned instead.
+fn account_init_steps(current_lamports: u64, rent_minimum: u64) -> Vec<AccountInitStep> {
+    if current_lamports == 0 {
+        return vec![AccountInitStep::Create {
+            lamports: rent_minimum,
+        }];
+    }
+    let mut steps = Vec::with_capacity(3);
+    let top_up = rent_minimum.saturating_sub(current_lamports);
+    if top_up > 0 {
+        steps.push(AccountInitStep::Transfer { lamports: top_up });
+    }
+    steps.push(AccountInitStep::Allocate);
+    steps.push(AccountInitStep::Assign);
+    steps
+}
+
+// Create the PDA `account` with `space` bytes owned by `owner`, signing with its seeds.
+// Returns the lamports the payer put in.
+fn create_pda_account<'info>(
+    payer: AccountInfo<'info>,
+    account: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    seeds: &[&[u8]],
+) -> Result<u64> {
+    let signer = &[seeds];
+    let rent_minimum = Rent::get()?.minimum_balance(space);
+    let mut paid = 0;
+
+    for step in account_init_steps(account.lamports(), rent_minimum) {
+        match step {
+            AccountInitStep::Create { lamports } => {
+                let cpi_accounts = system_program::CreateAccount {
+                    from: payer.clone(),
+                    to: account.clone(),
+                };
+                system_program::create_account(
+                    CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer),
+                    lamports,
+                    space as u64,
+                    owner,
+                )?;
+                paid = lamports;
+            }
+            AccountInitStep::Transfer { lamports } => {
+                let cpi_accounts = system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                };
+                system_program::transfer(
+                    CpiContext::new(system_program.clone(), cpi_accounts),
+                    lamports,
+                )?;
+                paid = lamports;
+            }
+            AccountInitStep::Allocate => {
+                let cpi_accounts = system_program::Allocate {
+                    account_to_allocate: account.clone(),
+                };
+                system_program::allocate(
+                    CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer),
+                    space as u64,
+                )?;
+            }
+            AccountInitStep::Assign => {
+                let cpi_accounts = system_program::Assign {
+                    account_to_assign: account.clone(),
+                };
+                system_program::assign(
+                    CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer),
+                    owner,
+                )?;
+            }
+        }
+    }
+
+    Ok(paid)
+}
+
+// Create a program-owned PDA that Anchor does not initialize, unless it already exists,
+// and write an empty account of type T into it
+fn init_program_account<'info, T: AccountSerialize + Default>(
+    payer: &Signer<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    if account.owner == &crate::ID {
+        return Ok(());
+    }
+
+    create_pda_account(
+        payer.to_account_info(),
+        account.clone(),
+        system_program.to_account_info(),
+        space,
+        &crate::ID,
+        seeds,
+    )?;
+
+    let mut data = account.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data[..];
+    T::default().try_serialize(&mut writer)?;
+
+    Ok(())
+}
+
+// Purchases add to a position only under the schedule it was created with
+fn check_vesting_type(investor: &Investor, vesting_type: u8) -> Result<()> {
+    if investor.allocation > 0 {
+        require!(
+            investor.vesting_type == vesting_type,
+            CustomError::MismatchedVestingType
+        );
+    }
+    Ok(())
+}
+
+// Fold position `from` into `to`, which already holds an allocation
+fn merge_positions(to: &mut Investor, from: &Investor, current_time: u64) -> Result<()> {
+    // Schedules are compatible when they vest the same way from the same TGE
+    require!(
+        to.vesting_type == from.vesting_type && to.revocable == from.revocable,
+        CustomError::IncompatiblePositions
+    );
+    require!(!to.revoked, CustomError::PositionRevoked);
+    require!(
+        !is_block_active(to, current_time),
+        CustomError::InvestorIsBlocked
+    );
+
+    to.allocation = to
+        .allocation
+        .checked_add(from.allocation)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    to.claimed_tokens = to
+        .claimed_tokens
+        .checked_add(from.claimed_tokens)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    to.released_tokens = to
+        .released_tokens
+        .checked_add(from.released_tokens)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    to.cliff_end = std::cmp::min(to.cliff_end, from.cliff_end);
+    to.last_claimed = std::cmp::max(to.last_claimed, from.last_claimed);
+    to.whitelisted_at = std::cmp::min(to.whitelisted_at, from.whitelisted_at);
+
+    Ok(())
+}
+
+// Wallet whose associated token account receives claims of a position
+fn claim_destination_of(investor: &Investor, claimer: Pubkey) -> Pubkey {
+    if investor.position_mint != Pubkey::default() || investor.claim_destination == Pubkey::default() {
+        claimer
+    } else {
+        investor.claim_destination
+    }
+}
+
+// A requested amount may be anything up to the claimable maximum; None claims all of it
+fn claim_amount_of(requested: Option<u64>, claimable_tokens: u64) -> Result<u64> {
+    match requested {
+        Some(requested) => {
+            require!(requested > 0, CustomError::NoTokensAvailableToClaim);
+            require!(
+                requested <= claimable_tokens,
+                CustomError::ClaimAmountExceedsClaimable
+            );
+            Ok(requested)
+        }
+        None => Ok(claimable_tokens),
+    }
+}
+
+// Book a claim of `amount` tokens against a position and the sale totals
+fn record_claim(
+    config: &mut TokenIco,
+    investor: &mut Investor,
+    amount: u64,
+    current_time: u64,
+) -> Result<()> {
+    // Update investor claimed tokens
+    investor.claimed_tokens = investor
+        .claimed_tokens
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    // Update released tokens
+    investor.released_tokens = investor
+        .released_tokens
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    // Update last claimed timestamp
+    investor.last_claimed = current_time;
+    investor.cliff_end = get_next_cliff_end(
+        investor.vesting_type,
+        investor.cliff_end,
+        config.seconds_per_day,
+        config.tge_time,
+        current_time,
+    )?;
+
+    // Update total claimed in config
+    config.total_claimed = config
+        .total_claimed
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    config.claim_count = config
+        .claim_count
+        .checked_add(1)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    Ok(())
+}
+
+// A block applies until it is lifted, or until blocked_until when an expiry was set
+fn is_block_active(investor: &Investor, current_time: u64) -> bool {
+    investor.blocked && (investor.blocked_until == 0 || current_time < investor.blocked_until)
+}
+
+// Claimable tokens for a stored position. A revoked grant is frozen at what had
+// vested when it was revoked, so all of its remaining allocation is claimable.
+fn calculate_investor_claimable(
+    investor: &Investor,
+    config: &TokenIco,
+    current_time: u64,
+) -> Result<(u64, u64)> {
+    if investor.revoked {
+        let claimable = investor.allocation.saturating_sub(investor.claimed_tokens);
+        return Ok((claimable, investor.allocation));
+    }
+
+    calculate_claimable_tokens(
+        investor.vesting_type,
+        investor.allocation,
+        investor.claimed_tokens,
+        config.tge_time,
+        config.seconds_per_day,
+        current_time,
+    )
+}
+
+// Add this private helper function to handle whitelisting logic
+fn process_investor_whitelist(
+    investor: &mut Investor,
+    buyer_key: Pubkey,
+    position_index: u32,
+    config: &mut TokenIco,
+    wallet_positions: &mut WalletPositions,
+    vesting_type: u8,
+    tokens_to_allocate: u64,
+) -> Result<bool> {
+    let is_first_purchase = investor.allocation == 0;
+
+    // Purchases never go into revocable grants
+    require!(!investor.revoked, CustomError::PositionRevoked);
+    require!(!investor.revocable, CustomError::MismatchedRevocableFlag);
+
+    // If this is investor's first purchase, initialize all whitelist fields
+    if is_first_purchase {
+        investor.address = buyer_key;
+        investor.position_index = position_index;
+        investor.vesting_type = vesting_type;
+        investor.released_tokens = 0;
+        investor.cliff_end = config.tge_time;
+        investor.last_claimed = 0;
+        investor.claimed_tokens = 0;
+        investor.blocked = false;
+        investor.block_reason = BLOCK_REASON_UNSPECIFIED;
+        investor.blocked_until = 0;
+        investor.revocable = false;
+        investor.claim_destination = Pubkey::default();
+        investor.whitelisted_at = Clock::get()?.unix_timestamp as u64;
+
+        record_new_position(config, wallet_positions, vesting_type)?;
+
+        // Log initial whitelist
+        msg!("Investor automatically whitelisted on first purchase!");
+        msg!("Investor Address: {}", investor.address);
+        msg!("Vesting Type: {:?}", investor.vesting_type);
+        msg!("Whitelisted At: {}", investor.whitelisted_at);
+
+        emit!(InvestorWhitelisted {
+            investor: investor.address,
+            position_index,
+            allocation: tokens_to_allocate,
+            vesting_type: investor.vesting_type,
+            cliff_end: investor.cliff_end,
+        });
+    }
+
+    Ok(is_first_purchase)
+}
+
+// A position received its first allocation; the wallet's first one counts the wallet
+fn record_new_position(
+    config: &mut TokenIco,
+    wallet_positions: &mut WalletPositions,
+    vesting_type: u8,
+) -> Result<()> {
+    let positions = if vesting_type == VESTING_TYPE_LINEAR {
+        &mut config.linear_positions
+    } else {
+        &mut config.immediate_positions
+    };
+    *positions = positions
+        .checked_add(1)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    wallet_positions.open_positions = wallet_positions
+        .open_positions
+        .checked_add(1)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    if wallet_positions.open_positions == 1 {
+        config.wallet_count = config
+            .wallet_count
+            .checked_add(1)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+    }
+    Ok(())
+}
+
+// A position no longer holds an allocation; the wallet's last one uncounts the wallet
+fn forget_position(config: &mut TokenIco, wallet_positions: &mut WalletPositions, vesting_type: u8) {
+    let positions = if vesting_type == VESTING_TYPE_LINEAR {
+        &mut config.linear_positions
+    } else {
+        &mut config.immediate_positions
+    };
+    *positions = positions.saturating_sub(1);
+
+    if wallet_positions.open_positions > 0 {
+        wallet_positions.open_positions -= 1;
+        if wallet_positions.open_positions == 0 {
+            config.wallet_count = config.wallet_count.saturating_sub(1);
+        }
+    }
+}
+
+// Reward tokens allocated through a purchase and the lamports it raised
+fn record_purchase(config: &mut TokenIco, tokens: u64, lamports: u64) -> Result<()> {
+    config.total_user_allocated = config
+        .total_user_allocated
+        .checked_add(tokens)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    config.sol_raised = config
+        .sol_raised
+        .checked_add(lamports)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+// Unlock points of a schedule under the sale's TGE, computed by the shared vesting-math crate
+fn timeline_points(
+    vesting_type: u8,
+    total_allocation: u64,
+    config: &TokenIco,
+) -> Result<Vec<TimelinePoint>> {
+    Ok(vesting_math::vesting_timeline(
+        vesting_type,
+        total_allocation,
+        config.tge_time,
+        config.seconds_per_day,
+    )
+    .map_err(CustomError::from)?
+    .into_iter()
+    .map(|point| TimelinePoint {
+        timestamp: point.timestamp,
+        cumulative_vested: point.cumulative_vested,
+        unlocked: point.unlocked,
+    })
+    .collect())
+}
+
+// Claimable tokens of a schedule, computed by the shared vesting-math crate
+fn calculate_claimable_tokens(
+    vesting_type: u8,
+    total_allocation: u64,
+    already_claimed: u64,
+    tge_time: u64,
+    seconds_per_day: u64,
+    current_time: u64,
+) -> Result<(u64, u64)> {
+    let (claimable, total_releasable) = vesting_math::calculate_claimable_tokens(
+        vesting_type,
+        total_allocation,
+        already_claimed,
+        tge_time,
+        seconds_per_day,
+        current_time,
+    )
+    .map_err(CustomError::from)?;
+
+    Ok((claimable, total_releasable))
+}
+
+// =============================== Accounts ======================================
+
+#[derive(Accounts)]
+pub struct InitializeIco<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<TokenIco>(),
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: This is the treasury account where SOL is stored. Owner check is done implicitly by transfer.
+    #[account(mut)]
+    pub sol_treasury: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = reward_token_treasury.mint == reward_token_mint.key(),
+        constraint = reward_token_treasury.owner == vault_authority.key(),
+    )]
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct InvestorEntry<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub investor_details: Account<'info, Investor>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<WalletPositions>(),
+        seeds = [b"wallet positions", investor_address.as_ref()],
+        bump
+    )]
+    pub wallet_positions: Account<'info, WalletPositions>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WhitelistInvestorsBatch<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// For BuyTokensWithSol
+#[derive(Accounts)]
+#[instruction(
+    investor_address: Pubkey,
+    position_index: u32,
+    amount: u64,
+    vesting_type: u8,
+    bonus_position_index: Option<u32>
+)]
+pub struct BuyTokensWithSol<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        init_if_needed,  // This allows initialization if the account doesn't exist
+        payer = buyer,   // Buyer pays for account creation
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub investor_details: Account<'info, Investor>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<WalletPositions>(),
+        seeds = [b"wallet positions", investor_address.as_ref()],
+        bump
+    )]
+    pub wallet_positions: Account<'info, WalletPositions>,
+
+    /// CHECK: Validated against config.sol_treasury
+    #[account(mut, address = ico_config.sol_treasury)]
+    pub sol_treasury: UncheckedAccount<'info>,
+
+    /// Purchase bonus tiers; purchases made without them get no bonus
+    #[account(
+        seeds = [b"bonus_tiers"],
+        bump
+    )]
+    pub bonus_tiers: Option<Account<'info, BonusTiers>>,
+
+    /// Position receiving the bonus when the tiers vest it on its own schedule
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+        seeds = [
+            b"investor is my hero",
+            investor_address.as_ref(),
+            &bonus_position_index.unwrap_or_default().to_le_bytes()
+        ],
+        bump
+    )]
+    pub bonus_position: Option<Account<'info, Investor>>,
+
+    /// Optional referrer credited with this purchase
+    #[account(
+        mut,
+        seeds = [b"referrer", referrer.address.as_ref()],
+        bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+
+    /// CHECK: Referrer's wallet, receives its share of proceeds; checked in the handler
+    #[account(mut)]
+    pub referrer_wallet: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// For BuyWithToken
+#[derive(Accounts)]
+#[instruction(
+    investor_address: Pubkey,
+    position_index: u32,
+    amount: u64,
+    vesting_type: u8,
+    bonus_position_index: Option<u32>
+)]
+pub struct BuyWithToken<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        init_if_needed,  // This allows initialization if the account doesn't exist
+        payer = buyer,   // Buyer pays for account creation
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub investor_details: Account<'info, Investor>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<WalletPositions>(),
+        seeds = [b"wallet positions", investor_address.as_ref()],
+        bump
+    )]
+    pub wallet_positions: Account<'info, WalletPositions>,
+
+    #[account(
+        mut,
+        seeds = [b"payment_mint", mint.key().as_ref()],
+        bump
+    )]
+    pub payment_mint: Account<'info, PaymentMint>,
+
+    #[account(address = payment_mint.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = payment_mint.treasury
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == buyer.key(),
+        constraint = buyer_token_account.mint == mint.key()
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Purchase bonus tiers; purchases made without them get no bonus
+    #[account(
+        seeds = [b"bonus_tiers"],
+        bump
+    )]
+    pub bonus_tiers: Option<Account<'info, BonusTiers>>,
+
+    /// Position receiving the bonus when the tiers vest it on its own schedule
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+        seeds = [
+            b"investor is my hero",
+            investor_address.as_ref(),
+            &bonus_position_index.unwrap_or_default().to_le_bytes()
+        ],
+        bump
+    )]
+    pub bonus_position: Option<Account<'info, Investor>>,
+
+    /// CHECK: Temporary wSOL account, created and closed within the instruction. Only needed to unwrap.
+    #[account(
+        mut,
+        seeds = [b"wsol_escrow", buyer.key().as_ref()],
+        bump
+    )]
+    pub wsol_escrow: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: PDA owning the wSOL escrow. Only needed to unwrap.
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Validated against config.sol_treasury. Only needed to unwrap.
+    #[account(mut, address = ico_config.sol_treasury)]
+    pub sol_treasury: Option<UncheckedAccount<'info>>,
+
+    /// Optional referrer credited with this purchase
+    #[account(
+        mut,
+        seeds = [b"referrer", referrer.address.as_ref()],
+        bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+
+    /// Referrer's token account for its share of proceeds; checked in the handler
+    #[account(mut)]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureAuction<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Auction>(),
+        seeds = [b"auction"],
+        bump
+    )]
+    pub auction: Account<'info, Auction>,
+
+    pub system_program: Program<'info, System>,
+}
 
-            for &period in &MILESTONE_PERIODS {
-                cumulative_months += period;
+#[derive(Accounts)]
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct BidAuction<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
 
-                let milestone_seconds = month_in_seconds
-                    .checked_mul(cumulative_months)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
 
-                let milestone_date = tge_date
-                    .checked_add(milestone_seconds)
-                    .ok_or(CustomError::ArithmeticOverflow)?;
+    /// Holds bid payments until settlement
+    #[account(
+        mut,
+        seeds = [b"auction"],
+        bump
+    )]
+    pub auction: Account<'info, Auction>,
 
-                if milestone_date > current_time {
-                    return Ok(milestone_date);
-                }
-            }
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<AuctionBid>(),
+        seeds = [b"auction_bid", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub auction_bid: Account<'info, AuctionBid>,
 
-            // If passed all milestones
-            let total_months: u64 = MILESTONE_PERIODS.iter().sum();
-            let final_seconds = month_in_seconds
-                .checked_mul(total_months)
-                .ok_or(CustomError::ArithmeticOverflow)?;
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub investor_details: Account<'info, Investor>,
 
-            tge_date
-                .checked_add(final_seconds)
-                .ok_or(CustomError::ArithmeticOverflow.into())
-        }
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<WalletPositions>(),
+        seeds = [b"wallet positions", investor_address.as_ref()],
+        bump
+    )]
+    pub wallet_positions: Account<'info, WalletPositions>,
 
-        _ => Err(CustomError::InvalidVestingType.into()),
-    }
+    pub system_program: Program<'info, System>,
 }
 
-// Add this private helper function to handle whitelisting logic
-fn process_investor_whitelist(
-    investor: &mut Account<'_, Investor>,
-    buyer_key: Pubkey,
-    config_tge_time: u64,
-    vesting_type: u8,
-    tokens_to_allocate: u64,
-) -> Result<bool> {
-    let is_first_purchase = investor.allocation == 0;
+#[derive(Accounts)]
+pub struct SettleAuction<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
 
-    // If this is investor's first purchase, initialize all whitelist fields
-    if is_first_purchase {
-        investor.address = buyer_key;
-        investor.vesting_type = vesting_type;
-        investor.released_tokens = 0;
-        investor.cliff_end = config_tge_time;
-        investor.last_claimed = 0;
-        investor.claimed_tokens = 0;
-        investor.blocked = false;
-        investor.whitelisted_at = Clock::get()?.unix_timestamp as u64;
+    #[account(
+        mut,
+        seeds = [b"auction"],
+        bump
+    )]
+    pub auction: Account<'info, Auction>,
 
-        // Log initial whitelist
-        msg!("Investor automatically whitelisted on first purchase!");
-        msg!("Investor Address: {}", investor.address);
-        msg!("Vesting Type: {:?}", investor.vesting_type);
-        msg!("Whitelisted At: {}", investor.whitelisted_at);
+    /// CHECK: Validated against config.sol_treasury
+    #[account(mut, address = ico_config.sol_treasury)]
+    pub sol_treasury: UncheckedAccount<'info>,
+}
 
-        emit!(InvestorWhitelisted {
-            investor: investor.address,
-            allocation: tokens_to_allocate,
-            vesting_type: investor.vesting_type,
-            cliff_end: investor.cliff_end,
-        });
-    }
+#[derive(Accounts)]
+#[instruction(position_index: u32)]
+pub struct ClaimAuctionRebate<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
 
-    Ok(is_first_purchase)
+    #[account(
+        mut,
+        seeds = [b"auction"],
+        bump
+    )]
+    pub auction: Account<'info, Auction>,
+
+    #[account(
+        mut,
+        seeds = [b"auction_bid", wallet.key().as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub auction_bid: Account<'info, AuctionBid>,
 }
 
-// Helper function to calculate claimable tokens based on vesting type
-fn calculate_claimable_tokens(
-    vesting_type: u8,
-    total_allocation: u64,
-    already_claimed: u64,
-    tge_time: u64,
-    seconds_per_day: u64,
-    current_time: u64,
-) -> Result<(u64, u64)> {
-    // Calculate the total releasable tokens based on vesting type and current time
-    let total_releasable = match vesting_type {
-        // Immediate vesting - 10% at TGE, rest linearly over 365 days
-        VESTING_TYPE_IMMEDIATE => {
-            // 10% at TGE initially
+#[derive(Accounts)]
+pub struct ConfigureProRataSale<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
 
-            let mut initial_release = total_allocation
-                .checked_mul(10)
-                .ok_or(CustomError::ArithmeticOverflow)?
-                .checked_div(100)
-                .ok_or(CustomError::ArithmeticOverflow)?;
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
 
-            // Remaining 90% for daily vesting
-            let mut remaining_allocation = total_allocation
-                .checked_sub(initial_release)
-                .ok_or(CustomError::ArithmeticOverflow)?;
+    #[account(
+        init,
+        payer = authority,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<ProRataSale>(),
+        seeds = [b"pro_rata_sale"],
+        bump
+    )]
+    pub pro_rata_sale: Account<'info, ProRataSale>,
 
-            // Daily vesting amount
-            let daily_release = remaining_allocation
-                .checked_div(365)
-                .ok_or(CustomError::ArithmeticOverflow)?;
+    pub system_program: Program<'info, System>,
+}
 
-            // Days passed since TGE
-            let mut days_passed = if current_time <= tge_time {
-                0
-            } else {
-                current_time
-                    .checked_sub(tge_time)
-                    .ok_or(CustomError::ArithmeticOverflow)?
-                    .checked_div(seconds_per_day)
-                    .ok_or(CustomError::ArithmeticOverflow)?
-            };
+#[derive(Accounts)]
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct CommitToSale<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
 
-            msg!("days_passed =============> {:?}", days_passed);
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
 
-            // Cap at 365 days
-            let effective_days = std::cmp::min(days_passed, 365);
-            msg!("effective_days =============> {:?}", effective_days);
+    /// Holds commitments until settlement
+    #[account(
+        mut,
+        seeds = [b"pro_rata_sale"],
+        bump
+    )]
+    pub pro_rata_sale: Account<'info, ProRataSale>,
 
-            if effective_days == 365 {
-                total_allocation
-            } else {
-            // Calculate vested tokens
-            let vested_from_daily = daily_release
-                .checked_mul(effective_days)
-                .ok_or(CustomError::ArithmeticOverflow)?;
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Commitment>(),
+        seeds = [b"commitment", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub commitment: Account<'info, Commitment>,
 
-            msg!("vested_from_daily =============> {:?}", vested_from_daily);
+    pub system_program: Program<'info, System>,
+}
 
-            // Total releasable: initial + vested from daily
-            initial_release
-                .checked_add(vested_from_daily)
-                .ok_or(CustomError::ArithmeticOverflow)?
-            }
-        }
+#[derive(Accounts)]
+pub struct FinalizeSale<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
 
-        // Linear vesting with milestones
-        VESTING_TYPE_LINEAR => {
-            // Initial 5% at TGE
-            let mut initial_release = total_allocation
-                .checked_mul(5)
-                .ok_or(CustomError::ArithmeticOverflow)?
-                .checked_div(100)
-                .ok_or(CustomError::ArithmeticOverflow)?;
+    #[account(
+        mut,
+        seeds = [b"pro_rata_sale"],
+        bump
+    )]
+    pub pro_rata_sale: Account<'info, ProRataSale>,
+}
 
-            // Calculate seconds in months for different milestones
-            let mut month_in_seconds = seconds_per_day
-                .checked_mul(30)
-                .ok_or(CustomError::ArithmeticOverflow)?;
+#[derive(Accounts)]
+#[instruction(position_index: u32)]
+pub struct Settle<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
 
-            // Define milestones in seconds from TGE
-            let mut milestone_8_months = month_in_seconds
-                .checked_mul(8)
-                .ok_or(CustomError::ArithmeticOverflow)?;
-            let mut milestone_18_months = month_in_seconds
-                .checked_mul(18)
-                .ok_or(CustomError::ArithmeticOverflow)?;
-            let mut milestone_24_months = month_in_seconds
-                .checked_mul(24)
-                .ok_or(CustomError::ArithmeticOverflow)?;
-            let mut milestone_36_months = month_in_seconds
-                .checked_mul(36)
-                .ok_or(CustomError::ArithmeticOverflow)?;
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
 
-            // Calculate time elapsed since TGE
-            let mut time_elapsed = if current_time <= tge_time {
-                0
-            } else {
-                current_time
-                    .checked_sub(tge_time)
-                    .ok_or(CustomError::ArithmeticOverflow)?
-            };
+    #[account(
+        mut,
+        seeds = [b"pro_rata_sale"],
+        bump
+    )]
+    pub pro_rata_sale: Account<'info, ProRataSale>,
 
-            // Calculate vested amount based on time passed
-            let mut vested_amount = initial_release; // Start with TGE release (5%)
-
-            // TGE + 8 months: 10%
-            if time_elapsed >= milestone_8_months {
-                vested_amount = vested_amount
-                    .checked_add(
-                        total_allocation
-                            .checked_mul(10)
-                            .ok_or(CustomError::ArithmeticOverflow)?
-                            .checked_div(100)
-                            .ok_or(CustomError::ArithmeticOverflow)?,
-                    ).ok_or(CustomError::ArithmeticOverflow)?;
-            }
+    // Closed once settled, returning its rent to the wallet
+    #[account(
+        mut,
+        close = wallet,
+        seeds = [b"commitment", wallet.key().as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub commitment: Account<'info, Commitment>,
 
-            // TGE + 18 months: 20%
-            if time_elapsed >= milestone_18_months {
-                vested_amount = vested_amount
-                    .checked_add(
-                        total_allocation
-                            .checked_mul(20)
-                            .ok_or(CustomError::ArithmeticOverflow)?
-                            .checked_div(100)
-                            .ok_or(CustomError::ArithmeticOverflow)?,
-                    ).ok_or(CustomError::ArithmeticOverflow)?;
-            }
+    /// CHECK: Investor PDA, created by the handler only if the commitment is allocated tokens
+    #[account(
+        mut,
+        seeds = [b"investor is my hero", wallet.key().as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub investor_details: UncheckedAccount<'info>,
 
-            // TGE + 24 months: 20%
-            if time_elapsed >= milestone_24_months {
-                vested_amount = vested_amount
-                    .checked_add(
-                        total_allocation
-                            .checked_mul(20)
-                            .ok_or(CustomError::ArithmeticOverflow)?
-                            .checked_div(100)
-                            .ok_or(CustomError::ArithmeticOverflow)?,
-                    ).ok_or(CustomError::ArithmeticOverflow)?;
-            }
+    /// CHECK: WalletPositions PDA, created by the handler like investor_details
+    #[account(
+        mut,
+        seeds = [b"wallet positions", wallet.key().as_ref()],
+        bump
+    )]
+    pub wallet_positions: UncheckedAccount<'info>,
 
-            // TGE + 36 months: 45% (remaining all)
-            if time_elapsed >= milestone_36_months {
-                vested_amount = total_allocation;
-            }
+    /// CHECK: Validated against config.sol_treasury
+    #[account(mut, address = ico_config.sol_treasury)]
+    pub sol_treasury: UncheckedAccount<'info>,
 
-            vested_amount
-        }
-        _ => return Err(CustomError::InvalidVestingType.into()),
-    };
+    pub system_program: Program<'info, System>,
+}
 
-    // Calculate claimable tokens (total releasable minus already claimed)
-    msg!("already_claimed ===========> {}", already_claimed);
-    msg!("total_releasable ===========> {}", total_releasable);
-    let claimable = total_releasable.saturating_sub(already_claimed);
-    msg!("claimable ===========> {}", claimable);
+#[derive(Accounts)]
+pub struct SetBonusTiers<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
 
-    Ok((claimable, total_releasable))
-}
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
 
-// =============================== Accounts ======================================
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = BONUS_TIERS_SPACE,
+        seeds = [b"bonus_tiers"],
+        bump
+    )]
+    pub bonus_tiers: Account<'info, BonusTiers>,
+
+    pub system_program: Program<'info, System>,
+}
 
 #[derive(Accounts)]
-pub struct InitializeIco<'info> {
+pub struct RegisterReferrer<'info> {
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub referrer: Signer<'info>,
 
     #[account(
         init,
-        payer = authority,
-        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<TokenIco>(),
-        seeds = [b"config"],
+        payer = referrer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Referrer>(),
+        seeds = [b"referrer", referrer.key().as_ref()],
         bump
     )]
-    pub ico_config: Account<'info, TokenIco>,
+    pub referrer_account: Account<'info, Referrer>,
 
-    pub reward_token_mint: Account<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
 
-    pub usdc_mint: Account<'info, Mint>,
-    pub usdt_mint: Account<'info, Mint>,
-    
-    /// CHECK: This is the treasury account where SOL is stored. Owner check is done implicitly by transfer.
+#[derive(Accounts)]
+pub struct ClaimReferralBonus<'info> {
     #[account(mut)]
-    pub sol_treasury: UncheckedAccount<'info>,
+    pub referrer: Signer<'info>,
 
     #[account(
         mut,
-        constraint = usdc_treasury.mint == usdc_mint.key(),
-        constraint = usdc_treasury.owner == authority.key(),
+        seeds = [b"referrer", referrer.key().as_ref()],
+        bump
     )]
-    pub usdc_treasury: Account<'info, TokenAccount>,
+    pub referrer_account: Account<'info, Referrer>,
 
     #[account(
         mut,
-        constraint = usdt_treasury.mint == usdt_mint.key(),
-        constraint = usdt_treasury.owner == authority.key(),
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
     )]
-    pub usdt_treasury: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
 
     #[account(
         mut,
-        constraint = reward_token_treasury.mint == reward_token_mint.key(),
-        constraint = reward_token_treasury.owner == vault_authority.key(),
+        constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
+        constraint = reward_token_treasury.owner == vault_authority.key()
     )]
-    pub reward_token_treasury: Account<'info, TokenAccount>,
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    /// Written to when rewards are minted on claim
+    #[account(mut, address = ico_config.reward_token_mint)]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
-        seeds = [b"vault_authority"],
-        bump
+        init_if_needed,
+        payer = referrer,
+        associated_token::mint = reward_token_mint,
+        associated_token::authority = referrer,
+        associated_token::token_program = token_program
     )]
-    pub vault_authority: AccountInfo<'info>,
+    pub referrer_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-#[instruction(investor_address:Pubkey)]
-pub struct InvestorEntry<'info> {
+pub struct AddPaymentMint<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
 
     #[account(
-        mut,
         seeds = [b"config"],
         bump
     )]
     pub ico_config: Account<'info, TokenIco>,
 
     #[account(
-        init_if_needed,
+        init,
         payer = authority,
-        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
-        seeds = [b"investor is my hero", investor_address.key().as_ref()],
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<PaymentMint>(),
+        seeds = [b"payment_mint", mint.key().as_ref()],
         bump
     )]
-    pub investor_details: Account<'info, Investor>,
+    pub payment_mint: Account<'info, PaymentMint>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        constraint = treasury.mint == mint.key(),
+        constraint = treasury.owner == authority.key(),
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
 
     pub system_program: Program<'info, System>,
 }
 
-// For BuyTokensWithSol
 #[derive(Accounts)]
-#[instruction(investor_address: Pubkey, amount: u64, vesting_type: u8)]
-pub struct BuyTokensWithSol<'info> {
-    #[account(mut)]
-    pub buyer: Signer<'info>,
+pub struct UpdatePaymentMint<'info> {
+    pub authority: Signer<'info>,
 
     #[account(
-        mut,
         seeds = [b"config"],
         bump
     )]
     pub ico_config: Account<'info, TokenIco>,
 
     #[account(
-        init_if_needed,  // This allows initialization if the account doesn't exist
-        payer = buyer,   // Buyer pays for account creation
-        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
-        seeds = [b"investor is my hero", investor_address.as_ref()],
+        mut,
+        seeds = [b"payment_mint", payment_mint.mint.as_ref()],
         bump
     )]
-    pub investor_details: Account<'info, Investor>,
-
-    /// CHECK: Validated against config.sol_treasury
-    #[account(mut, address = ico_config.sol_treasury)]
-    pub sol_treasury: UncheckedAccount<'info>,
+    pub payment_mint: Account<'info, PaymentMint>,
 
-    pub system_program: Program<'info, System>,
+    /// Treasury to use from now on; pass the current one to keep it
+    #[account(
+        constraint = treasury.mint == payment_mint.mint,
+        constraint = treasury.owner == authority.key(),
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
 }
 
-// For BuyTokenWithUsdc
 #[derive(Accounts)]
-#[instruction(investor_address: Pubkey, amount: u64, vesting_type: u8)]
-pub struct BuyTokenWithUsdc<'info> {
-    #[account(mut)]
-    pub buyer: Signer<'info>,
-
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct ClaimTokens<'info> {
     #[account(
         mut,
         seeds = [b"config"],
@@ -1895,41 +5285,62 @@ pub struct BuyTokenWithUsdc<'info> {
     pub ico_config: Account<'info, TokenIco>,
 
     #[account(
-        init_if_needed,  // This allows initialization if the account doesn't exist
-        payer = buyer,   // Buyer pays for account creation
-        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
-        seeds = [b"investor is my hero", investor_address.as_ref()],
-        bump
+        mut,
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump,
+        // constraint = !investor_details.blocked @ CustomError::InvestorIsBlocked,
     )]
     pub investor_details: Account<'info, Investor>,
 
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
     #[account(
         mut,
-        constraint = usdc_treasury.mint == usdc_mint.key(),
-        address = ico_config.usdc_treasury
+        constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
+        constraint = reward_token_treasury.owner == vault_authority.key()
     )]
-    pub usdc_treasury: Account<'info, TokenAccount>,
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
 
+    /// Authority that can sign for token transfers from treasury
     #[account(
-        mut, 
-        constraint = buyer_token_account.owner == buyer.key(), 
-        constraint = buyer_token_account.mint == usdc_mint.key()
+        // address = ico_config.authority @ CustomError::UnauthorizedAccess
+        mut
     )]
-    pub buyer_token_account: Account<'info, TokenAccount>,
+    pub investor: Signer<'info>,
 
-    #[account(address = ico_config.usdc_mint)]
-    pub usdc_mint: Account<'info, Mint>,
+    /// Written to when rewards are minted on claim
+    #[account(mut, address = ico_config.reward_token_mint)]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Validated in the handler against the position's claim destination
+    pub claim_recipient: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = investor,
+        associated_token::mint = reward_token_mint,
+        associated_token::authority = claim_recipient,
+        associated_token::token_program = token_program
+    )]
+    pub investor_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    /// Position token account of the signer, required for tokenized positions
+    pub position_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(investor_address: Pubkey, amount: u64, vesting_type: u8)]
-pub struct BuyTokenWithUsdt<'info> {
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct CrankClaim<'info> {
     #[account(mut)]
-    pub buyer: Signer<'info>,
+    pub keeper: Signer<'info>,
 
     #[account(
         mut,
@@ -1939,44 +5350,59 @@ pub struct BuyTokenWithUsdt<'info> {
     pub ico_config: Account<'info, TokenIco>,
 
     #[account(
-        init_if_needed,  // This allows initialization if the account doesn't exist
-        payer = buyer,   // Buyer pays for account creation
-        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
-        seeds = [b"investor is my hero", investor_address.as_ref()],
+        mut,
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
         bump
     )]
     pub investor_details: Account<'info, Investor>,
 
     #[account(
-        mut,
-        constraint = usdt_treasury.mint == usdt_mint.key(),
-        address = ico_config.usdt_treasury
+        seeds = [b"vault_authority"],
+        bump
     )]
-    pub usdt_treasury: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
 
     #[account(
-        mut, 
-        constraint = buyer_token_account.owner == buyer.key(), 
-        constraint = buyer_token_account.mint == usdt_mint.key()
+        mut,
+        constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
+        constraint = reward_token_treasury.owner == vault_authority.key()
     )]
-    pub buyer_token_account: Account<'info, TokenAccount>,
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(address = ico_config.usdt_mint)]
-    pub usdt_mint: Account<'info, Mint>,
+    /// Written to when rewards are minted on claim
+    #[account(mut, address = ico_config.reward_token_mint)]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
 
-    pub token_program: Program<'info, Token>,
+    /// CHECK: Must be the investor's own wallet
+    #[account(address = investor_details.address @ CustomError::InvalidAddress)]
+    pub investor_wallet: UncheckedAccount<'info>,
 
-    pub system_program: Program<'info, System>,
-}
+    #[account(
+        init_if_needed,
+        payer = keeper,
+        associated_token::mint = reward_token_mint,
+        associated_token::authority = investor_wallet,
+        associated_token::token_program = token_program
+    )]
+    pub investor_token_account: InterfaceAccount<'info, TokenAccount>,
 
-#[derive(Accounts)]
-pub struct Calculate<'info> {
+    /// Receives the keeper fee; without it no fee is taken
+    #[account(
+        mut,
+        constraint = keeper_token_account.mint == ico_config.reward_token_mint,
+        constraint = keeper_token_account.owner == keeper.key()
+    )]
+    pub keeper_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(investor_address: Pubkey)]
-pub struct ClaimTokens<'info> {
+pub struct CrankClaimBatch<'info> {
+    pub keeper: Signer<'info>,
+
     #[account(
         mut,
         seeds = [b"config"],
@@ -1984,13 +5410,42 @@ pub struct ClaimTokens<'info> {
     )]
     pub ico_config: Account<'info, TokenIco>,
 
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
     #[account(
         mut,
-        seeds = [b"investor is my hero", investor_address.as_ref()],
-        bump,
-        // constraint = !investor_details.blocked @ CustomError::InvestorIsBlocked,
+        constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
+        constraint = reward_token_treasury.owner == vault_authority.key()
     )]
-    pub investor_details: Account<'info, Investor>,
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    /// Written to when rewards are minted on claim
+    #[account(mut, address = ico_config.reward_token_mint)]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives the keeper fee; without it no fee is taken
+    #[account(
+        mut,
+        constraint = keeper_token_account.mint == ico_config.reward_token_mint,
+        constraint = keeper_token_account.owner == keeper.key()
+    )]
+    pub keeper_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAllPositions<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
 
     #[account(
         seeds = [b"vault_authority"],
@@ -2003,23 +5458,29 @@ pub struct ClaimTokens<'info> {
         constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
         constraint = reward_token_treasury.owner == vault_authority.key()
     )]
-    pub reward_token_treasury: Account<'info, TokenAccount>,
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
 
-    /// Authority that can sign for token transfers from treasury
-    #[account(
-        // address = ico_config.authority @ CustomError::UnauthorizedAccess
-        mut
-    )]
+    #[account(mut)]
     pub investor: Signer<'info>,
 
+    /// Written to when rewards are minted on claim
+    #[account(mut, address = ico_config.reward_token_mint)]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Only positions whose claim destination is this wallet are claimed
+    pub claim_recipient: UncheckedAccount<'info>,
+
     #[account(
-        mut,
-        constraint = investor_token_account.mint == ico_config.reward_token_mint,
-        constraint = investor_token_account.owner == investor.key()
+        init_if_needed,
+        payer = investor,
+        associated_token::mint = reward_token_mint,
+        associated_token::authority = claim_recipient,
+        associated_token::token_program = token_program
     )]
-    pub investor_token_account: Account<'info, TokenAccount>,
+    pub investor_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
@@ -2038,6 +5499,7 @@ pub struct SetIcoConfig<'info> {
 }
 
 #[derive(Accounts)]
+#[instruction(token_type: u8, token: Pubkey)]
 pub struct SetMintAddress<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
@@ -2050,8 +5512,16 @@ pub struct SetMintAddress<'info> {
     )]
     pub ico_config: Account<'info, TokenIco>,
 
-    /// CHECK: Used only to fetch decimals from the mint; verified by CPI
-    pub mint_account: Account<'info, Mint>,
+    // The new reward mint itself, so its decimals and extensions are the ones stored
+    #[account(address = token)]
+    pub mint_account: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA that must hold the new mint's authority in mint mode
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -2090,19 +5560,26 @@ pub struct UpdateTreasuryWallet<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(investor_address:Pubkey)]
+#[instruction(investor_address: Pubkey, position_index: u32)]
 pub struct RemoveInvestor<'info> {
     #[account(mut)]
     pub ico_config: Account<'info, TokenIco>,
 
     #[account(
         mut, 
-        seeds = [b"investor is my hero", investor_address.key().as_ref()],
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
         bump,
         close = authority
     )]
     pub investor_details: Account<'info, Investor>,
 
+    #[account(
+        mut,
+        seeds = [b"wallet positions", investor_address.as_ref()],
+        bump
+    )]
+    pub wallet_positions: Account<'info, WalletPositions>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
 
@@ -2154,10 +5631,126 @@ pub struct PrizeTokensTransfer<'info> {
 
     #[account(
         mut,
-        constraint = recipient_token_account.mint == ico_config.reward_token_mint,
-        constraint = recipient_token_account.owner == authority.key()
+        constraint = recipient_token_account.mint == ico_config.reward_token_mint,
+        constraint = recipient_token_account.owner == authority.key()
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
+        constraint = reward_token_treasury.owner == vault_authority.key()
+    )]
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = ico_config.reward_token_mint)]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct PaymentTokensTransfer<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        seeds = [b"payment_mint", mint.key().as_ref()],
+        bump
+    )]
+    pub payment_mint: Account<'info, PaymentMint>,
+
+    #[account(address = payment_mint.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == mint.key(),
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = payment_mint.treasury,
+        constraint = treasury.owner == authority.key()
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct BlockInvestor<'info> {
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        mut, 
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub investor_details: Account<'info, Investor>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct UnblockInvestor<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        mut,
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub investor_details: Account<'info, Investor>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DepositPrize<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(mut, address = ico_config.reward_token_mint)]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = authority_token_account.owner == authority.key(),
+        constraint = authority_token_account.mint == reward_token_mint.key()
     )]
-    pub recipient_token_account: Account<'info, TokenAccount>,
+    pub authority_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         seeds = [b"vault_authority"],
@@ -2170,14 +5763,14 @@ pub struct PrizeTokensTransfer<'info> {
         constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
         constraint = reward_token_treasury.owner == vault_authority.key()
     )]
-    pub reward_token_treasury: Account<'info, TokenAccount>,
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct UsdcTokensTransfer<'info> {
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct RevokeVesting<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
 
@@ -2190,24 +5783,57 @@ pub struct UsdcTokensTransfer<'info> {
 
     #[account(
         mut,
-        constraint = recipient_usdc_account.mint == ico_config.usdc_mint,
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
+        bump
+    )]
+    pub investor_details: Account<'info, Investor>,
+
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
+        constraint = reward_token_treasury.owner == vault_authority.key()
+    )]
+    pub reward_token_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    /// Written to when rewards are minted on claim
+    #[account(mut, address = ico_config.reward_token_mint)]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = clawback_token_account.mint == ico_config.reward_token_mint,
+        constraint = clawback_token_account.owner == ico_config.clawback_destination @ CustomError::InvalidClawbackDestination
     )]
-    pub recipient_usdc_account: Account<'info, TokenAccount>,
+    pub clawback_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    /// Only needed when the vested amount is paid out immediately.
+    /// Owner validated in the handler against the position's claim destination.
     #[account(
         mut,
-        constraint = usdc_treasury.mint == ico_config.usdc_mint.key(),
-        constraint = usdc_treasury.owner == authority.key()
+        constraint = investor_token_account.mint == ico_config.reward_token_mint
     )]
-    pub usdc_treasury: Account<'info, TokenAccount>,
+    pub investor_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Position token account of the holder, required to pay out a tokenized position
+    pub position_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct UsdtTokensTransfer<'info> {
+#[instruction(from_index: u32, new_address: Pubkey, new_index: u32)]
+pub struct TransferPosition<'info> {
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub beneficiary: Signer<'info>,
+
+    /// Optional co-signer, required when ico_config.transfer_requires_admin is set
+    pub admin: Option<Signer<'info>>,
 
     #[account(
         mut,
@@ -2218,60 +5844,86 @@ pub struct UsdtTokensTransfer<'info> {
 
     #[account(
         mut,
-        constraint = recipient_usdt_account.mint == ico_config.usdt_mint,
+        seeds = [b"investor is my hero", beneficiary.key().as_ref(), &from_index.to_le_bytes()],
+        bump,
+        constraint = from_investor.address == beneficiary.key() @ CustomError::UnauthorizedAccess,
+        close = beneficiary
     )]
-    pub recipient_usdt_account: Account<'info, TokenAccount>,
+    pub from_investor: Account<'info, Investor>,
 
     #[account(
         mut,
-        constraint = usdt_treasury.mint == ico_config.usdt_mint.key(),
-        constraint = usdt_treasury.owner == authority.key(),
-        
+        seeds = [b"wallet positions", beneficiary.key().as_ref()],
+        bump
     )]
-    pub usdt_treasury: Account<'info, TokenAccount>,
+    pub from_wallet_positions: Account<'info, WalletPositions>,
 
-    pub token_program: Program<'info, Token>,
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<Investor>(),
+        seeds = [b"investor is my hero", new_address.as_ref(), &new_index.to_le_bytes()],
+        bump
+    )]
+    pub to_investor: Account<'info, Investor>,
+
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        space = ANCHOR_DISCRIMINATOR_SIZE + size_of::<WalletPositions>(),
+        seeds = [b"wallet positions", new_address.as_ref()],
+        bump
+    )]
+    pub to_wallet_positions: Account<'info, WalletPositions>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(investor_address:Pubkey)]
-pub struct BlockInvestor<'info> {
-    pub ico_config: Account<'info, TokenIco>,
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct SetClaimDestination<'info> {
+    pub beneficiary: Signer<'info>,
 
     #[account(
-        mut, 
-        seeds = [b"investor is my hero", investor_address.key().as_ref()],
+        mut,
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
         bump
     )]
     pub investor_details: Account<'info, Investor>,
-
-    #[account(mut)]
-    pub authority: Signer<'info>,
-
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct DepositPrize<'info> {
+#[instruction(investor_address: Pubkey, position_index: u32)]
+pub struct TokenizePosition<'info> {
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub beneficiary: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [b"config"],
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
         bump
     )]
-    pub ico_config: Account<'info, TokenIco>,
+    pub investor_details: Account<'info, Investor>,
 
-    #[account(mut, address = ico_config.reward_token_mint)]
-    pub reward_token_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = beneficiary,
+        seeds = [b"position_mint", investor_details.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = vault_authority,
+        mint::token_program = token_program
+    )]
+    pub position_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
-        mut,
-        constraint = authority_token_account.owner == authority.key(),
-        constraint = authority_token_account.mint == reward_token_mint.key()
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = position_mint,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program
     )]
-    pub authority_token_account: Account<'info, TokenAccount>,
+    pub beneficiary_position_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         seeds = [b"vault_authority"],
@@ -2279,14 +5931,9 @@ pub struct DepositPrize<'info> {
     )]
     pub vault_authority: AccountInfo<'info>,
 
-    #[account(
-        mut,
-        constraint = reward_token_treasury.mint == ico_config.reward_token_mint,
-        constraint = reward_token_treasury.owner == vault_authority.key()
-    )]
-    pub reward_token_treasury: Account<'info, TokenAccount>,
-
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
@@ -2323,6 +5970,9 @@ pub struct GetIcoDates<'info> {
 #[derive(Accounts)]
 pub struct GetTokenRate<'info> {
     pub ico_config: Account<'info, TokenIco>,
+
+    /// Also report the rate of this payment token
+    pub payment_mint: Option<Account<'info, PaymentMint>>,
 }
 
 #[derive(Accounts)]
@@ -2349,19 +5999,19 @@ pub struct GetTokenBalance<'info> {
 }
 
 #[derive(Accounts)]
-// #[instruction(investor_address: Pubkey)]
+// #[instruction(investor_address: Pubkey, position_index: u32)]
 pub struct GetInvestorAddress<'info> {
     pub investor_details: Account<'info, Investor>,
 }
 
 // Define the context for GetVestingBalance
 #[derive(Accounts)]
-#[instruction(investor_address: Pubkey)]
+#[instruction(investor_address: Pubkey, position_index: u32)]
 pub struct GetVestingBalance<'info> {
     pub ico_config: Account<'info, TokenIco>,
     
     #[account(
-        seeds = [b"investor is my hero", investor_address.as_ref()],
+        seeds = [b"investor is my hero", investor_address.as_ref(), &position_index.to_le_bytes()],
         bump,
     )]
     pub investor_details: Account<'info, Investor>,
@@ -2370,6 +6020,36 @@ pub struct GetVestingBalance<'info> {
 }
 
 
+#[derive(Accounts)]
+pub struct GetSaleStats<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+}
+
+#[derive(Accounts)]
+pub struct GetScheduleTimeline<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub ico_config: Account<'info, TokenIco>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct GetWalletPositions<'info> {
+    pub ico_config: Account<'info, TokenIco>,
+
+    #[account(
+        seeds = [b"wallet positions", wallet.as_ref()],
+        bump,
+    )]
+    pub wallet_positions: Account<'info, WalletPositions>,
+}
+
 // Define the context for GetLinearVestingEndTime
 #[derive(Accounts)]
 pub struct GetLinearVestingEndTime<'info> {
@@ -2384,20 +6064,14 @@ pub struct GetLinearVestingEndTime<'info> {
 
 // Token Ico Configuration
 #[account]
+#[derive(Default)]
 pub struct TokenIco {
     pub authority: Pubkey,
     pub reward_token_mint: Pubkey,
 
     pub sol_treasury: Pubkey,
-    pub usdc_treasury: Pubkey,
-    pub usdt_treasury: Pubkey,
     pub prize_treasury: Pubkey,
 
-    pub usdc_mint: Pubkey,
-    pub usdt_mint: Pubkey,
-
-    pub usdc_decimals: u8,
-    pub usdt_decimals: u8,
     pub reward_token_decimals: u8,
 
     // pub prize_mint: Pubkey,
@@ -2409,8 +6083,6 @@ pub struct TokenIco {
     pub max_amount: u64,
 
     pub tokens_per_sol: u64,
-    pub tokens_per_usdc: u64,
-    pub tokens_per_usdt: u64,
 
     pub seconds_per_day: u64,
 
@@ -2422,9 +6094,34 @@ pub struct TokenIco {
     pub total_allocated: u64,
     // pub total_sold: u64,
     pub total_claimed: u64,
+
+    pub clawback_destination: Pubkey, // Receives unvested tokens of revoked grants
+
+    pub position_transfers_enabled: bool,
+    pub transfer_requires_admin: bool,
+
+    pub keeper_fee_bps: u16,
+    pub keeper_fee_cap: u64, // Reward token base units, 0 = uncapped
+
+    pub reward_mode: u8,
+    pub reward_supply_cap: u64, // Reward token base units, 0 = uncapped
+
+    pub referral_bonus_bps: u16,
+    pub referral_proceeds_bps: u16,
+    pub referral_bonus_cap: u64, // Bonus tokens per referrer, 0 = uncapped
+
+    pub sale_mode: u8,
+
+    // Sale statistics, see get_sale_stats
+    pub immediate_positions: u32, // Positions holding an allocation, per vesting type
+    pub linear_positions: u32,
+    pub wallet_count: u32, // Wallets with at least one position holding an allocation
+    pub sol_raised: u64, // Lamports, including wSOL
+    pub claim_count: u64,
 }
 
 #[account]
+#[derive(Default)]
 pub struct Investor {
     pub address: Pubkey,
     pub allocation: u64,
@@ -2435,11 +6132,121 @@ pub struct Investor {
     pub claimed_tokens: u64,
     pub blocked: bool, // Added field to indicate if the investor is blocked
     pub whitelisted_at: u64,
+    pub block_reason: u8,
+    pub blocked_until: u64, // 0 = blocked until explicitly unblocked
+    pub revocable: bool,
+    pub revoked: bool,
+    pub revoked_at: u64,
+    pub position_mint: Pubkey, // Default when the position is not tokenized
+    pub position_index: u32,
+    pub claim_destination: Pubkey, // Default pays the beneficiary itself
+}
+
+// Affiliate account, PDA at ["referrer", wallet]
+#[account]
+pub struct Referrer {
+    pub address: Pubkey,
+    pub bonus_tokens: u64,  // Bonus reward tokens credited, vesting like an Immediate position
+    pub claimed_bonus: u64,
+    pub referred_tokens: u64, // Reward tokens bought through this referrer
+    pub referral_count: u32,
+}
+
+// Accepted payment token, PDA at ["payment_mint", mint]
+#[account]
+pub struct PaymentMint {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub treasury: Pubkey,
+    pub tokens_per_token: u64, // Price: reward tokens per whole payment token (unused for wSOL)
+    pub enabled: bool,
+    pub unwrap_to_sol_vault: bool, // wSOL only: proceeds go to the SOL treasury as native SOL
+    pub total_raised: u64, // Base units received; wSOL counts towards config.sol_raised instead
+}
+
+// Dutch auction state, PDA at ["auction"]. Also holds bid payments until settlement.
+#[account]
+pub struct Auction {
+    pub start_price: u64, // Lamports per whole reward token
+    pub floor_price: u64,
+    pub supply: u64, // Reward tokens for sale
+    pub tokens_sold: u64,
+    pub total_paid: u64,     // Lamports held for bids and rebates
+    pub clearing_price: u64, // 0 until supply sells out or the auction is settled
+    pub settled: bool,
+}
+
+// Auction purchases of one position, PDA at ["auction_bid", wallet, position index]
+#[account]
+pub struct AuctionBid {
+    pub wallet: Pubkey,
+    pub position_index: u32,
+    pub tokens: u64,
+    pub paid: u64, // Lamports
+    pub rebate_claimed: bool,
+}
+
+// Pro-rata sale state, PDA at ["pro_rata_sale"]. Also holds commitments until settlement.
+#[account]
+pub struct ProRataSale {
+    pub token_cap: u64,
+    pub total_committed: u64, // Lamports
+    pub total_demand: u64,    // Tokens the commitments would buy, set at finalization
+    pub tokens_allocated: u64,
+    pub finalized: bool,
+}
+
+// Pro-rata sale commitment of one position, PDA at ["commitment", wallet, position index]
+#[account]
+pub struct Commitment {
+    pub wallet: Pubkey,
+    pub position_index: u32,
+    pub vesting_type: u8,
+    pub amount: u64, // Lamports
+    pub settled: bool,
+}
+
+// Purchase bonus tiers, PDA at ["bonus_tiers"]
+#[account]
+pub struct BonusTiers {
+    pub volume_tiers: Vec<BonusTier>, // Threshold: base tokens bought
+    pub time_tiers: Vec<BonusTier>,   // Threshold: seconds since ico_start_time
+    pub bonus_vesting_type: Option<u8>, // None = bonus joins the purchased position
+}
+
+impl BonusTiers {
+    // Bonus tokens for a purchase of `base_tokens`, `elapsed` seconds into the sale:
+    // the highest volume tier reached plus the first time tier still open
+    pub fn bonus_for(&self, base_tokens: u64, elapsed: u64) -> Result<u64> {
+        let volume_bps = self
+            .volume_tiers
+            .iter()
+            .rev()
+            .find(|tier| base_tokens >= tier.threshold)
+            .map_or(0, |tier| tier.bonus_bps);
+        let time_bps = self
+            .time_tiers
+            .iter()
+            .find(|tier| elapsed < tier.threshold)
+            .map_or(0, |tier| tier.bonus_bps);
+
+        bps_of(base_tokens, volume_bps + time_bps)
+    }
+}
+
+// Per-wallet counter of vesting positions
+#[account]
+#[derive(Default)]
+pub struct WalletPositions {
+    pub address: Pubkey,
+    pub position_count: u32,
+    pub open_positions: u32, // Positions holding an allocation
 }
 
 // all types of events
 
 #[event]
+#[derive(Debug)]
 pub struct InitializeEvent {
     pub authority: Pubkey,
     pub ico_start_time: u64,
@@ -2448,62 +6255,304 @@ pub struct InitializeEvent {
 }
 
 #[event]
+#[derive(Debug)]
 pub struct InvestorWhitelisted {
     pub investor: Pubkey,
+    pub position_index: u32,
     pub allocation: u64,
     pub vesting_type: u8,
     pub cliff_end: u64,
 }
 
 #[event]
+#[derive(Debug)]
 pub struct TokenPurchaseEventForSol {
     pub buyer: Pubkey,
     pub sol_amount: u64,
+    pub token_amount: u64, // Base allocation
+    pub bonus_amount: u64,
+    pub timestamp: u64,
+    pub wrapped: bool, // Paid in wSOL through buy_with_token
+    pub referrer: Option<Pubkey>,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct TokenPurchaseEventForToken {
+    pub buyer: Pubkey,
+    pub mint: Pubkey,
+    pub token_amount_paid: u64,
+    pub token_amount_received: u64,
+    pub token_amount: u64, // Base allocation
+    pub bonus_amount: u64,
+    pub timestamp: u64,
+    pub treasury: Pubkey,
+    pub referrer: Option<Pubkey>,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct PaymentMintUpdated {
+    pub authority: Pubkey,
+    pub mint: Pubkey,
+    pub treasury: Pubkey,
+    pub tokens_per_token: u64,
+    pub enabled: bool,
+    pub unwrap_to_sol_vault: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct ICODateChanged {
+    pub authority: Pubkey,
+    pub ico_start_time: u64,
+    pub ico_end_time: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct InvestorBlocked {
+    pub investor: Pubkey,
+    pub position_index: u32,
+    pub blocked_by: Pubkey,
+    pub reason: u8,
+    pub expires_at: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct InvestorUnblocked {
+    pub investor: Pubkey,
+    pub position_index: u32,
+    pub unblocked_by: Pubkey,
+    pub reason: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct VestingRevoked {
+    pub investor: Pubkey,
+    pub position_index: u32,
+    pub revoked_by: Pubkey,
+    pub vested: u64,
+    pub clawed_back: u64,
+    pub paid_out: u64,
+    pub clawback_destination: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct ClawbackDestinationUpdated {
+    pub authority: Pubkey,
+    pub destination: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct PositionTransferConfigUpdated {
+    pub authority: Pubkey,
+    pub enabled: bool,
+    pub require_admin_cosign: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct PositionTransferred {
+    pub from: Pubkey,
+    pub from_index: u32,
+    pub to: Pubkey,
+    pub to_index: u32,
+    pub allocation: u64,
+    pub claimed_tokens: u64,
+    pub merged: bool,
+    pub approved_by: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct PositionTokenized {
+    pub investor: Pubkey,
+    pub position_mint: Pubkey,
+    pub holder: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct ClaimDestinationUpdated {
+    pub investor: Pubkey,
+    pub position_index: u32,
+    pub destination: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct CrankClaimed {
+    pub investor: Pubkey,
+    pub position_index: u32,
+    pub keeper: Pubkey,
+    pub amount: u64,
+    pub keeper_fee: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct AuctionConfigured {
+    pub authority: Pubkey,
+    pub start_price: u64,
+    pub floor_price: u64,
+    pub supply: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct AuctionBidPlaced {
+    pub buyer: Pubkey,
+    pub investor: Pubkey,
+    pub position_index: u32,
     pub token_amount: u64,
+    pub price: u64,
+    pub sol_amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct AuctionSettled {
+    pub clearing_price: u64,
+    pub tokens_sold: u64,
+    pub proceeds: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct AuctionRebateClaimed {
+    pub investor: Pubkey,
+    pub position_index: u32,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct ProRataSaleConfigured {
+    pub authority: Pubkey,
+    pub token_cap: u64,
+    pub tokens_per_sol: u64,
     pub timestamp: u64,
 }
 
 #[event]
-pub struct TokenPurchaseEventForUsdc {
+#[derive(Debug)]
+pub struct SaleCommitted {
     pub buyer: Pubkey,
-    pub usdc_amount: u64,
+    pub investor: Pubkey,
+    pub position_index: u32,
+    pub sol_amount: u64,
+    pub total_commitment: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct SaleFinalized {
+    pub total_committed: u64,
+    pub total_demand: u64,
+    pub token_cap: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct CommitmentSettled {
+    pub investor: Pubkey,
+    pub position_index: u32,
     pub token_amount: u64,
+    pub sol_amount: u64, // Lamports kept for the allocation
+    pub refund: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct BonusTiersUpdated {
+    pub authority: Pubkey,
+    pub volume_tiers: Vec<BonusTier>,
+    pub time_tiers: Vec<BonusTier>,
+    pub bonus_vesting_type: Option<u8>,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct ReferralConfigUpdated {
+    pub authority: Pubkey,
+    pub bonus_bps: u16,
+    pub proceeds_bps: u16,
+    pub bonus_cap: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct ReferrerRegistered {
+    pub referrer: Pubkey,
     pub timestamp: u64,
-    pub usdc_treasury: Pubkey,
 }
 
 #[event]
-pub struct TokenPurchaseEventForUsdt {
+#[derive(Debug)]
+pub struct ReferralRecorded {
+    pub referrer: Pubkey,
     pub buyer: Pubkey,
-    pub usdt_amount: u64,
+    pub payment_mint: Pubkey, // Default for native SOL
     pub token_amount: u64,
+    pub bonus_tokens: u64,
+    pub proceeds_cut: u64, // In base units of the payment currency
     pub timestamp: u64,
-    pub usdt_treasury: Pubkey,
 }
 
 #[event]
-pub struct ICODateChanged {
-    pub authority: Pubkey,
-    pub ico_start_time: u64,
-    pub ico_end_time: u64,
+#[derive(Debug)]
+pub struct ReferralBonusClaimed {
+    pub referrer: Pubkey,
+    pub amount: u64,
+    pub remaining: u64,
     pub timestamp: u64,
 }
 
 #[event]
-pub struct InvestorBlocked {
-    pub investor: Pubkey,
-    pub blocked_by: Pubkey,
+#[derive(Debug)]
+pub struct KeeperFeeUpdated {
+    pub authority: Pubkey,
+    pub fee_bps: u16,
+    pub fee_cap: u64,
     pub timestamp: u64,
 }
 
 #[event]
+#[derive(Debug)]
 pub struct InvestorRemoved {
     pub investor: Pubkey,
+    pub position_index: u32,
     pub removed_by: Pubkey,
     pub timestamp: u64,
 }
 
 #[event]
+#[derive(Debug)]
 pub struct PrizeTokenUpdated {
     pub authority: Pubkey,
     pub token: Pubkey,
@@ -2511,6 +6560,7 @@ pub struct PrizeTokenUpdated {
 }
 
 #[event]
+#[derive(Debug)]
 pub struct WithdrawnSol {
     pub authority: Pubkey,
     pub amount: u64,
@@ -2519,6 +6569,7 @@ pub struct WithdrawnSol {
 }
 
 #[event]
+#[derive(Debug)]
 pub struct PrizeWithdrawTokens {
     pub authority: Pubkey,
     pub amount: u64,
@@ -2526,28 +6577,28 @@ pub struct PrizeWithdrawTokens {
 }
 
 #[event]
-pub struct UsdcWithdrawTokens {
-    pub authority: Pubkey,
-    pub amount: u64,
-    pub timestamp: u64,
-}
-
-#[event]
-pub struct UsdtWithdrawTokens {
+#[derive(Debug)]
+pub struct PaymentTokensWithdrawn {
     pub authority: Pubkey,
+    pub mint: Pubkey,
+    pub treasury: Pubkey,
     pub amount: u64,
     pub timestamp: u64,
 }
 
 #[event]
+#[derive(Debug)]
 pub struct TokensClaimed {
     pub investor: Pubkey,
+    pub position_index: u32,
+    pub destination: Pubkey,
     pub amount: u64,
     pub timestamp: u64,
     pub remaining: u64,
 }
 
 #[event]
+#[derive(Debug)]
 pub struct PrizeDeposited {
     pub depositor: Pubkey,
     pub amount: u64,
@@ -2555,13 +6606,7 @@ pub struct PrizeDeposited {
 }
 
 #[event]
-pub struct UsdtAddressUpdated {
-    pub authority: Pubkey,
-    pub token: Pubkey,
-    pub timestamp: u64,
-}
-
-#[event]
+#[derive(Debug)]
 pub struct SolTreasuryUpdated {
     pub authority: Pubkey,
     pub sol_treasury: Pubkey,
@@ -2569,13 +6614,7 @@ pub struct SolTreasuryUpdated {
 }
 
 #[event]
-pub struct UsdcAddressUpdated {
-    pub authority: Pubkey,
-    pub token: Pubkey,
-    pub timestamp: u64,
-}
-
-#[event]
+#[derive(Debug)]
 pub struct TGEDateChanged {
     pub authority: Pubkey,
     pub new_tge_time: u64,
@@ -2583,6 +6622,7 @@ pub struct TGEDateChanged {
 }
 
 #[event]
+#[derive(Debug)]
 pub struct SecondsPerDayChanged {
     pub authority: Pubkey,
     pub new_value: u64,
@@ -2591,6 +6631,7 @@ pub struct SecondsPerDayChanged {
 }
 
 #[event]
+#[derive(Debug)]
 pub struct OwnershipTransferred {
     pub previous_owner: Pubkey,
     pub new_owner: Pubkey,
@@ -2598,12 +6639,14 @@ pub struct OwnershipTransferred {
 }
 
 #[event]
+#[derive(Debug)]
 pub struct OwnershipRenounced {
     pub previous_owner: Pubkey,
     pub timestamp: u64,
 }
 
 #[event]
+#[derive(Debug)]
 pub struct PausedEvent {
     pub authority: Pubkey,
     pub paused: bool,
@@ -2612,6 +6655,161 @@ pub struct PausedEvent {
 
 // ============================== custom types =====================
 
+// Return data of the view instructions
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct IcoDates {
+    pub ico_start_time: u64,
+    pub ico_end_time: u64,
+    pub tge_time: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RateInfo {
+    pub tokens_per_sol: u64,
+    pub payment_mint: Option<Pubkey>, // Set when a payment mint was passed
+    pub tokens_per_token: u64,        // 0 for wSOL, which uses tokens_per_sol
+    pub payment_mint_enabled: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BuyLimits {
+    pub min_amount: u64,
+    pub max_amount: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct VestingBalance {
+    pub investor: Pubkey,
+    pub position_index: u32,
+    pub vesting_type: u8,
+    pub total_allocation: u64,
+    pub claimed_tokens: u64,
+    pub claimable_tokens: u64,
+    pub total_vested: u64,
+    pub remaining_tokens: u64,
+    pub next_cliff: u64,
+    pub last_claimed: u64,
+    pub blocked: bool,
+    pub block_reason: u8,
+    pub blocked_until: u64,
+    pub revoked: bool,
+}
+
+// Linear vesting milestone timestamps
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MilestoneSchedule {
+    pub tge_time: u64,
+    pub month_8: u64,  // 15% vested
+    pub month_18: u64, // 35% vested
+    pub month_24: u64, // 55% vested
+    pub month_36: u64, // 100% vested
+    pub completed: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TokenRaised {
+    pub mint: Pubkey,
+    pub total_raised: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct WalletPositionsSummary {
+    pub wallet: Pubkey,
+    pub position_count: u32,     // Position indexes the wallet has used
+    pub positions_included: u32, // Investor PDAs passed to get_wallet_positions
+    pub total_allocation: u64,
+    pub total_claimed: u64,
+    pub total_claimable: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SaleStats {
+    pub total_allocated: u64,
+    pub total_user_allocated: u64, // Allocated through purchases, bonuses included
+    pub total_prize_deposited: u64,
+    pub total_claimed: u64,
+    pub claim_count: u64,
+    pub position_count: u32, // Positions holding an allocation
+    pub immediate_positions: u32, // Positions, not buyers, per vesting type
+    pub linear_positions: u32,
+    pub wallet_count: u32, // Wallets holding at least one position
+    pub sol_raised: u64,
+    pub token_raised: Vec<TokenRaised>, // Payment mints passed to get_sale_stats
+}
+
+// One unlock; immediate vesting is aggregated by 30-day month
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TimelinePoint {
+    pub timestamp: u64,
+    pub cumulative_vested: u64,
+    pub unlocked: u64, // Released since the previous point
+}
+
+// At most 14 points, well inside the return data limit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct VestingTimeline {
+    pub investor: Pubkey, // Default for a hypothetical schedule
+    pub position_index: u32,
+    pub vesting_type: u8,
+    pub total_allocation: u64,
+    pub claimed_tokens: u64,
+    pub points: Vec<TimelinePoint>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BonusTier {
+    pub threshold: u64,
+    pub bonus_bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct WhitelistEntry {
+    pub address: Pubkey,
+    pub position_index: u32,
+    pub amount: u64,
+    pub vesting_type: u8,
+    pub revocable: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BatchClaimEntry {
+    pub investor_address: Pubkey,
+    pub position_index: u32,
+}
+
+// Outcome of a batch instruction; statuses[i] is the BATCH_STATUS_* of entry i
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BatchResult {
+    pub processed: u32,
+    pub skipped: u32,
+    pub invalid: u32,
+    pub not_processed: u32,
+    pub statuses: Vec<u8>,
+}
+
+impl BatchResult {
+    fn new(len: usize) -> Self {
+        BatchResult {
+            processed: 0,
+            skipped: 0,
+            invalid: 0,
+            not_processed: len as u32,
+            statuses: vec![BATCH_STATUS_NOT_PROCESSED; len],
+        }
+    }
+
+    fn set(&mut self, index: usize, status: u8) {
+        self.statuses[index] = status;
+        self.not_processed -= 1;
+        match status {
+            BATCH_STATUS_PROCESSED => self.processed += 1,
+            BATCH_STATUS_SKIPPED => self.skipped += 1,
+            _ => self.invalid += 1,
+        }
+    }
+}
+
 // Additional error codes
 #[error_code]
 pub enum CustomError {
@@ -2654,6 +6852,889 @@ pub enum CustomError {
     #[msg("Mismatched vesting type for existing investor.")]
     MismatchedVestingType,
     #[msg("Buyer have not enough rent exempt")]
-    BuyerNotRentExempt
+    BuyerNotRentExempt,
+    #[msg("Invalid block reason code.")]
+    InvalidBlockReason,
+    #[msg("Block expiry must be in the future.")]
+    InvalidBlockExpiry,
+    #[msg("Investor is not blocked.")]
+    InvestorNotBlocked,
+    #[msg("This vesting position is not revocable.")]
+    PositionNotRevocable,
+    #[msg("This vesting position has been revoked.")]
+    PositionRevoked,
+    #[msg("Revocable flag does not match the existing position.")]
+    MismatchedRevocableFlag,
+    #[msg("Clawback destination has not been configured.")]
+    ClawbackDestinationNotSet,
+    #[msg("Token account is not owned by the clawback destination.")]
+    InvalidClawbackDestination,
+    #[msg("Investor token account is required to pay out vested tokens.")]
+    InvestorTokenAccountRequired,
+    #[msg("Position transfers are not enabled for this sale.")]
+    PositionTransfersDisabled,
+    #[msg("Position transfer requires the admin co-signature.")]
+    AdminCosignRequired,
+    #[msg("Positions have incompatible vesting schedules and cannot be merged.")]
+    IncompatiblePositions,
+    #[msg("This position is tokenized.")]
+    PositionIsTokenized,
+    #[msg("Position token account is required for a tokenized position.")]
+    PositionTokenAccountRequired,
+    #[msg("Signer does not hold the position token.")]
+    NotPositionHolder,
+    #[msg("Position index must refer to an existing position or the next free one.")]
+    InvalidPositionIndex,
+    #[msg("Account is not a vesting position of this wallet.")]
+    InvalidPositionAccount,
+    #[msg("Claim recipient does not match the position's claim destination.")]
+    InvalidClaimDestination,
+    #[msg("Keeper fee exceeds the maximum allowed.")]
+    KeeperFeeTooHigh,
+    #[msg("Remaining accounts do not match the batch entries.")]
+    InvalidRemainingAccounts,
+    #[msg("Requested claim amount exceeds the claimable amount.")]
+    ClaimAmountExceedsClaimable,
+    #[msg("Invalid reward mode.")]
+    InvalidRewardMode,
+    #[msg("Mint authority of the reward token is not the vault authority.")]
+    MintAuthorityNotVault,
+    #[msg("Minting would exceed the reward token supply cap.")]
+    RewardSupplyCapExceeded,
+    #[msg("Mint has a token extension that is not supported.")]
+    UnsupportedMintExtension,
+    #[msg("Payment token is disabled.")]
+    PaymentMintDisabled,
+    #[msg("Token rate must be greater than zero.")]
+    InvalidTokenRate,
+    #[msg("Only wrapped SOL can be unwrapped into the SOL treasury.")]
+    UnwrapRequiresNativeMint,
+    #[msg("wSOL escrow, vault authority and SOL treasury accounts are required to unwrap.")]
+    UnwrapAccountsRequired,
+    #[msg("Referral share exceeds the maximum allowed.")]
+    ReferralBpsTooHigh,
+    #[msg("Buyers cannot refer their own purchases.")]
+    SelfReferral,
+    #[msg("Referrer payout account is required for the proceeds share.")]
+    ReferrerPayoutAccountRequired,
+    #[msg("Referrer payout account does not belong to the referrer.")]
+    InvalidReferrerPayoutAccount,
+    #[msg("Bonus tiers must be sorted by threshold, at most 8 per list and at most 5000 basis points each.")]
+    InvalidBonusTiers,
+    #[msg("A separate bonus position is required for this purchase.")]
+    BonusPositionRequired,
+    #[msg("This instruction is not available in the current sale mode.")]
+    WrongSaleMode,
+    #[msg("Auction needs a non-zero floor, a start price at or above the floor and a supply.")]
+    InvalidAuctionConfig,
+    #[msg("The auction has sold out.")]
+    AuctionSoldOut,
+    #[msg("The auction has neither sold out nor ended.")]
+    AuctionNotEnded,
+    #[msg("The auction is already settled.")]
+    AuctionAlreadySettled,
+    #[msg("The auction is not settled yet.")]
+    AuctionNotSettled,
+    #[msg("No rebate available.")]
+    NoRebateAvailable,
+    #[msg("The sale cap must be greater than zero.")]
+    InvalidSaleConfig,
+    #[msg("The sale window has not closed yet.")]
+    SaleNotEnded,
+    #[msg("The sale is already finalized.")]
+    SaleAlreadyFinalized,
+    #[msg("The sale is not finalized yet.")]
+    SaleNotFinalized,
+    #[msg("This commitment is already settled.")]
+    CommitmentAlreadySettled,
+    #[msg("Settlement would allocate more than the sale cap.")]
+    SaleCapExceeded,
+    #[msg("The purchase is too small to cover the wSOL escrow rent.")]
+    UnwrapAmountTooSmall,
+}
+
+impl From<VestingError> for CustomError {
+    fn from(error: VestingError) -> Self {
+        match error {
+            VestingError::InvalidVestingType => CustomError::InvalidVestingType,
+            VestingError::ArithmeticOverflow => CustomError::ArithmeticOverflow,
+        }
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RENT_MINIMUM: u64 = 1_670_400;
+
+    // What the system program tracks for an address while it is being created
+    #[derive(Debug, Clone, Default)]
+    struct SystemAccount {
+        lamports: u64,
+        allocated: bool,
+        program_owned: bool,
+    }
+
+    // Apply a step with the system program's rules
+    fn apply(
+        account: &mut SystemAccount,
+        step: AccountInitStep,
+    ) -> std::result::Result<(), &'static str> {
+        match step {
+            AccountInitStep::Create { lamports } => {
+                if account.lamports > 0 {
+                    return Err("account already in use");
+                }
+                account.lamports = lamports;
+                account.allocated = true;
+                account.program_owned = true;
+            }
+            AccountInitStep::Transfer { lamports } => account.lamports += lamports,
+            AccountInitStep::Allocate => {
+                if account.allocated || account.program_owned {
+                    return Err("account already in use");
+                }
+                account.allocated = true;
+            }
+            AccountInitStep::Assign => account.program_owned = true,
+        }
+        Ok(())
+    }
+
+    fn initialize(account: &mut SystemAccount) -> std::result::Result<(), &'static str> {
+        for step in account_init_steps(account.lamports, RENT_MINIMUM) {
+            apply(account, step)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn empty_pda_is_created_in_one_call() {
+        assert_eq!(
+            account_init_steps(0, RENT_MINIMUM),
+            vec![AccountInitStep::Create { lamports: RENT_MINIMUM }]
+        );
+
+        let mut account = SystemAccount::default();
+        initialize(&mut account).unwrap();
+        assert_eq!(account.lamports, RENT_MINIMUM);
+        assert!(account.allocated && account.program_owned);
+    }
+
+    #[test]
+    fn prefunded_pda_is_topped_up_allocated_and_assigned() {
+        // Someone sent a lamport to a wallet's investor PDA before the batch whitelisted it
+        let mut account = SystemAccount {
+            lamports: 1,
+            ..SystemAccount::default()
+        };
+        assert!(apply(
+            &mut account.clone(),
+            AccountInitStep::Create { lamports: RENT_MINIMUM }
+        )
+        .is_err());
+
+        assert_eq!(
+            account_init_steps(1, RENT_MINIMUM),
+            vec![
+                AccountInitStep::Transfer { lamports: RENT_MINIMUM - 1 },
+                AccountInitStep::Allocate,
+                AccountInitStep::Assign,
+            ]
+        );
+        initialize(&mut account).unwrap();
+        assert_eq!(account.lamports, RENT_MINIMUM);
+        assert!(account.allocated && account.program_owned);
+    }
+
+    #[test]
+    fn pda_funded_past_rent_is_not_topped_up() {
+        let mut account = SystemAccount {
+            lamports: RENT_MINIMUM + 5,
+            ..SystemAccount::default()
+        };
+        assert_eq!(
+            account_init_steps(account.lamports, RENT_MINIMUM),
+            vec![AccountInitStep::Allocate, AccountInitStep::Assign]
+        );
+        initialize(&mut account).unwrap();
+        assert_eq!(account.lamports, RENT_MINIMUM + 5);
+        assert!(account.allocated && account.program_owned);
+    }
+
+    fn blocked(blocked_until: u64) -> Investor {
+        Investor {
+            blocked: true,
+            block_reason: BLOCK_REASON_COMPLIANCE,
+            blocked_until,
+            ..Investor::default()
+        }
+    }
+
+    #[test]
+    fn block_without_expiry_lasts_until_lifted() {
+        let investor = blocked(0);
+        assert!(is_block_active(&investor, 0));
+        assert!(is_block_active(&investor, u64::MAX));
+
+        let lifted = Investor {
+            blocked: false,
+            ..investor
+        };
+        assert!(!is_block_active(&lifted, 0));
+    }
+
+    #[test]
+    fn block_expires_at_blocked_until() {
+        let investor = blocked(5_000);
+        assert!(is_block_active(&investor, 0));
+        assert!(is_block_active(&investor, 4_999));
+        assert!(!is_block_active(&investor, 5_000));
+        assert!(!is_block_active(&investor, 5_001));
+
+        // An expiry only matters while the block flag is set
+        let lifted = Investor {
+            blocked: false,
+            ..blocked(5_000)
+        };
+        assert!(!is_block_active(&lifted, 4_999));
+    }
+
+    const TGE: u64 = 1_000_000;
+    const DAY: u64 = 86_400;
+
+    fn sale_config() -> TokenIco {
+        TokenIco {
+            tge_time: TGE,
+            seconds_per_day: DAY,
+            ..TokenIco::default()
+        }
+    }
+
+    fn position(vesting_type: u8, allocation: u64, claimed_tokens: u64) -> Investor {
+        Investor {
+            allocation,
+            vesting_type,
+            claimed_tokens,
+            ..Investor::default()
+        }
+    }
+
+    #[test]
+    fn revoked_grant_is_claimable_up_to_its_frozen_allocation() {
+        let config = sale_config();
+        // 10% of an Immediate grant is released at TGE and the rest over a year
+        let live = position(VESTING_TYPE_IMMEDIATE, 1_000_000, 100_000);
+        assert_eq!(
+            calculate_investor_claimable(&live, &config, TGE).unwrap(),
+            (0, 100_000)
+        );
+
+        // Revoked with 400_000 vested: the rest of it is claimable at any time, and no more
+        let revoked = Investor {
+            allocation: 400_000,
+            revoked: true,
+            revoked_at: TGE + 30 * DAY,
+            ..live
+        };
+        for time in [0, TGE, TGE + 30 * DAY, TGE + 5 * 365 * DAY] {
+            assert_eq!(
+                calculate_investor_claimable(&revoked, &config, time).unwrap(),
+                (300_000, 400_000)
+            );
+        }
+    }
+
+    #[test]
+    fn fully_claimed_revoked_grant_has_nothing_left() {
+        let config = sale_config();
+        let revoked = Investor {
+            revoked: true,
+            ..position(VESTING_TYPE_LINEAR, 250_000, 250_000)
+        };
+        assert_eq!(
+            calculate_investor_claimable(&revoked, &config, TGE + 365 * DAY).unwrap(),
+            (0, 250_000)
+        );
+
+        // Revoked before TGE, nothing had vested
+        let revoked = Investor {
+            revoked: true,
+            ..position(VESTING_TYPE_LINEAR, 0, 0)
+        };
+        assert_eq!(
+            calculate_investor_claimable(&revoked, &config, TGE + 365 * DAY).unwrap(),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn purchases_keep_the_schedule_of_their_position() {
+        // An empty position takes whichever schedule its first purchase asks for
+        let empty = Investor::default();
+        assert!(check_vesting_type(&empty, VESTING_TYPE_IMMEDIATE).is_ok());
+        assert!(check_vesting_type(&empty, VESTING_TYPE_LINEAR).is_ok());
+
+        // Immediate is 0, which must not read as "no schedule yet"
+        let immediate = position(VESTING_TYPE_IMMEDIATE, 1_000, 0);
+        assert!(check_vesting_type(&immediate, VESTING_TYPE_IMMEDIATE).is_ok());
+        assert!(check_vesting_type(&immediate, VESTING_TYPE_LINEAR).is_err());
+
+        let linear = position(VESTING_TYPE_LINEAR, 1_000, 0);
+        assert!(check_vesting_type(&linear, VESTING_TYPE_LINEAR).is_ok());
+        assert!(check_vesting_type(&linear, VESTING_TYPE_IMMEDIATE).is_err());
+    }
+
+    #[test]
+    fn positions_are_numbered_without_gaps() {
+        let wallet = Pubkey::new_unique();
+        let mut positions = WalletPositions::default();
+        register_position(&mut positions, wallet, 0).unwrap();
+        assert_eq!((positions.address, positions.position_count), (wallet, 1));
+
+        // Reusing an index tops up that position, skipping one is refused
+        register_position(&mut positions, wallet, 0).unwrap();
+        assert_eq!(positions.position_count, 1);
+        assert!(register_position(&mut positions, wallet, 2).is_err());
+        register_position(&mut positions, wallet, 1).unwrap();
+        assert_eq!(positions.position_count, 2);
+    }
+
+    #[test]
+    fn wallets_are_counted_once_while_they_hold_a_position() {
+        let mut config = TokenIco::default();
+        let mut first = WalletPositions::default();
+        let mut second = WalletPositions::default();
+
+        record_new_position(&mut config, &mut first, VESTING_TYPE_IMMEDIATE).unwrap();
+        record_new_position(&mut config, &mut first, VESTING_TYPE_LINEAR).unwrap();
+        record_new_position(&mut config, &mut first, VESTING_TYPE_LINEAR).unwrap();
+        record_new_position(&mut config, &mut second, VESTING_TYPE_LINEAR).unwrap();
+        assert_eq!(
+            (config.immediate_positions, config.linear_positions, config.wallet_count),
+            (1, 3, 2)
+        );
+
+        // The wallet stays counted until its last position goes
+        forget_position(&mut config, &mut first, VESTING_TYPE_LINEAR);
+        forget_position(&mut config, &mut first, VESTING_TYPE_LINEAR);
+        assert_eq!((first.open_positions, config.wallet_count), (1, 2));
+        forget_position(&mut config, &mut first, VESTING_TYPE_IMMEDIATE);
+        assert_eq!((first.open_positions, config.wallet_count), (0, 1));
+
+        // Forgetting more than was recorded never underflows
+        forget_position(&mut config, &mut first, VESTING_TYPE_IMMEDIATE);
+        assert_eq!((first.open_positions, config.wallet_count), (0, 1));
+    }
+
+    #[test]
+    fn merged_position_keeps_both_allocations_and_claims() {
+        let config = sale_config();
+        let mut to = Investor {
+            cliff_end: TGE + 30 * DAY,
+            last_claimed: TGE + 10 * DAY,
+            whitelisted_at: 500,
+            ..position(VESTING_TYPE_IMMEDIATE, 600_000, 60_000)
+        };
+        let from = Investor {
+            cliff_end: TGE,
+            last_claimed: 0,
+            whitelisted_at: 700,
+            ..position(VESTING_TYPE_IMMEDIATE, 400_000, 40_000)
+        };
+        let time = TGE + 100 * DAY;
+        let (to_claimable, _) = calculate_investor_claimable(&to, &config, time).unwrap();
+        let (from_claimable, _) = calculate_investor_claimable(&from, &config, time).unwrap();
+
+        merge_positions(&mut to, &from, time).unwrap();
+        assert_eq!((to.allocation, to.claimed_tokens), (1_000_000, 100_000));
+        assert_eq!(to.cliff_end, TGE);
+        assert_eq!(to.last_claimed, TGE + 10 * DAY);
+        assert_eq!(to.whitelisted_at, 500);
+        // Nothing vests faster or slower for being merged, up to rounding
+        let (merged_claimable, _) = calculate_investor_claimable(&to, &config, time).unwrap();
+        assert!(merged_claimable.abs_diff(to_claimable + from_claimable) <= 1);
+    }
+
+    #[test]
+    fn incompatible_positions_are_not_merged() {
+        let from = position(VESTING_TYPE_IMMEDIATE, 400_000, 0);
+        let other_schedule = position(VESTING_TYPE_LINEAR, 600_000, 0);
+        let revocable = Investor {
+            revocable: true,
+            ..position(VESTING_TYPE_IMMEDIATE, 600_000, 0)
+        };
+        let revoked = Investor {
+            revoked: true,
+            ..position(VESTING_TYPE_IMMEDIATE, 600_000, 0)
+        };
+        for mut to in [other_schedule, revocable, revoked] {
+            let before = to.allocation;
+            assert!(merge_positions(&mut to, &from, TGE).is_err());
+            assert_eq!(to.allocation, before);
+        }
+
+        // A blocked target refuses the merge until its block expires
+        let mut to = Investor {
+            blocked: true,
+            blocked_until: TGE + DAY,
+            ..position(VESTING_TYPE_IMMEDIATE, 600_000, 0)
+        };
+        assert!(merge_positions(&mut to, &from, TGE).is_err());
+        merge_positions(&mut to, &from, TGE + DAY).unwrap();
+        assert_eq!(to.allocation, 1_000_000);
+    }
+
+    #[test]
+    fn claim_amount_is_bounded_by_the_claimable_tokens() {
+        assert_eq!(claim_amount_of(None, 700).unwrap(), 700);
+        assert_eq!(claim_amount_of(Some(1), 700).unwrap(), 1);
+        assert_eq!(claim_amount_of(Some(700), 700).unwrap(), 700);
+        assert!(claim_amount_of(Some(701), 700).is_err());
+        assert!(claim_amount_of(Some(0), 700).is_err());
+    }
+
+    #[test]
+    fn partial_claims_leave_the_rest_claimable() {
+        let mut config = sale_config();
+        let mut investor = position(VESTING_TYPE_IMMEDIATE, 1_000_000, 0);
+        let time = TGE + 100 * DAY;
+        let (claimable, vested) = calculate_investor_claimable(&investor, &config, time).unwrap();
+        assert_eq!(claimable, vested);
+
+        for requested in [claimable / 3, claimable / 3] {
+            let amount = claim_amount_of(Some(requested), claimable).unwrap();
+            record_claim(&mut config, &mut investor, amount, time).unwrap();
+        }
+        let claimed = 2 * (claimable / 3);
+        assert_eq!(investor.claimed_tokens, claimed);
+        assert_eq!((config.total_claimed, config.claim_count), (claimed, 2));
+
+        let (left, _) = calculate_investor_claimable(&investor, &config, time).unwrap();
+        assert_eq!(left, claimable - claimed);
+        let amount = claim_amount_of(None, left).unwrap();
+        record_claim(&mut config, &mut investor, amount, time).unwrap();
+        let (left, _) = calculate_investor_claimable(&investor, &config, time).unwrap();
+        assert_eq!((left, investor.claimed_tokens), (0, claimable));
+    }
+
+    #[test]
+    fn minting_stops_at_the_supply_cap() {
+        assert!(check_supply_cap(1_000, 400, 600).is_ok());
+        assert!(check_supply_cap(1_000, 400, 601).is_err());
+        assert!(check_supply_cap(1_000, 1_000, 0).is_ok());
+        assert!(check_supply_cap(1_000, 1_001, 0).is_err());
+        // Uncapped
+        assert!(check_supply_cap(0, u64::MAX - 1, 1).is_ok());
+        assert!(check_supply_cap(u64::MAX, u64::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn bps_round_down_and_refuse_overflow() {
+        assert_eq!(bps_of(10_000, 1).unwrap(), 1);
+        assert_eq!(bps_of(9_999, 1).unwrap(), 0);
+        assert_eq!(bps_of(123_456_789, 250).unwrap(), 3_086_419);
+        assert_eq!(bps_of(u64::MAX, 0).unwrap(), 0);
+        assert_eq!(bps_of(u64::MAX / 10_000, 10_000).unwrap(), u64::MAX / 10_000);
+        assert!(bps_of(u64::MAX, 2).is_err());
+    }
+
+    fn keeper_config(keeper_fee_bps: u16, keeper_fee_cap: u64) -> TokenIco {
+        TokenIco {
+            keeper_fee_bps,
+            keeper_fee_cap,
+            ..sale_config()
+        }
+    }
+
+    #[test]
+    fn keeper_fee_is_capped() {
+        // 1% of a 500 token claim is 5 tokens
+        let claim = 500 * TOKEN_DECIMALS;
+        assert_eq!(keeper_fee_for(&keeper_config(100, 0), claim).unwrap(), 5 * TOKEN_DECIMALS);
+
+        let fee = 5 * TOKEN_DECIMALS;
+        assert_eq!(keeper_fee_for(&keeper_config(100, fee + 1), claim).unwrap(), fee);
+        assert_eq!(keeper_fee_for(&keeper_config(100, fee), claim).unwrap(), fee);
+        assert_eq!(keeper_fee_for(&keeper_config(100, fee - 1), claim).unwrap(), fee - 1);
+        assert_eq!(keeper_fee_for(&keeper_config(0, fee), claim).unwrap(), 0);
+    }
+
+    #[test]
+    fn keeper_fee_never_exceeds_the_claim() {
+        let config = keeper_config(MAX_KEEPER_FEE_BPS, 0);
+        for claim in [0, 1, 19, 20, 21, 999_999, 7 * TOKEN_DECIMALS, u64::MAX / 10_000] {
+            let fee = keeper_fee_for(&config, claim).unwrap();
+            // 500 bps is a twentieth
+            assert!(fee <= claim / 20);
+        }
+    }
+
+    #[test]
+    fn claims_go_to_the_designated_destination() {
+        let (beneficiary, keeper, destination) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let investor = Investor {
+            address: beneficiary,
+            ..position(VESTING_TYPE_LINEAR, 1_000, 0)
+        };
+        // Without a destination the claimer is paid, which is the beneficiary outside cranks
+        assert_eq!(claim_destination_of(&investor, beneficiary), beneficiary);
+
+        // With one, every claimer pays into it, a keeper cranking the claim included
+        let investor = Investor {
+            claim_destination: destination,
+            ..investor
+        };
+        assert_eq!(claim_destination_of(&investor, beneficiary), destination);
+        assert_eq!(claim_destination_of(&investor, keeper), destination);
+    }
+
+    #[test]
+    fn tokenized_position_pays_the_token_holder() {
+        let (beneficiary, holder) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let investor = Investor {
+            address: beneficiary,
+            position_mint: Pubkey::new_unique(),
+            ..position(VESTING_TYPE_LINEAR, 1_000, 0)
+        };
+        assert_eq!(claim_destination_of(&investor, holder), holder);
+
+        // A destination left over from before tokenizing no longer applies
+        let investor = Investor {
+            claim_destination: Pubkey::new_unique(),
+            ..investor
+        };
+        assert_eq!(claim_destination_of(&investor, holder), holder);
+    }
+
+    fn referral_config(referral_bonus_bps: u16, referral_bonus_cap: u64) -> TokenIco {
+        TokenIco {
+            referral_bonus_bps,
+            referral_bonus_cap,
+            ..sale_config()
+        }
+    }
+
+    fn referrer() -> Referrer {
+        Referrer {
+            address: Pubkey::new_unique(),
+            bonus_tokens: 0,
+            claimed_bonus: 0,
+            referred_tokens: 0,
+            referral_count: 0,
+        }
+    }
+
+    #[test]
+    fn referral_bonus_is_credited_and_allocated() {
+        // 5% of each purchase, uncapped
+        let mut config = referral_config(500, 0);
+        let mut referrer = referrer();
+        assert_eq!(credit_referral(&mut config, &mut referrer, 10_000).unwrap(), 500);
+        assert_eq!(credit_referral(&mut config, &mut referrer, 999).unwrap(), 49);
+        assert_eq!(referrer.bonus_tokens, 549);
+        assert_eq!(referrer.referred_tokens, 10_999);
+        assert_eq!(referrer.referral_count, 2);
+        assert_eq!(config.total_allocated, 549);
+    }
+
+    #[test]
+    fn referral_bonus_stays_within_the_cap() {
+        let mut config = referral_config(500, 1_200);
+        let mut referrer = referrer();
+        assert_eq!(credit_referral(&mut config, &mut referrer, 20_000).unwrap(), 1_000);
+        // Only what is left under the cap, then nothing
+        assert_eq!(credit_referral(&mut config, &mut referrer, 20_000).unwrap(), 200);
+        assert_eq!(credit_referral(&mut config, &mut referrer, 20_000).unwrap(), 0);
+        assert_eq!(referrer.bonus_tokens, 1_200);
+        assert_eq!(config.total_allocated, 1_200);
+        // Referred purchases keep counting once the cap is reached
+        assert_eq!(referrer.referred_tokens, 60_000);
+        assert_eq!(referrer.referral_count, 3);
+
+        // Lowering the cap below what was already credited takes nothing back
+        config.referral_bonus_cap = 1_000;
+        assert_eq!(credit_referral(&mut config, &mut referrer, 20_000).unwrap(), 0);
+        assert_eq!(referrer.bonus_tokens, 1_200);
+    }
+
+    #[test]
+    fn buyers_cannot_refer_themselves() {
+        let (buyer, beneficiary, referrer) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(check_referrer(None, buyer, beneficiary).is_ok());
+        assert!(check_referrer(Some(referrer), buyer, beneficiary).is_ok());
+        assert!(check_referrer(Some(buyer), buyer, beneficiary).is_err());
+        assert!(check_referrer(Some(beneficiary), buyer, beneficiary).is_err());
+    }
+
+    fn tier(threshold: u64, bonus_bps: u16) -> BonusTier {
+        BonusTier {
+            threshold,
+            bonus_bps,
+        }
+    }
+
+    #[test]
+    fn bonus_tiers_must_ascend_within_limits() {
+        assert!(check_bonus_tiers(&[]).is_ok());
+        assert!(check_bonus_tiers(&[tier(100, 50), tier(1_000, MAX_BONUS_BPS)]).is_ok());
+        assert!(check_bonus_tiers(&[tier(100, MAX_BONUS_BPS + 1)]).is_err());
+        assert!(check_bonus_tiers(&[tier(1_000, 50), tier(100, 100)]).is_err());
+        assert!(check_bonus_tiers(&[tier(100, 50), tier(100, 100)]).is_err());
+
+        let full: Vec<BonusTier> = (1..=MAX_BONUS_TIERS as u64).map(|i| tier(i, 10)).collect();
+        assert!(check_bonus_tiers(&full).is_ok());
+        let too_many: Vec<BonusTier> =
+            (1..=MAX_BONUS_TIERS as u64 + 1).map(|i| tier(i, 10)).collect();
+        assert!(check_bonus_tiers(&too_many).is_err());
+    }
+
+    #[test]
+    fn purchase_bonus_adds_the_volume_and_time_tiers_reached() {
+        let tiers = BonusTiers {
+            // 2% from 1_000 tokens, 5% from 10_000
+            volume_tiers: vec![tier(1_000, 200), tier(10_000, 500)],
+            // 10% in the first hour, 3% in the first day
+            time_tiers: vec![tier(3_600, 1_000), tier(86_400, 300)],
+            bonus_vesting_type: None,
+        };
+        let late = 86_400;
+        assert_eq!(tiers.bonus_for(999, late).unwrap(), 0);
+        assert_eq!(tiers.bonus_for(1_000, late).unwrap(), 20);
+        assert_eq!(tiers.bonus_for(9_999, late).unwrap(), 199);
+        assert_eq!(tiers.bonus_for(10_000, late).unwrap(), 500);
+
+        assert_eq!(tiers.bonus_for(500, 0).unwrap(), 50);
+        assert_eq!(tiers.bonus_for(500, 3_599).unwrap(), 50);
+        assert_eq!(tiers.bonus_for(500, 3_600).unwrap(), 15);
+        assert_eq!(tiers.bonus_for(500, 86_399).unwrap(), 15);
+
+        // Both apply together
+        assert_eq!(tiers.bonus_for(10_000, 0).unwrap(), 1_500);
+    }
+
+    // Prices fall from 1_000 to 400 lamports over a 100 second window
+    const AUCTION_START: u64 = 1_000;
+    const AUCTION_END: u64 = 1_100;
+
+    fn auction(supply: u64) -> Auction {
+        Auction {
+            start_price: 1_000,
+            floor_price: 400,
+            supply,
+            tokens_sold: 0,
+            total_paid: 0,
+            clearing_price: 0,
+            settled: false,
+        }
+    }
+
+    // Place a bid the way bid_auction does, returning the position's record
+    fn place_bid(auction: &mut Auction, time: u64, tokens: u64) -> AuctionBid {
+        let price = auction_price(auction, AUCTION_START, AUCTION_END, time);
+        let (tokens, cost) = auction_fill(auction, tokens, price).unwrap();
+        record_auction_bid(auction, tokens, cost, price).unwrap();
+        AuctionBid {
+            wallet: Pubkey::new_unique(),
+            position_index: 0,
+            tokens,
+            paid: cost,
+            rebate_claimed: false,
+        }
+    }
+
+    // Settle and pay every rebate, checking that the lamports held for bids are all accounted for
+    fn settle_and_refund(auction: &mut Auction, bids: &[AuctionBid]) -> (u64, Vec<u64>) {
+        let deposited: u64 = bids.iter().map(|bid| bid.paid).sum();
+        assert_eq!(auction.total_paid, deposited);
+
+        let proceeds = settle_auction_proceeds(auction).unwrap();
+        assert_eq!(auction.total_paid, deposited - proceeds);
+
+        let rebates: Vec<u64> = bids
+            .iter()
+            .map(|bid| auction_rebate(auction, bid).unwrap())
+            .collect();
+        assert_eq!(rebates.iter().sum::<u64>(), auction.total_paid);
+        assert_eq!(proceeds + rebates.iter().sum::<u64>(), deposited);
+        (proceeds, rebates)
+    }
+
+    #[test]
+    fn auction_price_decays_linearly_to_the_floor() {
+        let auction = auction(100);
+        let price = |time| auction_price(&auction, AUCTION_START, AUCTION_END, time);
+
+        assert_eq!(price(AUCTION_START - 10), 1_000);
+        assert_eq!(price(AUCTION_START), 1_000);
+        assert_eq!(price(AUCTION_START + 25), 850);
+        assert_eq!(price(AUCTION_START + 50), 700);
+        assert_eq!(price(AUCTION_START + 99), 406);
+        assert_eq!(price(AUCTION_END), 400);
+        assert_eq!(price(AUCTION_END + 50), 400);
+        // A zero-length window sells at the floor
+        assert_eq!(auction_price(&auction, AUCTION_START, AUCTION_START, AUCTION_START), 400);
+    }
+
+    #[test]
+    fn auction_sold_out_before_the_floor_clears_at_the_last_price() {
+        let mut auction = auction(100);
+        let bids = vec![
+            place_bid(&mut auction, AUCTION_START + 10, 40), // 940 per token
+            place_bid(&mut auction, AUCTION_START + 30, 50), // 820 per token
+            place_bid(&mut auction, AUCTION_START + 60, 30), // 640 per token, 10 left
+        ];
+        assert_eq!(bids[2].tokens, 10);
+        assert_eq!(bids[2].paid, 6_400);
+        assert_eq!(auction.tokens_sold, auction.supply);
+        assert_eq!(auction.clearing_price, 640);
+
+        let (proceeds, rebates) = settle_and_refund(&mut auction, &bids);
+        assert_eq!(auction.clearing_price, 640);
+        assert_eq!(proceeds, 100 * 640);
+        assert_eq!(rebates, vec![40 * 300, 50 * 180, 0]);
+    }
+
+    #[test]
+    fn unsold_auction_settles_at_the_floor() {
+        let mut auction = auction(100);
+        let bids = vec![
+            place_bid(&mut auction, AUCTION_START, 20),
+            place_bid(&mut auction, AUCTION_START + 75, 30), // 550 per token
+        ];
+        assert_eq!(auction.clearing_price, 0);
+
+        let (proceeds, rebates) = settle_and_refund(&mut auction, &bids);
+        assert!(auction.settled);
+        assert_eq!(auction.clearing_price, 400);
+        assert_eq!(proceeds, 50 * 400);
+        assert_eq!(rebates, vec![20 * 600, 30 * 150]);
+    }
+
+    #[test]
+    fn auction_rebates_drain_the_bid_balance() {
+        // Lamports on the auction account: its rent plus every bid
+        let rent = RENT_MINIMUM;
+        let mut auction = auction(1_000);
+        let bids: Vec<AuctionBid> = (0..10)
+            .map(|i| place_bid(&mut auction, AUCTION_START + i * 7, 37 + i))
+            .collect();
+        let mut balance = rent + bids.iter().map(|bid| bid.paid).sum::<u64>();
+
+        let proceeds = settle_auction_proceeds(&mut auction).unwrap();
+        balance -= proceeds;
+        for bid in &bids {
+            let rebate = auction_rebate(&auction, bid).unwrap();
+            auction.total_paid -= rebate;
+            balance -= rebate;
+        }
+        assert_eq!(auction.total_paid, 0);
+        assert_eq!(balance, rent);
+    }
+
+    #[test]
+    fn auction_fill_is_capped_by_the_unsold_supply() {
+        let mut auction = auction(100);
+        auction.tokens_sold = 95;
+        assert_eq!(auction_fill(&auction, 10, 500).unwrap(), (5, 2_500));
+        assert!(auction_fill(&auction, 5, u64::MAX).is_err());
+    }
+
+    const TOKENS_PER_SOL: u64 = 7_000;
+
+    // A finalized sale over `commitments`, with total_demand computed as finalize_sale does
+    fn finalized_sale(token_cap: u64, commitments: &[u64]) -> ProRataSale {
+        let total_committed = commitments.iter().sum();
+        ProRataSale {
+            token_cap,
+            total_committed,
+            total_demand: committed_tokens(TOKENS_PER_SOL, total_committed).unwrap(),
+            tokens_allocated: 0,
+            finalized: true,
+        }
+    }
+
+    // Settle every commitment, checking the per-commitment and whole-sale invariants.
+    // Returns the (tokens, spent, refund) of each.
+    fn settle_all(sale: &mut ProRataSale, commitments: &[u64]) -> Vec<(u64, u64, u64)> {
+        let settled: Vec<(u64, u64, u64)> = commitments
+            .iter()
+            .map(|&amount| {
+                let (tokens, spent, refund) =
+                    pro_rata_allocation(sale, TOKENS_PER_SOL, amount).unwrap();
+                assert_eq!(spent + refund, amount);
+                // The lamports kept always pay for the tokens allocated
+                assert!(committed_tokens(TOKENS_PER_SOL, spent).unwrap() >= tokens);
+                sale.tokens_allocated += tokens;
+                (tokens, spent, refund)
+            })
+            .collect();
+
+        assert!(sale.tokens_allocated <= sale.token_cap);
+        let returned: u64 = settled.iter().map(|&(_, spent, refund)| spent + refund).sum();
+        assert_eq!(returned, sale.total_committed);
+        settled
+    }
+
+    #[test]
+    fn undersubscribed_sale_fills_every_commitment() {
+        let commitments = [LAMPORTS_PER_SOL, 3 * LAMPORTS_PER_SOL / 2];
+        let mut sale = finalized_sale(1_000_000, &commitments);
+        assert_eq!(
+            settle_all(&mut sale, &commitments),
+            vec![
+                (7_000, LAMPORTS_PER_SOL, 0),
+                (10_500, 3 * LAMPORTS_PER_SOL / 2, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn oversubscribed_sale_scales_down_without_exceeding_the_cap() {
+        // Demand of 7_000 + 9_333 + 14_864 = 31_197 tokens for a cap of 10_000
+        let commitments = [LAMPORTS_PER_SOL, 1_333_333_333, 2_123_456_789];
+        let mut sale = finalized_sale(10_000, &commitments);
+        assert_eq!(sale.total_demand, 31_197);
+
+        let settled = settle_all(&mut sale, &commitments);
+        let tokens: Vec<u64> = settled.iter().map(|&(tokens, _, _)| tokens).collect();
+        assert_eq!(tokens, vec![2_243, 2_991, 4_764]);
+        // Rounding leaves a remainder of the cap unallocated
+        assert_eq!(sale.tokens_allocated, 9_998);
+        // 2_243 tokens cost 320_428_571.4 lamports, rounded up
+        assert_eq!(settled[0].1, 320_428_572);
+    }
+
+    #[test]
+    fn commitment_scaled_to_nothing_is_refunded_in_full() {
+        // 1_000 lamports buy 0.007 tokens, which round down to none
+        let commitments = [1_000, 10 * LAMPORTS_PER_SOL];
+        let mut sale = finalized_sale(100, &commitments);
+        let settled = settle_all(&mut sale, &commitments);
+        assert_eq!(settled[0], (0, 0, 1_000));
+        assert_eq!(settled[1].0, 100);
+    }
+
+    #[test]
+    fn pro_rata_invariants_hold_for_many_commitments() {
+        // Deterministic spread of commitments from dust to a few hundred SOL
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let commitments: Vec<u64> = (0..500)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed % (300 * LAMPORTS_PER_SOL)
+            })
+            .collect();
+
+        for token_cap in [1, 999, 123_456, 5_000_000, u32::MAX as u64] {
+            let mut sale = finalized_sale(token_cap, &commitments);
+            settle_all(&mut sale, &commitments);
+        }
+    }
+}
@@ -35,6 +35,13 @@ describe('vesting_program', () => {
   const usdtTreasury = Keypair.generate();
   const rewardTokenTreasury = Keypair.generate();
 
+  // Investor positions are keyed by wallet and a u32 position index
+  const positionIndexSeed = (index: number) => {
+    const buf = Buffer.alloc(4);
+    buf.writeUInt32LE(index);
+    return buf;
+  };
+
   // Create test investors
   const investorCount = 3;
   const investors = Array(investorCount).fill(0).map(() => ({
@@ -88,7 +95,7 @@ describe('vesting_program', () => {
     const remainingAccounts = await Promise.all(
       investors.map(async (investor) => {
         const [pda] = await PublicKey.findProgramAddressSync(
-          [Buffer.from("investor is my hero"), investor.keypair.publicKey.toBuffer()],
+          [Buffer.from("investor is my hero"), investor.keypair.publicKey.toBuffer(), positionIndexSeed(0)],
           program.programId
         );
         
@@ -119,7 +126,7 @@ describe('vesting_program', () => {
       // Verify each investor was whitelisted correctly
       for (let i = 0; i < investors.length; i++) {
         const [investorPDA] = PublicKey.findProgramAddressSync(
-          [Buffer.from("investor is my hero"), investors[i].keypair.publicKey.toBuffer()],
+          [Buffer.from("investor is my hero"), investors[i].keypair.publicKey.toBuffer(), positionIndexSeed(0)],
           program.programId
         );
         